@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use camel::eval::{self, env, eval, EvalStrategy};
+use camel::parser::Parser;
+
+/// A free head applied to `n` arguments, which is stuck and must be rebuilt
+fn stuck_spine(n: usize) -> String {
+  let args: Vec<String> = (0..n).map(|i| format!("a{}", i)).collect();
+  format!("f {}", args.join(" "))
+}
+
+/// The identity applied to `n` copies of itself, consuming one argument per step
+fn identity_spine(n: usize) -> String {
+  format!("{} y", vec!["(λx.x)"; n].join(" "))
+}
+
+fn spines(c: &mut Criterion) {
+  let mut group = c.benchmark_group("spine");
+  for n in [10, 100, 1000] {
+    for (name, source) in [("stuck", stuck_spine(n)), ("identity", identity_spine(n))] {
+      let term = Rc::new(Parser::new(&source).parse_term().unwrap());
+      group.bench_with_input(BenchmarkId::new(name, n), &term, |b, term| {
+        b.iter(|| eval(Rc::clone(term)))
+      });
+    }
+  }
+  group.finish();
+}
+
+/// The Church numeral `n` applied to the identity, unwinding `n` nested applications
+fn numeral(n: usize) -> String {
+  format!("(λf.λx.{}x{}) (λy.y) z", "f (".repeat(n), ")".repeat(n))
+}
+
+fn engines(c: &mut Criterion) {
+  let mut group = c.benchmark_group("engine");
+  for n in [10, 100, 1000] {
+    let source = numeral(n);
+    let term = Rc::new(Parser::new(&source).parse().unwrap());
+    group.bench_with_input(BenchmarkId::new("substitution", n), &term, |b, term| {
+      b.iter(|| eval::eval_with_strategy(Rc::clone(term), EvalStrategy::CallByValue))
+    });
+    group.bench_with_input(BenchmarkId::new("environment", n), &term, |b, term| {
+      b.iter(|| env::eval(term))
+    });
+    group.bench_with_input(BenchmarkId::new("secd", n), &term, |b, term| {
+      b.iter(|| eval::eval_with_strategy(Rc::clone(term), EvalStrategy::Secd))
+    });
+    group.bench_with_input(BenchmarkId::new("krivine", n), &term, |b, term| {
+      b.iter(|| eval::eval_with_strategy(Rc::clone(term), EvalStrategy::Krivine))
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, spines, engines);
+criterion_main!(benches);
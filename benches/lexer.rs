@@ -0,0 +1,36 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use camel::lexer::Lexer;
+
+/// Definitions and applications repeated until the input reaches `bytes`
+fn corpus(bytes: usize) -> String {
+  const CHUNK: &str =
+    "succ = λn.λf.λx.f (n f x)\nplus = \\m.\\n.m succ n\n(λx.x x) (plus one two)\n";
+  CHUNK.repeat(bytes.div_ceil(CHUNK.len()))
+}
+
+fn lex(c: &mut Criterion) {
+  let mut group = c.benchmark_group("lex");
+  for megabytes in [1, 4] {
+    let input = corpus(megabytes << 20);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_with_input(
+      BenchmarkId::from_parameter(format!("{}MiB", megabytes)),
+      &input,
+      |b, input| {
+        b.iter(|| {
+          let mut lexer = Lexer::new(input);
+          let mut count = 0;
+          while lexer.next_token().is_some() {
+            count += 1;
+          }
+          count
+        })
+      },
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use camel::arena::TermArena;
+use camel::Parser;
+
+/// Lines of terms repeated until there are `lines` of them
+fn corpus(lines: usize) -> Vec<String> {
+  const TERMS: [&str; 3] = [
+    "λn.λf.λx.f (n f x)",
+    "(λx.x x) (λm.λn.m (λn.λf.λx.f (n f x)) n)",
+    "λf.(λx.f (x x)) (λx.f (x x))",
+  ];
+  TERMS
+    .iter()
+    .cycle()
+    .take(lines)
+    .map(|t| t.to_string())
+    .collect()
+}
+
+fn parse(c: &mut Criterion) {
+  let mut group = c.benchmark_group("parse");
+  let lines = corpus(10_000);
+  group.throughput(Throughput::Elements(lines.len() as u64));
+  group.bench_with_input(BenchmarkId::new("rc", lines.len()), &lines, |b, lines| {
+    b.iter(|| {
+      lines
+        .iter()
+        .map(|line| Parser::new(line).parse().map(|term| term.size()))
+        .sum::<Result<usize, _>>()
+    })
+  });
+  group.bench_with_input(
+    BenchmarkId::new("arena", lines.len()),
+    &lines,
+    |b, lines| {
+      let mut arena = TermArena::new();
+      b.iter(|| {
+        arena.clear();
+        for line in lines {
+          arena.parse(line)?;
+        }
+        Ok::<_, anyhow::Error>(arena.len())
+      })
+    },
+  );
+  group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,251 @@
+//! Terms stored side by side in one allocation, for parsing many terms quickly
+//!
+//! A [`TermArena`] keeps every node of the terms parsed into it in a single
+//! vector, with children referred to by [`TermId`] rather than by `Rc`. Parsing
+//! costs one push per node instead of an allocation, and clearing the arena frees
+//! every term at once while keeping the space for the next batch. Terms that are
+//! needed as `Node`s, for example to evaluate them, can be copied out.
+//!
+//! ```
+//! # use camel::arena::{ArenaNode, TermArena};
+//! let corpus = ["λx.x", "f (g x)", "(λx.x x) (λx.x x)"];
+//! let mut arena = TermArena::new();
+//! let mut sizes = Vec::new();
+//! for source in corpus {
+//!   let root = arena.parse(source)?;
+//!   sizes.push(arena.size(root));
+//! }
+//! assert_eq!(sizes, [2, 5, 9]);
+//! assert_eq!(arena.len(), 16);
+//! let root = arena.parse("f x")?;
+//! assert!(matches!(arena[root], ArenaNode::Application { .. }));
+//! assert_eq!(arena.to_node(root).to_string(), "f x");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::ops::Index;
+use std::rc::Rc;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+use crate::parser::{Build, Parser};
+
+/// Where a node is in a `TermArena`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TermId(u32);
+
+impl TermId {
+  pub fn index(self) -> usize {
+    self.0 as usize
+  }
+}
+
+/// A node of a term in a `TermArena`, with its children given by position
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArenaNode<'inp> {
+  Abstraction { param: Cow<'inp, str>, body: TermId },
+  Application { lhs: TermId, rhs: TermId },
+  Identifier { name: Cow<'inp, str> },
+}
+
+/// Nodes of any number of terms, each child stored before its parent
+#[derive(Debug, Clone, Default)]
+pub struct TermArena<'inp> {
+  nodes: Vec<ArenaNode<'inp>>,
+}
+
+impl<'inp> TermArena<'inp> {
+  pub fn new() -> Self {
+    TermArena::default()
+  }
+
+  /// An empty arena with room for `nodes` nodes
+  pub fn with_capacity(nodes: usize) -> Self {
+    TermArena {
+      nodes: Vec::with_capacity(nodes),
+    }
+  }
+
+  /// Parse `source` as a single term into the arena, returning its root
+  ///
+  /// On an error, nodes of the part read before it may be left behind
+  pub fn parse(&mut self, source: &'inp str) -> Result<TermId, anyhow::Error> {
+    Parser::new(source).parse_into(self)
+  }
+
+  /// Add a node, whose children must already be in the arena
+  ///
+  /// # Panics
+  ///
+  /// If the arena already holds `u32::MAX` nodes
+  pub fn alloc(&mut self, node: ArenaNode<'inp>) -> TermId {
+    let id = TermId(u32::try_from(self.nodes.len()).expect("too many nodes for an arena"));
+    self.nodes.push(node);
+    id
+  }
+
+  pub fn get(&self, id: TermId) -> Option<&ArenaNode<'inp>> {
+    self.nodes.get(id.index())
+  }
+
+  /// Number of nodes in the arena, over every term in it
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Remove every term, keeping the space they took for the terms parsed next
+  pub fn clear(&mut self) {
+    self.nodes.clear();
+  }
+
+  /// Number of nodes in the term at `root`
+  pub fn size(&self, root: TermId) -> usize {
+    let mut size = 0;
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+      size += 1;
+      match &self[id] {
+        ArenaNode::Abstraction { body, .. } => stack.push(*body),
+        ArenaNode::Application { lhs, rhs } => stack.extend([*rhs, *lhs]),
+        ArenaNode::Identifier { .. } => (),
+      }
+    }
+    size
+  }
+
+  /// Copy the term at `root` out of the arena
+  ///
+  /// A node reached along several paths in the arena is copied only once, and
+  /// shared in the result
+  pub fn to_node(&self, root: TermId) -> Rc<Node<'inp>> {
+    enum Visit {
+      Enter(TermId),
+      Exit(TermId),
+    }
+
+    let mut built: Vec<Option<Rc<Node<'inp>>>> = vec![None; root.index() + 1];
+    let mut stack = vec![Visit::Enter(root)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Enter(id) if built[id.index()].is_some() => (),
+        Visit::Enter(id) => {
+          stack.push(Visit::Exit(id));
+          match &self[id] {
+            ArenaNode::Abstraction { body, .. } => stack.push(Visit::Enter(*body)),
+            ArenaNode::Application { lhs, rhs } => {
+              stack.push(Visit::Enter(*rhs));
+              stack.push(Visit::Enter(*lhs));
+            }
+            ArenaNode::Identifier { .. } => (),
+          }
+        }
+        Visit::Exit(id) => {
+          let child = |id: &TermId| {
+            let built = built[id.index()]
+              .as_ref()
+              .expect("children are built first");
+            Rc::clone(built)
+          };
+          let node = match &self[id] {
+            ArenaNode::Abstraction { param, body } => Node::Abstraction(Abstraction {
+              param: param.clone(),
+              body: child(body),
+            }),
+            ArenaNode::Application { lhs, rhs } => Node::Application(Application {
+              lhs: child(lhs),
+              rhs: child(rhs),
+            }),
+            ArenaNode::Identifier { name } => Node::Identifier(Identifier { name: name.clone() }),
+          };
+          built[id.index()] = Some(Rc::new(node));
+        }
+      }
+    }
+    built
+      .swap_remove(root.index())
+      .expect("the root is built last")
+  }
+}
+
+impl<'inp> Index<TermId> for TermArena<'inp> {
+  type Output = ArenaNode<'inp>;
+
+  fn index(&self, id: TermId) -> &ArenaNode<'inp> {
+    &self.nodes[id.index()]
+  }
+}
+
+impl<'inp> Build<'inp> for TermArena<'inp> {
+  type Term = TermId;
+
+  fn abstraction(&mut self, param: Cow<'inp, str>, body: TermId) -> TermId {
+    self.alloc(ArenaNode::Abstraction { param, body })
+  }
+
+  fn application(&mut self, lhs: TermId, rhs: TermId) -> TermId {
+    self.alloc(ArenaNode::Application { lhs, rhs })
+  }
+
+  fn identifier(&mut self, name: Cow<'inp, str>) -> TermId {
+    self.alloc(ArenaNode::Identifier { name })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x")]
+  #[case("λx.λy.x y")]
+  #[case("(λx.x x) (λy.y) (f (g z))")]
+  fn parses_like_the_parser(#[case] source: &str) -> Result<(), anyhow::Error> {
+    let mut arena = TermArena::new();
+    arena.parse("padding")?;
+    let root = arena.parse(source)?;
+    let term = Parser::new(source).parse()?;
+    assert_eq!(*arena.to_node(root), term);
+    assert_eq!(arena.size(root), term.size());
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λx.")]
+  #[case("(f x")]
+  #[case("f x)")]
+  fn fails_like_the_parser(#[case] source: &str) {
+    let arena = TermArena::new()
+      .parse(source)
+      .map_err(|err| err.to_string());
+    let parser = Parser::new(source).parse().map_err(|err| err.to_string());
+    assert_eq!(arena.unwrap_err(), parser.unwrap_err());
+  }
+
+  #[test]
+  fn deep_terms() -> Result<(), anyhow::Error> {
+    let source = format!("{}x{}", "(f ".repeat(100_000), ")".repeat(100_000));
+    let mut arena = TermArena::with_capacity(200_001);
+    let root = arena.parse(&source)?;
+    assert_eq!(arena.size(root), 200_001);
+    assert_eq!(arena.to_node(root).size(), 200_001);
+    arena.clear();
+    assert!(arena.is_empty() && arena.get(root).is_none());
+    Ok(())
+  }
+
+  #[test]
+  fn shares_nodes_when_copying_out() {
+    let mut arena = TermArena::new();
+    let x = arena.alloc(ArenaNode::Identifier { name: "x".into() });
+    let root = arena.alloc(ArenaNode::Application { lhs: x, rhs: x });
+    let Node::Application(app) = &*arena.to_node(root) else {
+      panic!("the root is an application");
+    };
+    assert!(Rc::ptr_eq(&app.lhs, &app.rhs));
+  }
+}
@@ -1,12 +1,30 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::rc::Rc;
 
+mod cursor;
+pub mod debruijn;
+mod diff;
+pub mod iter;
+pub mod latex;
+pub mod rewrite;
+#[cfg(feature = "serde")]
+mod serial;
+pub mod visit;
+
+pub use cursor::Cursor;
+pub use diff::{diff, Change, TermDiff};
+
 /// Nodes in the Abstract Syntax Tree
 ///
 /// Application: t1 t2
 /// Abstraction: λx. t1
 /// Identifier:  x
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Node<'inp> {
   Abstraction(Abstraction<'inp>),
   Application(Application<'inp>),
@@ -14,33 +32,565 @@ pub enum Node<'inp> {
 }
 
 /// An abstraction of a lambda function, containing a parameter and a body
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Abstraction<'inp> {
-  pub param: &'inp str,
+  pub param: Cow<'inp, str>,
   pub body: Rc<Node<'inp>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Application<'inp> {
   pub lhs: Rc<Node<'inp>>,
   pub rhs: Rc<Node<'inp>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Identifier<'inp> {
-  pub name: &'inp str,
+  pub name: Cow<'inp, str>,
+}
+
+/// A step from a term down to one of its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+  /// The body of an abstraction
+  Body,
+  /// The function side of an application
+  Lhs,
+  /// The argument side of an application
+  Rhs,
+}
+
+/// The position of a subterm, as the steps taken to reach it from the root
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Path(pub Vec<Direction>);
+
+impl Node<'_> {
+  /// Names of the variables occurring free in the term
+  pub fn free_vars(&self) -> HashSet<&str> {
+    enum Visit<'n> {
+      Enter(&'n Node<'n>),
+      Unbind(&'n str),
+    }
+
+    // number of enclosing binders for each name, walked with an explicit stack
+    let mut bound: HashMap<&str, usize> = HashMap::new();
+    let mut vars = HashSet::new();
+    let mut stack = vec![Visit::Enter(self)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Enter(Node::Abstraction(abs)) => {
+          *bound.entry(abs.param.as_ref()).or_default() += 1;
+          stack.push(Visit::Unbind(abs.param.as_ref()));
+          stack.push(Visit::Enter(&abs.body));
+        }
+        Visit::Enter(Node::Application(app)) => {
+          stack.push(Visit::Enter(&app.rhs));
+          stack.push(Visit::Enter(&app.lhs));
+        }
+        Visit::Enter(Node::Identifier(id)) => {
+          if bound.get(id.name.as_ref()).is_none_or(|&count| count == 0) {
+            vars.insert(id.name.as_ref());
+          }
+        }
+        Visit::Unbind(param) => {
+          *bound.get_mut(param).expect("bound on entry") -= 1;
+        }
+      }
+    }
+    vars
+  }
+
+  /// Names of the variables occurring bound, under an abstraction of their own name
+  ///
+  /// A name can be both bound and free, as `x` is in `x (λx.x)`, while the parameter
+  /// of an abstraction which never uses it is neither
+  pub fn bound_vars(&self) -> HashSet<&str> {
+    enum Visit<'n> {
+      Enter(&'n Node<'n>),
+      Unbind(&'n str),
+    }
+
+    let mut binders = Binders::default();
+    let mut vars = HashSet::new();
+    let mut stack = vec![Visit::Enter(self)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Enter(Node::Abstraction(abs)) => {
+          binders.bind(&abs.param);
+          stack.push(Visit::Unbind(&abs.param));
+          stack.push(Visit::Enter(&abs.body));
+        }
+        Visit::Enter(Node::Application(app)) => {
+          stack.push(Visit::Enter(&app.rhs));
+          stack.push(Visit::Enter(&app.lhs));
+        }
+        Visit::Enter(Node::Identifier(id)) => {
+          if binders.index(&id.name).is_some() {
+            vars.insert(id.name.as_ref());
+          }
+        }
+        Visit::Unbind(param) => binders.unbind(param),
+      }
+    }
+    vars
+  }
+
+  /// Whether the term has no free variables
+  pub fn is_closed(&self) -> bool {
+    self.free_vars().is_empty()
+  }
+
+  /// Positions of every beta redex in the term, outermost first and left before right
+  pub fn redexes(&self) -> Vec<Path> {
+    enum Visit<'n> {
+      Enter(&'n Node<'n>),
+      Descend(Direction),
+      Ascend,
+    }
+
+    let mut redexes = Vec::new();
+    let mut path = Vec::new();
+    let mut stack = vec![Visit::Enter(self)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Enter(node) => {
+          if let Node::Application(app) = node {
+            if let Node::Abstraction(..) = &*app.lhs {
+              redexes.push(Path(path.clone()));
+            }
+          }
+          for direction in [Direction::Rhs, Direction::Lhs, Direction::Body] {
+            if let Some(child) = node.child(direction) {
+              stack.extend([
+                Visit::Ascend,
+                Visit::Enter(child),
+                Visit::Descend(direction),
+              ]);
+            }
+          }
+        }
+        Visit::Descend(direction) => path.push(direction),
+        Visit::Ascend => {
+          path.pop();
+        }
+      }
+    }
+    redexes
+  }
+
+  /// The child reached by taking `direction`, if this node has one there
+  pub fn child(&self, direction: Direction) -> Option<&Rc<Self>> {
+    match (self, direction) {
+      (Node::Abstraction(abs), Direction::Body) => Some(&abs.body),
+      (Node::Application(app), Direction::Lhs) => Some(&app.lhs),
+      (Node::Application(app), Direction::Rhs) => Some(&app.rhs),
+      _ => None,
+    }
+  }
+
+  /// The subterm at `path`, if the path leads anywhere in this term
+  pub fn subterm(&self, path: &Path) -> Option<&Self> {
+    path.0.iter().try_fold(self, |node, &direction| {
+      node.child(direction).map(|child| &**child)
+    })
+  }
+
+  /// Number of nodes in the term
+  pub fn size(&self) -> usize {
+    let mut size = 0;
+    let mut stack = vec![self];
+    while let Some(node) = stack.pop() {
+      size += 1;
+      match node {
+        Node::Abstraction(abs) => stack.push(&abs.body),
+        Node::Application(app) => stack.extend([&*app.lhs, &*app.rhs]),
+        Node::Identifier(..) => (),
+      }
+    }
+    size
+  }
+
+  /// Whether the two terms are the same up to the names of their bound variables,
+  /// so that `λx.x` and `λy.y` are equal but `λx.y` and `λx.z` are not
+  pub fn alpha_eq(&self, other: &Node<'_>) -> bool {
+    enum Visit<'a, 'b> {
+      Compare(&'a Node<'a>, &'b Node<'b>),
+      Unbind(&'a str, &'b str),
+    }
+
+    let mut left = Binders::default();
+    let mut right = Binders::default();
+    let mut stack = vec![Visit::Compare(self, other)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Compare(Node::Abstraction(a), Node::Abstraction(b)) => {
+          left.bind(&a.param);
+          right.bind(&b.param);
+          stack.push(Visit::Unbind(&a.param, &b.param));
+          stack.push(Visit::Compare(&a.body, &b.body));
+        }
+        Visit::Compare(Node::Application(a), Node::Application(b)) => {
+          stack.push(Visit::Compare(&a.rhs, &b.rhs));
+          stack.push(Visit::Compare(&a.lhs, &b.lhs));
+        }
+        Visit::Compare(Node::Identifier(a), Node::Identifier(b)) => {
+          let same = match (left.index(&a.name), right.index(&b.name)) {
+            (Some(i), Some(j)) => i == j,
+            (None, None) => a.name == b.name,
+            _ => false,
+          };
+          if !same {
+            return false;
+          }
+        }
+        Visit::Compare(..) => return false,
+        Visit::Unbind(a, b) => {
+          left.unbind(a);
+          right.unbind(b);
+        }
+      }
+    }
+    true
+  }
+
+  /// A hash of the term which ignores the names of its bound variables, so that
+  /// alpha-equivalent terms hash the same
+  ///
+  /// The hash is the same in every run and on every platform, so it can be stored,
+  /// for example to find duplicates across a corpus of terms. Terms in memory can
+  /// be keyed by alpha-equivalence with `Alpha` instead
+  pub fn alpha_hash(&self) -> u64 {
+    let mut hasher = Fnv::default();
+    hash_nameless(self, &mut hasher);
+    hasher.finish()
+  }
+
+  /// Copy the term into one that owns all of its names, detaching it from the input
+  ///
+  /// The copy is built bottom up with an explicit stack, so deeply nested terms
+  /// cannot overflow the Rust stack
+  pub fn to_static(&self) -> Node<'static> {
+    enum Visit<'n, 'inp> {
+      Enter(&'n Node<'inp>),
+      Exit(&'n Node<'inp>),
+    }
+
+    let mut built: Vec<Rc<Node<'static>>> = Vec::new();
+    let mut stack = vec![Visit::Enter(self)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Enter(node @ Node::Abstraction(abs)) => {
+          stack.push(Visit::Exit(node));
+          stack.push(Visit::Enter(&abs.body));
+        }
+        Visit::Enter(node @ Node::Application(app)) => {
+          stack.push(Visit::Exit(node));
+          stack.push(Visit::Enter(&app.rhs));
+          stack.push(Visit::Enter(&app.lhs));
+        }
+        Visit::Enter(Node::Identifier(id)) => {
+          built.push(Rc::new(Node::Identifier(Identifier {
+            name: Cow::Owned(id.name.to_string()),
+          })));
+        }
+        Visit::Exit(Node::Abstraction(abs)) => {
+          let body = built.pop().expect("the body is built first");
+          built.push(Rc::new(Node::Abstraction(Abstraction {
+            param: Cow::Owned(abs.param.to_string()),
+            body,
+          })));
+        }
+        Visit::Exit(Node::Application(..)) => {
+          let rhs = built.pop().expect("the right side is built first");
+          let lhs = built.pop().expect("the left side is built first");
+          built.push(Rc::new(Node::Application(Application { lhs, rhs })));
+        }
+        Visit::Exit(Node::Identifier(..)) => unreachable!("identifiers are built on entry"),
+      }
+    }
+    let root = built.pop().expect("the root is built last");
+    Rc::into_inner(root).expect("the root is not shared")
+  }
+}
+
+/// The binders in scope during a walk of a term, for finding the de Bruijn index
+/// of each variable
+#[derive(Default)]
+struct Binders<'n> {
+  depth: usize,
+  /// depths of the binders in scope for each name, innermost last
+  scopes: HashMap<&'n str, Vec<usize>>,
+}
+
+impl<'n> Binders<'n> {
+  fn bind(&mut self, param: &'n str) {
+    self.scopes.entry(param).or_default().push(self.depth);
+    self.depth += 1;
+  }
+
+  fn unbind(&mut self, param: &str) {
+    self.scopes.get_mut(param).expect("bound on entry").pop();
+    self.depth -= 1;
+  }
+
+  /// Number of binders between a variable and the one binding it, or `None` if it
+  /// is free
+  fn index(&self, name: &str) -> Option<usize> {
+    let bound = self.scopes.get(name)?.last()?;
+    Some(self.depth - 1 - bound)
+  }
+}
+
+/// A term compared and hashed up to the names of its bound variables, for keeping
+/// alpha-equivalent terms together in sets and maps
+#[derive(Debug, Clone)]
+pub struct Alpha<'inp>(pub Rc<Node<'inp>>);
+
+impl PartialEq for Alpha<'_> {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.alpha_eq(&other.0)
+  }
+}
+
+impl Eq for Alpha<'_> {}
+
+impl Hash for Alpha<'_> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    hash_nameless(&self.0, state);
+  }
+}
+
+/// Feed a term to `state` as if its bound variables were de Bruijn indices, writing
+/// only fixed-width integers and bytes so that the input does not depend on the
+/// platform
+fn hash_nameless<H: Hasher>(node: &Node<'_>, state: &mut H) {
+  enum Visit<'n> {
+    Enter(&'n Node<'n>),
+    Unbind(&'n str),
+  }
+
+  let mut binders = Binders::default();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        state.write_u8(0);
+        binders.bind(&abs.param);
+        stack.push(Visit::Unbind(&abs.param));
+        stack.push(Visit::Enter(&abs.body));
+      }
+      Visit::Enter(Node::Application(app)) => {
+        state.write_u8(1);
+        stack.push(Visit::Enter(&app.rhs));
+        stack.push(Visit::Enter(&app.lhs));
+      }
+      Visit::Enter(Node::Identifier(id)) => match binders.index(&id.name) {
+        Some(index) => {
+          state.write_u8(2);
+          state.write_u64(index as u64);
+        }
+        None => {
+          state.write_u8(3);
+          state.write_u64(id.name.len() as u64);
+          state.write(id.name.as_bytes());
+        }
+      },
+      Visit::Unbind(param) => binders.unbind(param),
+    }
+  }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hashers is fixed,
+/// and so gives the same hash for a term in every run and on every platform
+struct Fnv(u64);
+
+impl Default for Fnv {
+  fn default() -> Self {
+    Fnv(0xcbf2_9ce4_8422_2325)
+  }
+}
+
+impl Hasher for Fnv {
+  fn finish(&self) -> u64 {
+    self.0
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      self.0 ^= u64::from(byte);
+      self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+  }
+}
+
+impl<'inp> Node<'inp> {
+  /// The subterm at `path` as shared with this term, if the path leads anywhere
+  /// in it
+  pub fn at(self: &Rc<Self>, path: &Path) -> Option<&Rc<Self>> {
+    path
+      .0
+      .iter()
+      .try_fold(self, |node, &direction| node.child(direction))
+  }
+
+  /// This term with the subterm at `path` replaced by `with`, or `None` if the
+  /// path leads nowhere in it
+  ///
+  /// Only the nodes along the path are rebuilt, and everything beside it is
+  /// shared with this term
+  pub fn replace_at(self: &Rc<Self>, path: &Path, with: Rc<Self>) -> Option<Rc<Self>> {
+    let mut ancestors = Vec::with_capacity(path.0.len());
+    let mut current = self;
+    for &direction in &path.0 {
+      ancestors.push((current, direction));
+      current = current.child(direction)?;
+    }
+    Some(
+      ancestors
+        .into_iter()
+        .rev()
+        .fold(with, |child, (parent, direction)| {
+          Rc::new(parent.with_child(direction, child))
+        }),
+    )
+  }
+
+  /// A copy of this node with the child in `direction` replaced by `child`
+  ///
+  /// Panics on an identifier, which has no children
+  pub(crate) fn with_child(&self, direction: Direction, child: Rc<Self>) -> Self {
+    match (self, direction) {
+      (Node::Abstraction(abs), _) => Node::Abstraction(Abstraction {
+        param: abs.param.clone(),
+        body: child,
+      }),
+      (Node::Application(app), Direction::Lhs) => Node::Application(Application {
+        lhs: child,
+        rhs: Rc::clone(&app.rhs),
+      }),
+      (Node::Application(app), _) => Node::Application(Application {
+        lhs: Rc::clone(&app.lhs),
+        rhs: child,
+      }),
+      (Node::Identifier(..), _) => unreachable!("identifiers have no children"),
+    }
+  }
+
+  /// Move out every child which this node alone keeps alive, leaving a shared
+  /// placeholder in its place
+  fn take_unshared_children(&mut self, children: &mut Vec<Rc<Node<'inp>>>) {
+    let mut take = |child: &mut Rc<Node<'inp>>| {
+      if Rc::strong_count(child) == 1 {
+        children.push(mem::replace(child, placeholder()));
+      }
+    };
+    match self {
+      Node::Abstraction(abs) => take(&mut abs.body),
+      Node::Application(app) => {
+        take(&mut app.lhs);
+        take(&mut app.rhs);
+      }
+      Node::Identifier(..) => (),
+    }
+  }
+}
+
+/// A leaf to stand in for children that have been moved out of a node being dropped
+fn placeholder<'inp>() -> Rc<Node<'inp>> {
+  thread_local! {
+    static PLACEHOLDER: Rc<Node<'static>> = Rc::new(Node::Identifier(Identifier {
+      name: Cow::Borrowed(""),
+    }));
+  }
+  PLACEHOLDER.try_with(Rc::clone).unwrap_or_else(|_| {
+    Rc::new(Node::Identifier(Identifier {
+      name: Cow::Borrowed(""),
+    }))
+  })
+}
+
+impl Drop for Node<'_> {
+  /// Free the subterms with an explicit stack, as the derived recursive drop would
+  /// overflow the Rust stack on deeply nested terms
+  fn drop(&mut self) {
+    #[cfg(feature = "profile")]
+    crate::profile::node_dropped();
+    let mut children = Vec::new();
+    self.take_unshared_children(&mut children);
+    while let Some(child) = children.pop() {
+      if let Ok(mut node) = Rc::try_unwrap(child) {
+        node.take_unshared_children(&mut children);
+      }
+    }
+  }
+}
+
+impl From<&Node<'_>> for Node<'static> {
+  fn from(node: &Node<'_>) -> Self {
+    node.to_static()
+  }
 }
 
 impl fmt::Display for Node<'_> {
+  /// Written with an explicit stack of what is left to write, so deeply nested
+  /// terms cannot overflow the Rust stack
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    enum Part<'n, 'inp> {
+      Term(&'n Node<'inp>),
+      Text(&'static str),
+    }
+
+    let mut stack = vec![Part::Term(self)];
+    while let Some(part) = stack.pop() {
+      match part {
+        Part::Term(Node::Abstraction(abs)) => {
+          write!(f, "(λ{}. ", abs.param)?;
+          stack.push(Part::Text(")"));
+          stack.push(Part::Term(&abs.body));
+        }
+        Part::Term(Node::Application(app)) => {
+          if let Node::Application(..) = &*app.rhs {
+            stack.push(Part::Text(")"));
+            stack.push(Part::Term(&app.rhs));
+            stack.push(Part::Text(" ("));
+          } else {
+            stack.push(Part::Term(&app.rhs));
+            stack.push(Part::Text(" "));
+          }
+          stack.push(Part::Term(&app.lhs));
+        }
+        Part::Term(Node::Identifier(id)) => f.write_str(&id.name)?,
+        Part::Text(text) => f.write_str(text)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Direction {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      Node::Abstraction(abs) => write!(f, "(λ{}. {})", abs.param, abs.body),
-      Node::Application(app) => write!(f, "{} {}", app.lhs, app.rhs),
-      Node::Identifier(id) => write!(f, "{}", id.name),
+      Direction::Body => write!(f, "body"),
+      Direction::Lhs => write!(f, "lhs"),
+      Direction::Rhs => write!(f, "rhs"),
     }
   }
 }
 
+impl fmt::Display for Path {
+  /// Written as the steps separated by dots, or `root` for the term itself
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let Some((first, rest)) = self.0.split_first() else {
+      return write!(f, "root");
+    };
+    write!(f, "{}", first)?;
+    for direction in rest {
+      write!(f, ".{}", direction)?;
+    }
+    Ok(())
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -50,15 +600,15 @@ mod tests {
   #[case(
     Node::Application(Application {
       lhs: Rc::new(Node::Abstraction(Abstraction {
-        param: "x",
+        param: "x".into(),
         body: Rc::new(Node::Identifier(Identifier {
-          name: "x",
+          name: "x".into(),
         })),
       })),
       rhs: Rc::new(Node::Abstraction(Abstraction {
-        param: "y",
+        param: "y".into(),
         body: Rc::new(Node::Identifier(Identifier {
-          name: "y",
+          name: "y".into(),
         })),
       })),
     }),
@@ -67,4 +617,159 @@ mod tests {
   fn simple_ast(#[case] ast: Node, #[case] expected_str: &str) {
     assert_eq!(ast.to_string(), expected_str);
   }
+
+  #[rstest]
+  #[case(
+    Node::Abstraction(Abstraction {
+      param: "x".into(),
+      body: Rc::new(Node::Identifier(Identifier {
+        name: "x".into(),
+      })),
+    }),
+    &[]
+  )]
+  #[case(
+    Node::Application(Application {
+      lhs: Rc::new(Node::Abstraction(Abstraction {
+        param: "x".into(),
+        body: Rc::new(Node::Application(Application {
+          lhs: Rc::new(Node::Identifier(Identifier {
+            name: "x".into(),
+          })),
+          rhs: Rc::new(Node::Identifier(Identifier {
+            name: "y".into(),
+          })),
+        })),
+      })),
+      rhs: Rc::new(Node::Identifier(Identifier {
+        name: "x".into(),
+      })),
+    }),
+    &["x", "y"]
+  )]
+  fn free_vars(#[case] ast: Node, #[case] expected_vars: &[&str]) {
+    assert_eq!(ast.free_vars(), expected_vars.iter().copied().collect());
+  }
+
+  #[rstest]
+  #[case("λx.x", &[], &["x"])]
+  #[case("x (λx.x)", &["x"], &["x"])]
+  #[case("λx.λy.y z", &["z"], &["y"])]
+  #[case("(λf.f a) (λb.c)", &["a", "c"], &["f"])]
+  fn variable_analysis(
+    #[case] input: &str,
+    #[case] expected_free: &[&str],
+    #[case] expected_bound: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    let term = crate::parser::Parser::new(input).parse()?;
+    assert_eq!(term.free_vars(), expected_free.iter().copied().collect());
+    assert_eq!(term.bound_vars(), expected_bound.iter().copied().collect());
+    assert_eq!(term.is_closed(), expected_free.is_empty());
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(Node::Identifier(Identifier { name: "x".into() }), 1)]
+  #[case(
+    Node::Abstraction(Abstraction {
+      param: "x".into(),
+      body: Rc::new(Node::Application(Application {
+        lhs: Rc::new(Node::Identifier(Identifier { name: "x".into() })),
+        rhs: Rc::new(Node::Identifier(Identifier { name: "y".into() })),
+      })),
+    }),
+    4
+  )]
+  fn size(#[case] ast: Node, #[case] expected_size: usize) {
+    assert_eq!(ast.size(), expected_size);
+  }
+
+  #[rstest]
+  #[case("x", &[])]
+  #[case("(λx.x) y", &["root"])]
+  #[case("(λx.x) ((λy.y) z)", &["root", "rhs"])]
+  #[case("λf.f ((λx.x) a) ((λy.y) b)", &["body.lhs.rhs", "body.rhs"])]
+  #[case("(λx.(λy.y) x) z", &["root", "lhs.body"])]
+  fn redexes(#[case] input: &str, #[case] expected_paths: &[&str]) -> Result<(), anyhow::Error> {
+    let term = crate::parser::Parser::new(input).parse()?;
+    let paths: Vec<_> = term.redexes().iter().map(ToString::to_string).collect();
+    assert_eq!(paths, expected_paths);
+    for path in term.redexes() {
+      assert!(matches!(term.subterm(&path), Some(Node::Application(..))));
+    }
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(Path::default(), Some("z"))]
+  #[case(Path(vec![Direction::Rhs]), Some("(λf. f (λx. x x)) z"))]
+  #[case(Path(vec![Direction::Lhs, Direction::Body, Direction::Rhs]), Some("(λf. f z) y"))]
+  #[case(Path(vec![Direction::Lhs, Direction::Rhs]), None)]
+  #[case(Path(vec![Direction::Rhs, Direction::Lhs]), None)]
+  fn replace_at(#[case] path: Path, #[case] expected: Option<&str>) -> Result<(), anyhow::Error> {
+    let term = Rc::new(crate::parser::Parser::new("(λf.f (λx.x x)) y").parse()?);
+    let with = Rc::new(Node::Identifier(Identifier { name: "z".into() }));
+    let replaced = term.replace_at(&path, with);
+    assert_eq!(replaced.map(|term| term.to_string()).as_deref(), expected);
+    assert_eq!(term.at(&path).map(|at| &**at), term.subterm(&path));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(Path::default(), "root")]
+  #[case(Path(vec![Direction::Rhs]), "rhs")]
+  #[case(Path(vec![Direction::Lhs, Direction::Body, Direction::Rhs]), "lhs.body.rhs")]
+  fn path_display(#[case] path: Path, #[case] expected_str: &str) {
+    assert_eq!(path.to_string(), expected_str);
+  }
+
+  #[rstest]
+  #[case("λx.x", "λy.y", true)]
+  #[case("λx.λy.x y", "λa.λb.a b", true)]
+  #[case("λx.λy.x", "λx.λy.y", false)]
+  #[case("λx.λx.x", "λx.λy.y", true)]
+  #[case("λx.λx.x", "λy.λx.y", false)]
+  #[case("λx.y", "λx.y", true)]
+  #[case("λx.y", "λx.z", false)]
+  #[case("λx.y", "λy.y", false)]
+  #[case("x y", "x y", true)]
+  #[case("λx.x", "x", false)]
+  fn alpha_equivalence(
+    #[case] a: &str,
+    #[case] b: &str,
+    #[case] expected: bool,
+  ) -> Result<(), anyhow::Error> {
+    let a = Rc::new(crate::parser::Parser::new(a).parse()?);
+    let b = Rc::new(crate::parser::Parser::new(b).parse()?);
+    assert_eq!(a.alpha_eq(&b), expected);
+    assert_eq!(b.alpha_eq(&a), expected);
+    assert_eq!(a.alpha_hash() == b.alpha_hash(), expected);
+    let set = HashSet::from([Alpha(a), Alpha(b)]);
+    assert_eq!(set.len(), if expected { 1 } else { 2 });
+    Ok(())
+  }
+
+  #[test]
+  fn alpha_hash_is_fixed() -> Result<(), anyhow::Error> {
+    let term = crate::parser::Parser::new("λx.x y").parse()?;
+    assert_eq!(term.alpha_hash(), 0xc795_ae11_3401_d16b);
+    Ok(())
+  }
+
+  #[test]
+  fn owned_copy() {
+    let input = String::from("x");
+    let borrowed = Node::Abstraction(Abstraction {
+      param: input.as_str().into(),
+      body: Rc::new(Node::Identifier(Identifier {
+        name: input.as_str().into(),
+      })),
+    });
+    let owned: Node<'static> = (&borrowed).into();
+    drop(borrowed);
+    drop(input);
+    assert_eq!(owned.to_string(), "(λx. x)");
+    assert_eq!(owned.clone(), owned);
+    assert_eq!(HashSet::from([owned.clone(), owned]).len(), 1);
+  }
 }
@@ -0,0 +1,153 @@
+//! A zipper over terms, for moving around one and editing it in place
+//!
+//! A [`Cursor`] keeps the subterm in focus together with the ancestors above it,
+//! so that each move and each edit costs the same however large the term is. The
+//! ancestors are only rebuilt on the way back up, and only where something below
+//! them changed.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::ast::Cursor;
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("λx.f x").parse()?);
+//! let mut cursor = Cursor::new(term);
+//! assert!(cursor.down_body() && cursor.down_rhs());
+//! cursor.replace(Rc::new(Parser::new("y").parse()?));
+//! assert_eq!(cursor.root().to_string(), "(λx. f y)");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::mem;
+use std::rc::Rc;
+
+use super::{Direction, Node, Path};
+
+/// A position in a term, from which the term can be navigated and edited
+#[derive(Debug, Clone)]
+pub struct Cursor<'inp> {
+  focus: Rc<Node<'inp>>,
+  /// The nodes above the focus, from the root down, each with the direction taken
+  /// from it. A node here may be out of date below that direction
+  ancestors: Vec<(Rc<Node<'inp>>, Direction)>,
+}
+
+impl<'inp> Cursor<'inp> {
+  /// A cursor at the root of `term`
+  pub fn new(term: Rc<Node<'inp>>) -> Self {
+    Cursor {
+      focus: term,
+      ancestors: Vec::new(),
+    }
+  }
+
+  /// The subterm at the cursor
+  pub fn focus(&self) -> &Rc<Node<'inp>> {
+    &self.focus
+  }
+
+  /// The position of the cursor in the term
+  pub fn path(&self) -> Path {
+    Path(
+      self
+        .ancestors
+        .iter()
+        .map(|&(_, direction)| direction)
+        .collect(),
+    )
+  }
+
+  pub fn is_root(&self) -> bool {
+    self.ancestors.is_empty()
+  }
+
+  /// Move to the child in `direction`, returning whether the focus has one there,
+  /// and staying put if not
+  pub fn down(&mut self, direction: Direction) -> bool {
+    let Some(child) = self.focus.child(direction).map(Rc::clone) else {
+      return false;
+    };
+    let parent = mem::replace(&mut self.focus, child);
+    self.ancestors.push((parent, direction));
+    true
+  }
+
+  pub fn down_body(&mut self) -> bool {
+    self.down(Direction::Body)
+  }
+
+  pub fn down_lhs(&mut self) -> bool {
+    self.down(Direction::Lhs)
+  }
+
+  pub fn down_rhs(&mut self) -> bool {
+    self.down(Direction::Rhs)
+  }
+
+  /// Move to the parent of the focus, returning whether there is one
+  pub fn up(&mut self) -> bool {
+    let Some((parent, direction)) = self.ancestors.pop() else {
+      return false;
+    };
+    let child = mem::replace(&mut self.focus, parent);
+    if self
+      .focus
+      .child(direction)
+      .is_some_and(|old| Rc::ptr_eq(old, &child))
+    {
+      return true;
+    }
+    self.focus = Rc::new(self.focus.with_child(direction, child));
+    true
+  }
+
+  /// Put `node` in place of the focus, returning the subterm it replaced
+  pub fn replace(&mut self, node: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    mem::replace(&mut self.focus, node)
+  }
+
+  /// The whole term, with every edit made through the cursor
+  pub fn root(mut self) -> Rc<Node<'inp>> {
+    while self.up() {}
+    self.focus
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+
+  #[test]
+  fn navigates_and_edits() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x) (f y)").parse()?);
+    let mut cursor = Cursor::new(Rc::clone(&term));
+    assert!(!cursor.down_body());
+    assert!(cursor.down_rhs() && cursor.down_lhs());
+    assert_eq!(cursor.path().to_string(), "rhs.lhs");
+    assert_eq!(cursor.focus().to_string(), "f");
+    assert!(!cursor.down_lhs());
+    cursor.replace(Rc::new(Parser::new("λz.z").parse()?));
+    assert!(cursor.up() && cursor.up() && !cursor.up());
+    assert!(cursor.is_root() && cursor.down_lhs() && cursor.down_body());
+    cursor.replace(Rc::new(Parser::new("g").parse()?));
+    assert_eq!(cursor.root().to_string(), "(λx. g) ((λz. z) y)");
+    assert_eq!(term.to_string(), "(λx. x) (f y)");
+    Ok(())
+  }
+
+  #[test]
+  fn shares_unedited_subterms() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(a b) (c d)").parse()?);
+    let mut cursor = Cursor::new(Rc::clone(&term));
+    cursor.down_lhs();
+    cursor.down_rhs();
+    assert!(Rc::ptr_eq(&cursor.clone().root(), &term));
+    cursor.replace(Rc::new(Parser::new("e").parse()?));
+    let edited = cursor.root();
+    let (Node::Application(before), Node::Application(after)) = (&*term, &*edited) else {
+      panic!("both are applications");
+    };
+    assert!(Rc::ptr_eq(&before.rhs, &after.rhs));
+    Ok(())
+  }
+}
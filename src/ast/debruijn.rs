@@ -0,0 +1,265 @@
+//! Nameless terms, with bound variables written as de Bruijn indices
+//!
+//! A bound variable is the number of binders between it and the binder it refers
+//! to, counting from 0 for the innermost, so that `λx.λy.x` becomes `λ λ 1`. Terms
+//! which differ only in the names of their bound variables have the same nameless
+//! form, which makes comparing and hashing them structural. Free variables keep
+//! their names.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::ast::debruijn::{from_debruijn, to_debruijn};
+//! # use camel::parser::Parser;
+//! let term = Parser::new("λx.λy.x y z").parse()?;
+//! let nameless = to_debruijn(&term);
+//! assert_eq!(nameless.to_string(), "(λ (λ 1 0 z))");
+//! assert_eq!(from_debruijn(&nameless).unwrap().to_string(), "(λx. (λx1. x x1 z))");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+use super::{Abstraction, Application, Binders, Identifier, Node};
+use crate::names::NameSupply;
+
+/// A term in nameless form
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DbNode<'inp> {
+  /// An abstraction, whose parameter is index 0 in its body
+  Abstraction(Rc<DbNode<'inp>>),
+  Application(Rc<DbNode<'inp>>, Rc<DbNode<'inp>>),
+  /// A variable bound by the binder this many binders out from it
+  Bound(usize),
+  Free(Cow<'inp, str>),
+}
+
+/// The nameless form of a term
+pub fn to_debruijn<'inp>(node: &Node<'inp>) -> Rc<DbNode<'inp>> {
+  enum Visit<'n, 'inp> {
+    Enter(&'n Node<'inp>),
+    /// Leave an abstraction, whose body is the top result
+    Abstract(&'n str),
+    /// Leave an application, whose sides are the top two results
+    Apply,
+  }
+
+  let mut binders = Binders::default();
+  let mut results = Vec::new();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        binders.bind(&abs.param);
+        stack.push(Visit::Abstract(&abs.param));
+        stack.push(Visit::Enter(&abs.body));
+      }
+      Visit::Enter(Node::Application(app)) => {
+        stack.push(Visit::Apply);
+        stack.push(Visit::Enter(&app.rhs));
+        stack.push(Visit::Enter(&app.lhs));
+      }
+      Visit::Enter(Node::Identifier(id)) => {
+        results.push(Rc::new(match binders.index(&id.name) {
+          Some(index) => DbNode::Bound(index),
+          None => DbNode::Free(id.name.clone()),
+        }));
+      }
+      Visit::Abstract(param) => {
+        binders.unbind(param);
+        let body = results.pop().expect("the body was entered");
+        results.push(Rc::new(DbNode::Abstraction(body)));
+      }
+      Visit::Apply => {
+        let rhs = results.pop().expect("the argument was entered");
+        let lhs = results.pop().expect("the function was entered");
+        results.push(Rc::new(DbNode::Application(lhs, rhs)));
+      }
+    }
+  }
+  results.pop().expect("the term was entered")
+}
+
+/// A named term for a nameless one, or `None` if an index refers past the outermost
+/// binder
+///
+/// Binders are named `x`, `x1`, `x2` and so on by depth, skipping the names of the
+/// free variables so that none of them is captured
+pub fn from_debruijn<'inp>(node: &DbNode<'inp>) -> Option<Rc<Node<'inp>>> {
+  enum Visit<'n, 'inp> {
+    Enter(&'n DbNode<'inp>),
+    Abstract,
+    Apply,
+  }
+
+  let mut supply = NameSupply::new();
+  let mut stack = vec![node];
+  while let Some(node) = stack.pop() {
+    match node {
+      DbNode::Abstraction(body) => stack.push(body),
+      DbNode::Application(lhs, rhs) => stack.extend([&**lhs, &**rhs]),
+      DbNode::Bound(..) => (),
+      DbNode::Free(name) => supply.reserve(name.as_ref()),
+    }
+  }
+  // the name of the binder at each depth, made up the first time a term reaches it
+  let mut names: Vec<String> = Vec::new();
+  let mut depth = 0;
+  let mut results = Vec::new();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(DbNode::Abstraction(body)) => {
+        if names.len() == depth {
+          names.push(supply.fresh("x"));
+        }
+        depth += 1;
+        stack.push(Visit::Abstract);
+        stack.push(Visit::Enter(body));
+      }
+      Visit::Enter(DbNode::Application(lhs, rhs)) => {
+        stack.push(Visit::Apply);
+        stack.push(Visit::Enter(rhs));
+        stack.push(Visit::Enter(lhs));
+      }
+      Visit::Enter(DbNode::Bound(index)) => {
+        let binder = depth.checked_sub(index + 1)?;
+        results.push(Rc::new(Node::Identifier(Identifier {
+          name: Cow::Owned(names[binder].clone()),
+        })));
+      }
+      Visit::Enter(DbNode::Free(name)) => {
+        results.push(Rc::new(Node::Identifier(Identifier { name: name.clone() })));
+      }
+      Visit::Abstract => {
+        depth -= 1;
+        let body = results.pop().expect("the body was entered");
+        results.push(Rc::new(Node::Abstraction(Abstraction {
+          param: Cow::Owned(names[depth].clone()),
+          body,
+        })));
+      }
+      Visit::Apply => {
+        let rhs = results.pop().expect("the argument was entered");
+        let lhs = results.pop().expect("the function was entered");
+        results.push(Rc::new(Node::Application(Application { lhs, rhs })));
+      }
+    }
+  }
+  results.pop()
+}
+
+impl Drop for DbNode<'_> {
+  /// Free the subterms with an explicit stack, as for `Node`
+  fn drop(&mut self) {
+    let mut children = Vec::new();
+    take_unshared_children(self, &mut children);
+    while let Some(child) = children.pop() {
+      if let Ok(mut node) = Rc::try_unwrap(child) {
+        take_unshared_children(&mut node, &mut children);
+      }
+    }
+  }
+}
+
+/// Move out every child which `node` alone keeps alive, leaving a leaf in its place
+fn take_unshared_children<'inp>(node: &mut DbNode<'inp>, children: &mut Vec<Rc<DbNode<'inp>>>) {
+  let mut take = |child: &mut Rc<DbNode<'inp>>| {
+    if Rc::strong_count(child) == 1 {
+      children.push(mem::replace(child, Rc::new(DbNode::Bound(0))));
+    }
+  };
+  match node {
+    DbNode::Abstraction(body) => take(body),
+    DbNode::Application(lhs, rhs) => {
+      take(lhs);
+      take(rhs);
+    }
+    DbNode::Bound(..) | DbNode::Free(..) => (),
+  }
+}
+
+impl fmt::Display for DbNode<'_> {
+  /// Written with an explicit stack, like `Node`
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    enum Part<'n, 'inp> {
+      Term(&'n DbNode<'inp>),
+      Text(&'static str),
+    }
+
+    let mut stack = vec![Part::Term(self)];
+    while let Some(part) = stack.pop() {
+      match part {
+        Part::Term(DbNode::Abstraction(body)) => {
+          f.write_str("(λ ")?;
+          stack.push(Part::Text(")"));
+          stack.push(Part::Term(body));
+        }
+        Part::Term(DbNode::Application(lhs, rhs)) => {
+          if let DbNode::Application(..) = &**rhs {
+            stack.push(Part::Text(")"));
+            stack.push(Part::Term(rhs));
+            stack.push(Part::Text(" ("));
+          } else {
+            stack.push(Part::Term(rhs));
+            stack.push(Part::Text(" "));
+          }
+          stack.push(Part::Term(lhs));
+        }
+        Part::Term(DbNode::Bound(index)) => write!(f, "{}", index)?,
+        Part::Term(DbNode::Free(name)) => f.write_str(name)?,
+        Part::Text(text) => f.write_str(text)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("λx.x", "(λ 0)")]
+  #[case("λx.λy.x", "(λ (λ 1))")]
+  #[case("λx.λx.x", "(λ (λ 0))")]
+  #[case("λf.λx.f (f x)", "(λ (λ 1 (1 0)))")]
+  #[case("λx.y x", "(λ y 0)")]
+  #[case("x (λx.x) x", "x (λ 0) x")]
+  fn nameless_form(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    let nameless = to_debruijn(&term);
+    assert_eq!(nameless.to_string(), expected);
+    let named = from_debruijn(&nameless).expect("indices are in scope");
+    assert!(named.alpha_eq(&term));
+    assert_eq!(to_debruijn(&named), nameless);
+    Ok(())
+  }
+
+  #[test]
+  fn alpha_equivalent_terms_are_equal() -> Result<(), anyhow::Error> {
+    let a = Parser::new("λx.λy.x y").parse()?;
+    let b = Parser::new("λa.λb.a b").parse()?;
+    assert_eq!(to_debruijn(&a), to_debruijn(&b));
+    Ok(())
+  }
+
+  #[test]
+  fn names_avoid_free_variables() -> Result<(), anyhow::Error> {
+    let term = Parser::new("λa.λb.x x1 a b").parse()?;
+    let named = from_debruijn(&to_debruijn(&term)).expect("indices are in scope");
+    assert_eq!(named.to_string(), "(λx2. (λx3. x x1 x2 x3))");
+    Ok(())
+  }
+
+  #[test]
+  fn unbound_index() {
+    let nameless = DbNode::Abstraction(Rc::new(DbNode::Bound(1)));
+    assert_eq!(from_debruijn(&nameless), None);
+  }
+}
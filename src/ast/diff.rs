@@ -0,0 +1,172 @@
+//! Where two terms differ
+//!
+//! [`diff`] walks two terms side by side and reports the smallest subterms at which
+//! they differ, so that a difference deep inside a term is reported there rather
+//! than at the root. Like [`Node::alpha_eq`], the names of bound variables do not
+//! count as a difference.
+//!
+//! ```
+//! # use camel::ast;
+//! # use camel::parser::Parser;
+//! let before = Parser::new("λf.f (g x) y").parse()?;
+//! let after = Parser::new("λh.h (g z) y").parse()?;
+//! let diff = ast::diff(&before, &after);
+//! assert_eq!(diff.to_string(), "body.lhs.rhs.rhs: x => z");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fmt;
+
+use super::{Binders, Direction, Node, Path};
+
+/// The subterms at which two terms differ, in pre-order
+#[derive(Debug, Clone, Default)]
+pub struct TermDiff<'a, 'b> {
+  pub changes: Vec<Change<'a, 'b>>,
+}
+
+/// A subterm of one term standing where the other has a different one
+#[derive(Debug, Clone)]
+pub struct Change<'a, 'b> {
+  pub path: Path,
+  pub before: &'a Node<'a>,
+  pub after: &'b Node<'b>,
+}
+
+impl TermDiff<'_, '_> {
+  /// Whether the terms are alpha-equivalent
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  pub fn paths(&self) -> impl Iterator<Item = &Path> {
+    self.changes.iter().map(|change| &change.path)
+  }
+}
+
+/// The smallest subterms at which `before` and `after` differ
+///
+/// Where both terms have an abstraction, or both an application, the difference is
+/// looked for in the children. Anywhere else, including an identifier bound in one
+/// term and free or bound elsewhere in the other, the subterms differ as a whole.
+pub fn diff<'a, 'b>(before: &'a Node<'a>, after: &'b Node<'b>) -> TermDiff<'a, 'b> {
+  enum Visit<'a, 'b> {
+    Compare(Option<Direction>, &'a Node<'a>, &'b Node<'b>),
+    /// Leave the pair of nodes last entered, unbinding their parameters if any
+    Up(Option<(&'a str, &'b str)>),
+  }
+
+  let mut left = Binders::default();
+  let mut right = Binders::default();
+  let mut path = Vec::new();
+  let mut changes = Vec::new();
+  let mut stack = vec![Visit::Compare(None, before, after)];
+  while let Some(visit) = stack.pop() {
+    let (a, b) = match visit {
+      Visit::Compare(direction, a, b) => {
+        path.extend(direction);
+        (a, b)
+      }
+      Visit::Up(params) => {
+        if let Some((a, b)) = params {
+          left.unbind(a);
+          right.unbind(b);
+        }
+        path.pop();
+        continue;
+      }
+    };
+    match (a, b) {
+      (Node::Abstraction(a), Node::Abstraction(b)) => {
+        left.bind(&a.param);
+        right.bind(&b.param);
+        stack.push(Visit::Up(Some((&a.param, &b.param))));
+        stack.push(Visit::Compare(Some(Direction::Body), &a.body, &b.body));
+        continue;
+      }
+      (Node::Application(a), Node::Application(b)) => {
+        stack.push(Visit::Up(None));
+        stack.push(Visit::Compare(Some(Direction::Rhs), &a.rhs, &b.rhs));
+        stack.push(Visit::Compare(Some(Direction::Lhs), &a.lhs, &b.lhs));
+        continue;
+      }
+      (Node::Identifier(x), Node::Identifier(y)) => {
+        let same = match (left.index(&x.name), right.index(&y.name)) {
+          (Some(i), Some(j)) => i == j,
+          (None, None) => x.name == y.name,
+          _ => false,
+        };
+        if !same {
+          changes.push(Change {
+            path: Path(path.clone()),
+            before: a,
+            after: b,
+          });
+        }
+      }
+      _ => changes.push(Change {
+        path: Path(path.clone()),
+        before: a,
+        after: b,
+      }),
+    }
+    path.pop();
+  }
+  TermDiff { changes }
+}
+
+impl fmt::Display for Change<'_, '_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {} => {}", self.path, self.before, self.after)
+  }
+}
+
+impl fmt::Display for TermDiff<'_, '_> {
+  /// A line for each change
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (index, change) in self.changes.iter().enumerate() {
+      if index > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{}", change)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("λx.x", "λy.y", &[])]
+  #[case("x", "y", &["root: x => y"])]
+  #[case("f x", "λx.x", &["root: f x => (λx. x)"])]
+  #[case("(a b) (c d)", "(a e) (f d)", &["lhs.rhs: b => e", "rhs.lhs: c => f"])]
+  #[case("λx.λy.x", "λx.λy.y", &["body.body: x => y"])]
+  #[case("λx.x y", "λy.y y", &["body.rhs: y => y"])]
+  #[case("λx.f x", "λx.f (g x)", &["body.rhs: x => g x"])]
+  fn finds_differences(
+    #[case] before: &str,
+    #[case] after: &str,
+    #[case] expected: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    let before = Parser::new(before).parse()?;
+    let after = Parser::new(after).parse()?;
+    let diff = diff(&before, &after);
+    let changes: Vec<_> = diff
+      .changes
+      .iter()
+      .map(|change| change.to_string())
+      .collect();
+    assert_eq!(changes, expected);
+    assert_eq!(diff.is_empty(), before.alpha_eq(&after));
+    for change in &diff.changes {
+      assert_eq!(before.subterm(&change.path), Some(change.before));
+      assert_eq!(after.subterm(&change.path), Some(change.after));
+    }
+    Ok(())
+  }
+}
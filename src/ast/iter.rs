@@ -0,0 +1,113 @@
+//! Iterators over the subterms of a term
+//!
+//! Each iterator keeps its own stack, so they are safe on terms of any depth, and
+//! yields every node of the term including the term itself.
+//!
+//! ```
+//! # use camel::parser::Parser;
+//! let term = Parser::new("(λx.x) y").parse()?;
+//! let pre: Vec<_> = term.subterms().map(|node| node.to_string()).collect();
+//! assert_eq!(pre, ["(λx. x) y", "(λx. x)", "x", "y"]);
+//! let post: Vec<_> = term.subterms_post_order().map(|node| node.to_string()).collect();
+//! assert_eq!(post, ["x", "(λx. x)", "y", "(λx. x) y"]);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use super::Node;
+
+impl<'inp> Node<'inp> {
+  /// Every subterm in pre-order, parents before their children and left before
+  /// right
+  pub fn subterms(&self) -> Subterms<'_, 'inp> {
+    Subterms { stack: vec![self] }
+  }
+
+  /// Every subterm in post-order, children before their parents and left before
+  /// right
+  pub fn subterms_post_order(&self) -> PostOrder<'_, 'inp> {
+    PostOrder {
+      stack: vec![(self, false)],
+    }
+  }
+
+  /// Every beta redex in the term, in pre-order like `subterms`
+  pub fn redex_subterms(&self) -> impl Iterator<Item = &Node<'inp>> {
+    self.subterms().filter(|node| node.is_redex())
+  }
+
+  /// Whether the term is an abstraction applied to an argument
+  pub fn is_redex(&self) -> bool {
+    matches!(self, Node::Application(app) if matches!(*app.lhs, Node::Abstraction(..)))
+  }
+}
+
+/// Pre-order iterator over subterms, from `Node::subterms`
+#[derive(Debug, Clone)]
+pub struct Subterms<'n, 'inp> {
+  stack: Vec<&'n Node<'inp>>,
+}
+
+impl<'n, 'inp> Iterator for Subterms<'n, 'inp> {
+  type Item = &'n Node<'inp>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let node = self.stack.pop()?;
+    match node {
+      Node::Abstraction(abs) => self.stack.push(&abs.body),
+      Node::Application(app) => self.stack.extend([&*app.rhs, &*app.lhs]),
+      Node::Identifier(..) => (),
+    }
+    Some(node)
+  }
+}
+
+/// Post-order iterator over subterms, from `Node::subterms_post_order`
+#[derive(Debug, Clone)]
+pub struct PostOrder<'n, 'inp> {
+  /// Nodes still to yield, each with whether its children have been pushed
+  stack: Vec<(&'n Node<'inp>, bool)>,
+}
+
+impl<'n, 'inp> Iterator for PostOrder<'n, 'inp> {
+  type Item = &'n Node<'inp>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (node, expanded) = self.stack.pop()?;
+      if expanded {
+        return Some(node);
+      }
+      self.stack.push((node, true));
+      match node {
+        Node::Abstraction(abs) => self.stack.push((&abs.body, false)),
+        Node::Application(app) => self.stack.extend([(&*app.rhs, false), (&*app.lhs, false)]),
+        Node::Identifier(..) => (),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", &[], 1)]
+  #[case("(λx.x) ((λy.y) z)", &["(λx. x) ((λy. y) z)", "(λy. y) z"], 7)]
+  #[case("λf.f ((λx.x x) f)", &["(λx. x x) f"], 9)]
+  fn iterates_subterms(
+    #[case] input: &str,
+    #[case] redexes: &[&str],
+    #[case] size: usize,
+  ) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    let found: Vec<_> = term.redex_subterms().map(|node| node.to_string()).collect();
+    assert_eq!(found, redexes);
+    assert_eq!(term.subterms().count(), size);
+    assert_eq!(term.subterms_post_order().count(), size);
+    assert_eq!(term.subterms().count(), term.size());
+    assert_eq!(term.redex_subterms().count(), term.redexes().len());
+    Ok(())
+  }
+}
@@ -0,0 +1,153 @@
+//! Writing terms as LaTeX math, for pasting into papers
+//!
+//! ```
+//! # use camel::ast::latex::Parens;
+//! # use camel::parser::Parser;
+//! let term = Parser::new("(λx.x) (λf.f y)").parse()?;
+//! assert_eq!(term.to_latex(), r"(\lambda x.\, x)\, \lambda f.\, f\, y");
+//! assert_eq!(
+//!   term.to_latex_with(Parens::Full),
+//!   r"((\lambda x.\, x)\, (\lambda f.\, (f\, y)))"
+//! );
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use super::Node;
+
+/// Where to put parentheses when writing a term
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parens {
+  /// Only where they are needed, with the body of an abstraction reaching as far
+  /// right as it can
+  #[default]
+  Minimal,
+  /// Around every abstraction, as camel writes terms itself
+  Binders,
+  /// Around every abstraction and application
+  Full,
+}
+
+impl Node<'_> {
+  /// The term as LaTeX math, with the fewest parentheses that keep it unambiguous
+  pub fn to_latex(&self) -> String {
+    self.to_latex_with(Parens::default())
+  }
+
+  pub fn to_latex_with(&self, parens: Parens) -> String {
+    enum Visit<'n> {
+      /// A subterm, whether it is the argument of an application, and whether it
+      /// ends the group of parentheses it is in, so that an abstraction there can go
+      /// without
+      Enter(&'n Node<'n>, bool, bool),
+      Text(&'static str),
+    }
+
+    let mut latex = String::new();
+    let mut stack = vec![Visit::Enter(self, false, true)];
+    while let Some(visit) = stack.pop() {
+      let (node, argument, last) = match visit {
+        Visit::Enter(node, argument, last) => (node, argument, last),
+        Visit::Text(text) => {
+          latex.push_str(text);
+          continue;
+        }
+      };
+      let wrap = match (node, parens) {
+        (Node::Identifier(..), _) => false,
+        (_, Parens::Full) => true,
+        (Node::Abstraction(..), Parens::Binders) => true,
+        (Node::Abstraction(..), _) => !last,
+        (_, _) => argument,
+      };
+      if wrap {
+        latex.push('(');
+        stack.push(Visit::Text(")"));
+      }
+      match node {
+        Node::Abstraction(abs) => {
+          latex.push_str(r"\lambda ");
+          push_name(&mut latex, &abs.param);
+          latex.push_str(r".\, ");
+          stack.push(Visit::Enter(&abs.body, false, wrap || last));
+        }
+        Node::Application(app) => {
+          stack.push(Visit::Enter(&app.rhs, true, wrap || last));
+          stack.push(Visit::Text(r"\, "));
+          stack.push(Visit::Enter(&app.lhs, false, false));
+        }
+        Node::Identifier(id) => push_name(&mut latex, &id.name),
+      }
+    }
+    latex
+  }
+}
+
+/// Write a name in math mode: a letter on its own, with any digits after it as a
+/// subscript, and anything longer set as a word with `\mathit`
+fn push_name(latex: &mut String, name: &str) {
+  let digits = name.trim_start_matches(|c: char| !c.is_ascii_digit());
+  let stem = &name[..name.len() - digits.len()];
+  let single = stem.len() == 1 && stem.chars().all(|c| c.is_ascii_alphabetic());
+  if single && digits.chars().all(|c| c.is_ascii_digit()) {
+    latex.push_str(stem);
+    if !digits.is_empty() {
+      latex.push_str("_{");
+      latex.push_str(digits);
+      latex.push('}');
+    }
+    return;
+  }
+  latex.push_str(r"\mathit{");
+  for c in name.chars() {
+    match c {
+      '\\' => latex.push_str(r"\textbackslash{}"),
+      '~' => latex.push_str(r"\textasciitilde{}"),
+      '^' => latex.push_str(r"\textasciicircum{}"),
+      '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+        latex.push('\\');
+        latex.push(c);
+      }
+      c => latex.push(c),
+    }
+  }
+  latex.push('}');
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", Parens::Minimal, "x")]
+  #[case("λx.λy.x", Parens::Minimal, r"\lambda x.\, \lambda y.\, x")]
+  #[case("λx.λy.x", Parens::Binders, r"(\lambda x.\, (\lambda y.\, x))")]
+  #[case("f (g x) y", Parens::Minimal, r"f\, (g\, x)\, y")]
+  #[case("f (g x) y", Parens::Full, r"((f\, (g\, x))\, y)")]
+  #[case("(λx.x) y", Parens::Minimal, r"(\lambda x.\, x)\, y")]
+  #[case("f (λx.x) y", Parens::Minimal, r"f\, (\lambda x.\, x)\, y")]
+  #[case("f (g (λx.x))", Parens::Minimal, r"f\, (g\, \lambda x.\, x)")]
+  #[case("λx.f (λy.y)", Parens::Minimal, r"\lambda x.\, f\, \lambda y.\, y")]
+  #[case("λx.f (λy.y)", Parens::Binders, r"(\lambda x.\, f\, (\lambda y.\, y))")]
+  #[case("x12 foo", Parens::Minimal, r"x_{12}\, \mathit{foo}")]
+  fn writes_latex(
+    #[case] input: &str,
+    #[case] parens: Parens,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(term.to_latex_with(parens), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x1y", r"\mathit{x1y}")]
+  #[case("a_b", r"\mathit{a\_b}")]
+  #[case(r"%\", r"\mathit{\%\textbackslash{}}")]
+  fn escapes_names(#[case] name: &str, #[case] expected: &str) {
+    let mut latex = String::new();
+    push_name(&mut latex, name);
+    assert_eq!(latex, expected);
+  }
+}
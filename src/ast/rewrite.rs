@@ -0,0 +1,210 @@
+//! Transforming terms by rewriting only the nodes that change
+//!
+//! A [`Rewriter`] overrides only the methods for the nodes it wants to replace, and
+//! [`rewrite`] rebuilds the rest of the term around the replacements. Subterms in
+//! which nothing was replaced are shared with the original term rather than
+//! copied. Like [`walk`](super::visit::walk), the rewrite keeps its own stack.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::ast::rewrite::{rewrite, Rewriter};
+//! # use camel::ast::{Application, Node};
+//! # use camel::parser::Parser;
+//! /// Drops applications of the identity function, `(λx.x) M` to `M`
+//! struct DropIdentity;
+//!
+//! impl<'inp> Rewriter<'inp> for DropIdentity {
+//!   fn rewrite_application(&mut self, app: &Application<'inp>) -> Option<Rc<Node<'inp>>> {
+//!     match &*app.lhs {
+//!       Node::Abstraction(abs) if matches!(&*abs.body, Node::Identifier(id) if id.name == abs.param) => {
+//!         Some(Rc::clone(&app.rhs))
+//!       }
+//!       _ => None,
+//!     }
+//!   }
+//! }
+//!
+//! let term = Rc::new(Parser::new("λy.(λx.x) ((λx.x) y)").parse()?);
+//! assert_eq!(rewrite(&term, &mut DropIdentity).to_string(), "(λy. y)");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::rc::Rc;
+
+use super::{Abstraction, Application, Identifier, Node};
+
+/// When a rewriter is asked about a node, relative to its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+  /// After the children have been rewritten, so a rewriter sees the new children.
+  /// A replacement is final, and is not rewritten again
+  #[default]
+  BottomUp,
+  /// Before the children, so a rewriter sees the original node. The children of a
+  /// replacement are rewritten in turn, though the replacement itself is not
+  TopDown,
+}
+
+/// Replacements for nodes, where `None` keeps the node as it is
+pub trait Rewriter<'inp> {
+  fn order(&self) -> Order {
+    Order::BottomUp
+  }
+
+  fn rewrite_abstraction(&mut self, _abs: &Abstraction<'inp>) -> Option<Rc<Node<'inp>>> {
+    None
+  }
+
+  fn rewrite_application(&mut self, _app: &Application<'inp>) -> Option<Rc<Node<'inp>>> {
+    None
+  }
+
+  fn rewrite_identifier(&mut self, _id: &Identifier<'inp>) -> Option<Rc<Node<'inp>>> {
+    None
+  }
+}
+
+/// `node` with the replacements from `rewriter` made throughout
+pub fn rewrite<'inp>(node: &Rc<Node<'inp>>, rewriter: &mut impl Rewriter<'inp>) -> Rc<Node<'inp>> {
+  enum Visit<'inp> {
+    Enter(Rc<Node<'inp>>),
+    /// Leave a node with children, which are the top results
+    Leave(Rc<Node<'inp>>),
+  }
+
+  let order = rewriter.order();
+  let mut results = Vec::new();
+  let mut stack = vec![Visit::Enter(Rc::clone(node))];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(node) => {
+        let node = match order {
+          Order::TopDown => replace(node, rewriter),
+          Order::BottomUp => node,
+        };
+        match &*node {
+          Node::Abstraction(abs) => {
+            let body = Rc::clone(&abs.body);
+            stack.push(Visit::Leave(node));
+            stack.push(Visit::Enter(body));
+          }
+          Node::Application(app) => {
+            let (lhs, rhs) = (Rc::clone(&app.lhs), Rc::clone(&app.rhs));
+            stack.push(Visit::Leave(node));
+            stack.push(Visit::Enter(rhs));
+            stack.push(Visit::Enter(lhs));
+          }
+          Node::Identifier(..) => results.push(match order {
+            Order::TopDown => node,
+            Order::BottomUp => replace(node, rewriter),
+          }),
+        }
+      }
+      Visit::Leave(node) => {
+        let rebuilt = match &*node {
+          Node::Abstraction(abs) => {
+            let body = results.pop().expect("the body was entered");
+            if Rc::ptr_eq(&body, &abs.body) {
+              Rc::clone(&node)
+            } else {
+              Rc::new(Node::Abstraction(Abstraction {
+                param: abs.param.clone(),
+                body,
+              }))
+            }
+          }
+          Node::Application(app) => {
+            let rhs = results.pop().expect("the argument was entered");
+            let lhs = results.pop().expect("the function was entered");
+            if Rc::ptr_eq(&lhs, &app.lhs) && Rc::ptr_eq(&rhs, &app.rhs) {
+              Rc::clone(&node)
+            } else {
+              Rc::new(Node::Application(Application { lhs, rhs }))
+            }
+          }
+          Node::Identifier(..) => unreachable!("identifiers have no children"),
+        };
+        results.push(match order {
+          Order::TopDown => rebuilt,
+          Order::BottomUp => replace(rebuilt, rewriter),
+        });
+      }
+    }
+  }
+  results.pop().expect("the term was entered")
+}
+
+/// The replacement `rewriter` gives for `node`, or `node` itself if there is none
+fn replace<'inp>(node: Rc<Node<'inp>>, rewriter: &mut impl Rewriter<'inp>) -> Rc<Node<'inp>> {
+  let replacement = match &*node {
+    Node::Abstraction(abs) => rewriter.rewrite_abstraction(abs),
+    Node::Application(app) => rewriter.rewrite_application(app),
+    Node::Identifier(id) => rewriter.rewrite_identifier(id),
+  };
+  replacement.unwrap_or(node)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::borrow::Cow;
+
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  /// Replaces `a` with `b`, and applications of `b` to anything with `b` alone
+  struct Collapse(Order);
+
+  impl<'inp> Rewriter<'inp> for Collapse {
+    fn order(&self) -> Order {
+      self.0
+    }
+
+    fn rewrite_application(&mut self, app: &Application<'inp>) -> Option<Rc<Node<'inp>>> {
+      match &*app.lhs {
+        Node::Identifier(id) if id.name == "b" => Some(Rc::clone(&app.lhs)),
+        _ => None,
+      }
+    }
+
+    fn rewrite_identifier(&mut self, id: &Identifier<'inp>) -> Option<Rc<Node<'inp>>> {
+      (id.name == "a").then(|| {
+        Rc::new(Node::Identifier(Identifier {
+          name: Cow::Borrowed("b"),
+        }))
+      })
+    }
+  }
+
+  #[rstest]
+  #[case(Order::BottomUp, "a c", "b")]
+  #[case(Order::TopDown, "a c", "b c")]
+  #[case(Order::BottomUp, "λx.b (a c)", "(λx. b)")]
+  #[case(Order::TopDown, "λx.b (a c)", "(λx. b)")]
+  #[case(Order::TopDown, "λx.a (a c)", "(λx. b (b c))")]
+  fn rewrites_in_order(
+    #[case] order: Order,
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(rewrite(&term, &mut Collapse(order)).to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn shares_unchanged_subterms() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x y) a").parse()?);
+    let rewritten = rewrite(&term, &mut Collapse(Order::BottomUp));
+    let (Node::Application(before), Node::Application(after)) = (&*term, &*rewritten) else {
+      panic!("both are applications");
+    };
+    assert!(Rc::ptr_eq(&before.lhs, &after.lhs));
+    let untouched = Rc::new(Parser::new("λx.x y").parse()?);
+    assert!(Rc::ptr_eq(
+      &rewrite(&untouched, &mut Collapse(Order::TopDown)),
+      &untouched
+    ));
+    Ok(())
+  }
+}
@@ -0,0 +1,130 @@
+//! Serde support for terms, behind the `serde` feature
+//!
+//! A term is written as a flat sequence of its nodes in prefix order, each tagged
+//! with its kind, rather than as nested objects. Formats then never nest deeper
+//! than one level, so terms of any depth can be written and read back, and neither
+//! direction recurses. The representation is stable: a term written by one version
+//! of camel reads back in any later one.
+//!
+//! | `kind`          | other fields | followed by            |
+//! |-----------------|--------------|------------------------|
+//! | `"abstraction"` | `param`      | the body               |
+//! | `"application"` |              | the function, then the argument |
+//! | `"identifier"`  | `name`       |                        |
+//!
+//! ```
+//! # use camel::parser::Parser;
+//! # use camel::Node;
+//! let term = Parser::new("λx.f x").parse()?;
+//! let json = serde_json::to_string(&term)?;
+//! assert_eq!(
+//!   json,
+//!   r#"[{"kind":"abstraction","param":"x"},{"kind":"application"},{"kind":"identifier","name":"f"},{"kind":"identifier","name":"x"}]"#
+//! );
+//! assert_eq!(serde_json::from_str::<Node>(&json)?, term);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Abstraction, Application, Identifier, Node};
+
+/// A single node, without its children
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry<'a> {
+  Abstraction {
+    #[serde(borrow)]
+    param: Cow<'a, str>,
+  },
+  Application,
+  Identifier {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+  },
+}
+
+impl Serialize for Node<'_> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(self.subterms().map(|node| match node {
+      Node::Abstraction(abs) => Entry::Abstraction {
+        param: Cow::Borrowed(&abs.param),
+      },
+      Node::Application(..) => Entry::Application,
+      Node::Identifier(id) => Entry::Identifier {
+        name: Cow::Borrowed(&id.name),
+      },
+    }))
+  }
+}
+
+impl<'de: 'inp, 'inp> Deserialize<'de> for Node<'inp> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let entries = Vec::<Entry<'de>>::deserialize(deserializer)?;
+    // read backwards, every subterm is complete before the node it belongs to, with
+    // the function of an application above its argument
+    let mut done: Vec<Rc<Node<'inp>>> = Vec::new();
+    let missing = || D::Error::custom("node is missing a subterm");
+    for entry in entries.into_iter().rev() {
+      let node = match entry {
+        Entry::Abstraction { param } => Node::Abstraction(Abstraction {
+          param,
+          body: done.pop().ok_or_else(missing)?,
+        }),
+        Entry::Application => {
+          let lhs = done.pop().ok_or_else(missing)?;
+          let rhs = done.pop().ok_or_else(missing)?;
+          Node::Application(Application { lhs, rhs })
+        }
+        Entry::Identifier { name } => Node::Identifier(Identifier { name }),
+      };
+      done.push(Rc::new(node));
+    }
+    match done.pop() {
+      Some(term) if done.is_empty() => Ok(Rc::unwrap_or_clone(term)),
+      Some(..) => Err(D::Error::custom("more than one term")),
+      None => Err(D::Error::invalid_length(0, &"at least one node")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x")]
+  #[case("λx.λy.x (f y)")]
+  #[case("(λx.x x) (λx.x x)")]
+  #[case(&format!("{}x", "λx.".repeat(100_000)))]
+  fn round_trips(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let json = serde_json::to_string(&term)?;
+    // derived equality recurses, so deep terms are compared by writing them again
+    let back: Rc<Node> = serde_json::from_str(&json)?;
+    assert_eq!(serde_json::to_string(&back)?, json);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("[]", "invalid length 0, expected at least one node")]
+  #[case(
+    r#"[{"kind":"application"},{"kind":"identifier","name":"f"}]"#,
+    "node is missing a subterm"
+  )]
+  #[case(
+    r#"[{"kind":"identifier","name":"f"},{"kind":"identifier","name":"x"}]"#,
+    "more than one term"
+  )]
+  #[case(r#"[{"kind":"lambda","param":"x"}]"#, "unknown variant `lambda`")]
+  fn rejects_malformed(#[case] json: &str, #[case] expected: &str) {
+    let err = serde_json::from_str::<Node>(json).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err);
+  }
+}
@@ -0,0 +1,213 @@
+//! Traversing terms without matching on every kind of node
+//!
+//! A [`Visitor`] overrides only the methods for the nodes it cares about, and
+//! [`walk`] calls them in pre-order, with a matching `leave` call once all of a
+//! node's children have been visited. Any `visit` method can return
+//! [`Walk::Skip`] to keep the walk out of that node's children. The walk keeps its
+//! own stack, so it is safe on terms of any depth.
+//!
+//! ```
+//! # use camel::ast::visit::{walk, Visitor, Walk};
+//! # use camel::ast::Identifier;
+//! # use camel::parser::Parser;
+//! /// Counts occurrences of variables
+//! struct Count(usize);
+//!
+//! impl<'n, 'inp> Visitor<'n, 'inp> for Count {
+//!   fn visit_identifier(&mut self, _: &'n Identifier<'inp>) {
+//!     self.0 += 1;
+//!   }
+//! }
+//!
+//! let term = Parser::new("λf.λx.f (f x)").parse()?;
+//! let mut count = Count(0);
+//! walk(&term, &mut count);
+//! assert_eq!(count.0, 3);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! [`VisitorMut`] and [`walk_mut`] do the same with mutable access, copying any
+//! subterm shared with another term before it is handed out.
+
+use std::rc::Rc;
+
+use super::{Abstraction, Application, Identifier, Node};
+
+/// Whether a walk goes on into the children of the node just visited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Walk {
+  #[default]
+  Continue,
+  Skip,
+}
+
+/// Callbacks for the nodes of a term, in the order `walk` reaches them
+pub trait Visitor<'n, 'inp> {
+  fn visit_abstraction(&mut self, _abs: &'n Abstraction<'inp>) -> Walk {
+    Walk::Continue
+  }
+
+  /// Called after the body of an abstraction, unless the walk skipped it
+  fn leave_abstraction(&mut self, _abs: &'n Abstraction<'inp>) {}
+
+  fn visit_application(&mut self, _app: &'n Application<'inp>) -> Walk {
+    Walk::Continue
+  }
+
+  /// Called after both sides of an application, unless the walk skipped them
+  fn leave_application(&mut self, _app: &'n Application<'inp>) {}
+
+  fn visit_identifier(&mut self, _id: &'n Identifier<'inp>) {}
+}
+
+/// Visit every node of `node` in pre-order, left before right
+pub fn walk<'n, 'inp>(node: &'n Node<'inp>, visitor: &mut impl Visitor<'n, 'inp>) {
+  enum Visit<'n, 'inp> {
+    Enter(&'n Node<'inp>),
+    LeaveAbstraction(&'n Abstraction<'inp>),
+    LeaveApplication(&'n Application<'inp>),
+  }
+
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        if visitor.visit_abstraction(abs) == Walk::Continue {
+          stack.push(Visit::LeaveAbstraction(abs));
+          stack.push(Visit::Enter(&abs.body));
+        }
+      }
+      Visit::Enter(Node::Application(app)) => {
+        if visitor.visit_application(app) == Walk::Continue {
+          stack.push(Visit::LeaveApplication(app));
+          stack.push(Visit::Enter(&app.rhs));
+          stack.push(Visit::Enter(&app.lhs));
+        }
+      }
+      Visit::Enter(Node::Identifier(id)) => visitor.visit_identifier(id),
+      Visit::LeaveAbstraction(abs) => visitor.leave_abstraction(abs),
+      Visit::LeaveApplication(app) => visitor.leave_application(app),
+    }
+  }
+}
+
+/// Callbacks with mutable access to the nodes of a term, in the order `walk_mut`
+/// reaches them
+///
+/// Changes to a node are made before its children are reached, so a visitor sees
+/// the children a node has after it was changed. There are no `leave` calls, as a
+/// node cannot be handed out again while its children are borrowed
+pub trait VisitorMut<'inp> {
+  fn visit_abstraction_mut(&mut self, _abs: &mut Abstraction<'inp>) -> Walk {
+    Walk::Continue
+  }
+
+  fn visit_application_mut(&mut self, _app: &mut Application<'inp>) -> Walk {
+    Walk::Continue
+  }
+
+  fn visit_identifier_mut(&mut self, _id: &mut Identifier<'inp>) {}
+}
+
+/// Visit every node of `node` in pre-order with mutable access, copying each
+/// subterm the walk enters which is shared with another term, so that only this
+/// term changes
+pub fn walk_mut<'inp>(node: &mut Rc<Node<'inp>>, visitor: &mut impl VisitorMut<'inp>) {
+  let mut stack = vec![Rc::make_mut(node)];
+  while let Some(node) = stack.pop() {
+    match node {
+      Node::Abstraction(abs) => {
+        if visitor.visit_abstraction_mut(abs) == Walk::Continue {
+          stack.push(Rc::make_mut(&mut abs.body));
+        }
+      }
+      Node::Application(app) => {
+        if visitor.visit_application_mut(app) == Walk::Continue {
+          let Application { lhs, rhs } = app;
+          stack.push(Rc::make_mut(rhs));
+          stack.push(Rc::make_mut(lhs));
+        }
+      }
+      Node::Identifier(id) => visitor.visit_identifier_mut(id),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::borrow::Cow;
+
+  use super::*;
+  use crate::parser::Parser;
+
+  /// Records the order nodes are reached in, skipping the bodies of `λskip`
+  #[derive(Default)]
+  struct Order(Vec<String>);
+
+  impl<'n, 'inp> Visitor<'n, 'inp> for Order {
+    fn visit_abstraction(&mut self, abs: &'n Abstraction<'inp>) -> Walk {
+      self.0.push(format!("λ{}", abs.param));
+      match abs.param.as_ref() {
+        "skip" => Walk::Skip,
+        _ => Walk::Continue,
+      }
+    }
+
+    fn leave_abstraction(&mut self, abs: &'n Abstraction<'inp>) {
+      self.0.push(format!("/λ{}", abs.param));
+    }
+
+    fn visit_application(&mut self, _: &'n Application<'inp>) -> Walk {
+      self.0.push("@".to_string());
+      Walk::Continue
+    }
+
+    fn leave_application(&mut self, _: &'n Application<'inp>) {
+      self.0.push("/@".to_string());
+    }
+
+    fn visit_identifier(&mut self, id: &'n Identifier<'inp>) {
+      self.0.push(id.name.to_string());
+    }
+  }
+
+  #[test]
+  fn visits_in_pre_order() -> Result<(), anyhow::Error> {
+    let term = Parser::new("λx.x (λskip.y) z").parse()?;
+    let mut order = Order::default();
+    walk(&term, &mut order);
+    assert_eq!(
+      order.0,
+      ["λx", "@", "@", "x", "λskip", "/@", "z", "/@", "/λx"]
+    );
+    Ok(())
+  }
+
+  /// Renames every variable named `x` to `y`, bound or not
+  struct Rename;
+
+  impl<'inp> VisitorMut<'inp> for Rename {
+    fn visit_abstraction_mut(&mut self, abs: &mut Abstraction<'inp>) -> Walk {
+      if abs.param == "x" {
+        abs.param = Cow::Borrowed("y");
+      }
+      Walk::Continue
+    }
+
+    fn visit_identifier_mut(&mut self, id: &mut Identifier<'inp>) {
+      if id.name == "x" {
+        id.name = Cow::Borrowed("y");
+      }
+    }
+  }
+
+  #[test]
+  fn mutates_without_touching_shared_terms() -> Result<(), anyhow::Error> {
+    let original = Rc::new(Parser::new("λx.x z x").parse()?);
+    let mut term = Rc::clone(&original);
+    walk_mut(&mut term, &mut Rename);
+    assert_eq!(term.to_string(), "(λy. y z y)");
+    assert_eq!(original.to_string(), "(λx. x z x)");
+    Ok(())
+  }
+}
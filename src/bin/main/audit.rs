@@ -0,0 +1,82 @@
+//! Checks that the interpreter itself behaves the same from one run to the next
+
+use std::rc::Rc;
+use std::thread;
+
+use anyhow::{bail, Context};
+
+use camel::{CancelToken, Evaluator, Parser as TermParser};
+
+/// Evaluations of the same source made on the calling thread, each of which is
+/// expected to print identically
+const LOCAL_RUNS: usize = 2;
+
+/// Parse and evaluate `source` several times on this thread and once on another,
+/// returning the printed normal form if every run printed the same bytes
+pub fn determinism(source: &str, cancel: &CancelToken) -> Result<String, anyhow::Error> {
+  let mut runs = Vec::new();
+  for run in 0..LOCAL_RUNS {
+    runs.push((
+      format!("run {} on the main thread", run + 1),
+      normalize(source, cancel)?,
+    ));
+  }
+  let other = thread::scope(|scope| {
+    thread::Builder::new()
+      .spawn_scoped(scope, || normalize(source, cancel))
+      .context("failed to start the audit thread")?
+      .join()
+      .unwrap_or_else(|_| bail!("the audit thread panicked"))
+  })?;
+  runs.push(("a run on another thread".to_string(), other));
+
+  let (_, expected) = &runs[0];
+  if runs.iter().all(|(_, output)| output == expected) {
+    return Ok(expected.clone());
+  }
+  let report: Vec<_> = runs
+    .iter()
+    .map(|(name, output)| format!("{}: {}", name, output))
+    .collect();
+  bail!("evaluation is not deterministic\n{}", report.join("\n"))
+}
+
+fn normalize(source: &str, cancel: &CancelToken) -> Result<String, anyhow::Error> {
+  let term = TermParser::new(source).parse()?;
+  let normal = Evaluator::default()
+    .with_cancel(cancel)
+    .eval(Rc::new(term))?;
+  Ok(normal.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("(λx.λy.x) a b", Ok("a"))]
+  #[case(
+    "λx.",
+    Err("Unexpected end of input, expected one of '(', lambda, identifier")
+  )]
+  fn audits_determinism(#[case] source: &str, #[case] expected: Result<&str, &str>) {
+    let result = determinism(source, &CancelToken::new());
+    assert_eq!(
+      result.map_err(|err| err.to_string()),
+      expected.map(str::to_string).map_err(str::to_string)
+    );
+  }
+
+  #[test]
+  fn deep_terms_on_another_thread() -> Result<(), anyhow::Error> {
+    let source = format!(
+      "(λx.x) ({}z{})",
+      "λx.y (".repeat(50_000),
+      ")".repeat(50_000)
+    );
+    let normal = determinism(&source, &CancelToken::new())?;
+    assert!(normal.ends_with(&format!("z{}", ")".repeat(50_000))));
+    Ok(())
+  }
+}
@@ -0,0 +1,184 @@
+//! Fixed benchmark suites, giving numbers which can be compared across versions
+//! of camel and across its evaluation strategies
+//!
+//! A suite is written like a `.camel` file, with definitions in scope for every
+//! line after them, and each benchmark is the term on the line after a
+//! `-- bench: <name>` comment. Suites are built into the binary, so that everyone
+//! running one runs exactly the same terms.
+//!
+//! Every benchmark is run under every strategy, and the report gives the beta
+//! reductions taken, the largest term reached, the fastest of several runs, and a
+//! hash of the result. Strategies that stop at weak head normal form give
+//! different results from the others, but the same strategy should always give
+//! the same hash.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+
+use camel::ast::Node;
+use camel::parser::Statement;
+use camel::{CancelToken, EvalError, EvalStrategy, Evaluator, Parser as TermParser};
+
+use crate::definitions::{self, Definitions};
+use crate::table::{Align, Cell, Output, Style, Table};
+
+/// Church factorial, Ackermann, insertion sort over Scott lists, and numerals
+/// written in SKI combinators
+pub const STANDARD: &str = include_str!("bench/standard.camel");
+
+pub struct Options {
+  /// Times each benchmark is run under each strategy, keeping the fastest
+  pub runs: usize,
+  /// Beta reductions allowed for each run
+  pub fuel: usize,
+  pub output: Output,
+}
+
+struct Benchmark {
+  name: String,
+  term: Rc<Node<'static>>,
+}
+
+/// Run every benchmark in `suite` under every strategy, printing a row for each
+/// once all have finished
+pub fn run(
+  suite_name: &str,
+  suite: &str,
+  options: &Options,
+  cancel: &CancelToken,
+) -> Result<(), anyhow::Error> {
+  let benchmarks = parse(suite)?;
+  if options.output == Output::Table {
+    println!(
+      "camel {} bench --suite {}, fastest of {} runs",
+      env!("CARGO_PKG_VERSION"),
+      suite_name,
+      options.runs
+    );
+  }
+  let mut table = Table::new(&[
+    ("benchmark", Align::Left),
+    ("strategy", Align::Left),
+    ("steps", Align::Right),
+    ("max size", Align::Right),
+    ("time ms", Align::Right),
+    ("result", Align::Left),
+  ]);
+  for benchmark in &benchmarks {
+    for &strategy in EvalStrategy::ALL {
+      let evaluator = Evaluator::new(strategy)
+        .with_cancel(cancel)
+        .with_fuel(options.fuel);
+      let mut fastest = Duration::MAX;
+      let mut result = Err(EvalError::Cancelled);
+      for _ in 0..options.runs.max(1) {
+        result = evaluator.eval(Rc::clone(&benchmark.term));
+        fastest = fastest.min(evaluator.stats().elapsed);
+        if result.is_err() {
+          break;
+        }
+      }
+      if cancel.is_cancelled() {
+        return Err(EvalError::Cancelled.into());
+      }
+      let stats = evaluator.stats();
+      let result = match result {
+        Ok(normal) => Cell::text(format!("{:016x}", normal.alpha_hash())),
+        Err(err) => Cell::text(format!("error: {}", err)).styled(Style::Bad),
+      };
+      table.row(vec![
+        Cell::text(&benchmark.name),
+        Cell::text(format!("{:?}", strategy)),
+        Cell::number(stats.beta_reductions),
+        Cell::number(stats.max_size),
+        Cell::millis(fastest.as_secs_f64()),
+        result,
+      ]);
+    }
+  }
+  table.print(options.output);
+  Ok(())
+}
+
+/// The benchmarks of a suite, with the definitions before each substituted in
+fn parse(suite: &str) -> Result<Vec<Benchmark>, anyhow::Error> {
+  let mut definitions = Definitions::new();
+  let mut benchmarks = Vec::new();
+  let mut name = None;
+  for (index, line) in suite.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    if let Some(comment) = line.strip_prefix("--") {
+      if let Some(bench) = comment.trim().strip_prefix("bench:") {
+        name = Some(bench.trim().to_string());
+      }
+      continue;
+    }
+    let statement = TermParser::new(line)
+      .parse_statement()
+      .map_err(|err| anyhow!("line {}: {:#}", index + 1, err))?;
+    match (statement, name.take()) {
+      (Statement::Definition(def), None) => {
+        let term = definitions::expand(&definitions, Rc::new(def.term.to_static()));
+        definitions.insert(def.name.to_string(), term);
+      }
+      (Statement::Term(term), Some(name)) => benchmarks.push(Benchmark {
+        name,
+        term: definitions::expand(&definitions, Rc::new(term.to_static())),
+      }),
+      (Statement::Definition(..), Some(name)) => {
+        bail!("line {}: benchmark {} is a definition", index + 1, name)
+      }
+      (Statement::Term(..), None) => bail!("line {}: term without a benchmark name", index + 1),
+    }
+  }
+  Ok(benchmarks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[test]
+  fn parses_benchmarks() -> Result<(), anyhow::Error> {
+    let suite = "-- a suite\nid = λx.x\n\n-- bench: apply\nid y\n--bench:twice\nid (id z)\n";
+    let benchmarks = parse(suite)?;
+    let parsed: Vec<_> = benchmarks
+      .iter()
+      .map(|bench| (bench.name.as_str(), bench.term.to_string()))
+      .collect();
+    assert_eq!(
+      parsed,
+      [
+        ("apply", "(λx. x) y".to_string()),
+        ("twice", "(λx. x) ((λx. x) z)".to_string())
+      ]
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x y", "line 1: term without a benchmark name")]
+  #[case("-- bench: id\nid = λx.x", "line 2: benchmark id is a definition")]
+  #[case("-- bench: broken\nλx.", "line 2: ")]
+  fn rejects_malformed_suites(#[case] suite: &str, #[case] expected: &str) {
+    let err = parse(suite).err().expect("the suite is malformed");
+    assert!(err.to_string().starts_with(expected), "{}", err);
+  }
+
+  #[test]
+  fn parses_the_standard_suite() -> Result<(), anyhow::Error> {
+    let benchmarks = parse(STANDARD)?;
+    assert!(!benchmarks.is_empty());
+    for bench in &benchmarks {
+      assert!(!bench.name.is_empty());
+      assert!(bench.term.is_closed(), "{}", bench.name);
+    }
+    Ok(())
+  }
+}
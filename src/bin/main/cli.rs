@@ -0,0 +1,549 @@
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg(not(feature = "repl"))]
+use anyhow::bail;
+use anyhow::Context;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+
+use camel::ast::Node;
+use camel::eval::graph;
+use camel::eval::hooks::Hooks;
+use camel::list::{self, ListEncoding};
+#[cfg(feature = "bignum")]
+use camel::numeral::big;
+use camel::numeral::{self, Magnitude};
+use camel::registry::Registry;
+use camel::timing::{self, Phase, PhaseTimes};
+use camel::{binary, CancelToken, EvalStrategy, Evaluator, Parser as TermParser};
+
+use crate::progress::Progress;
+use crate::table::{Align, Cell, Output, Table};
+
+/// Program accepts either a raw program or a filename as input, and starts a REPL
+/// when given neither
+#[derive(Parser, Debug)]
+#[command(name = "camel")]
+#[command(about = "")]
+#[command(group = ArgGroup::new("input").args(&["path", "raw", "resume_from"]))]
+pub struct Args {
+  /// Path to the file
+  #[arg(short, long, group = "input")]
+  path: Option<String>,
+
+  /// Raw string input
+  #[arg(short, long, group = "input")]
+  raw: Option<String>,
+
+  /// Continue from the term saved when a limit stopped an earlier run
+  #[arg(long, group = "input")]
+  resume_from: Option<String>,
+
+  /// Beta reductions allowed before giving up
+  #[arg(long, requires = "input")]
+  fuel: Option<usize>,
+
+  /// Seconds allowed before giving up
+  #[arg(long, requires = "input")]
+  timeout: Option<u64>,
+
+  /// Save the term reached to this file when Ctrl-C or a limit stops evaluation,
+  /// to continue from with --resume-from
+  #[arg(long, requires = "input", conflicts_with = "audit")]
+  checkpoint: Option<PathBuf>,
+
+  /// Check a property of the interpreter while evaluating the input
+  #[arg(long, value_enum, requires = "input", conflicts_with = "resume_from")]
+  audit: Option<Audit>,
+
+  /// How to read the input, by the name of a front end listed in --capabilities
+  #[arg(long, default_value = "text", value_parser = front_end, conflicts_with = "audit")]
+  input_format: String,
+
+  /// How to write out the normal form, by the name of a back end listed in
+  /// --capabilities
+  #[arg(long, default_value = "text", value_parser = back_end)]
+  format: String,
+
+  /// How to evaluate, by the name of a strategy listed in --capabilities. Limits
+  /// step through the default order, so a strategy cannot be given with them
+  #[arg(
+    long,
+    default_value_t = EvalStrategy::default().name().to_string(),
+    value_parser = strategy,
+    conflicts_with_all = ["fuel", "timeout", "checkpoint", "resume_from", "audit"],
+  )]
+  strategy: String,
+
+  /// Largest Church numeral to decode when the normal form is one, past which it
+  /// is only reported as larger
+  #[arg(long, default_value_t = u64::MAX)]
+  numeral_bound: u64,
+
+  /// Print the number the input stands for instead of its normal form, without
+  /// normalizing it, so that numerals too large to build can still be read
+  #[cfg(feature = "bignum")]
+  #[arg(long, value_enum, requires = "input", conflicts_with_all = ["resume_from", "audit"])]
+  decode: Option<Numeral>,
+
+  /// Print the heap allocations made by each pass to stderr when done
+  #[cfg(feature = "profile")]
+  #[arg(long)]
+  heap_profile: bool,
+
+  /// Also capture a backtrace every this many allocations, to report where they
+  /// were made
+  #[cfg(feature = "profile")]
+  #[arg(long, requires = "heap_profile")]
+  heap_sample: Option<usize>,
+
+  /// Print what this build supports and exit, as JSON with `--format json`
+  #[arg(long, conflicts_with = "input")]
+  capabilities: bool,
+
+  /// Shell command to run when evaluation reaches a normal form, with CAMEL_EVENT
+  /// and CAMEL_STEPS set and the term on its standard input
+  #[arg(long, requires = "input", conflicts_with = "audit")]
+  hook: Option<String>,
+
+  /// Also run the hook every this many beta reductions
+  #[arg(long, requires = "hook")]
+  hook_every: Option<NonZeroUsize>,
+
+  /// Print the time spent in each phase to stderr when done
+  #[arg(long, requires = "input", conflicts_with = "audit")]
+  timings: bool,
+
+  /// How to print the report of --timings
+  #[arg(long, value_enum, default_value_t = Output::Table, requires = "timings")]
+  output: Output,
+
+  #[command(subcommand)]
+  command: Option<Command>,
+}
+
+impl Args {
+  /// How often to sample allocations if profiling the heap, where 0 is never
+  #[cfg(feature = "profile")]
+  pub fn heap_profile(&self) -> Option<usize> {
+    self.heap_profile.then(|| self.heap_sample.unwrap_or(0))
+  }
+}
+
+/// Check that a front end is registered as `name`
+fn front_end(name: &str) -> Result<String, String> {
+  registered(name, Registry::builtin().capabilities().front_ends)
+}
+
+/// Check that a back end is registered as `name`
+fn back_end(name: &str) -> Result<String, String> {
+  registered(name, Registry::builtin().capabilities().back_ends)
+}
+
+/// Check that a strategy is registered as `name`
+fn strategy(name: &str) -> Result<String, String> {
+  registered(name, Registry::builtin().capabilities().strategies)
+}
+
+fn registered(name: &str, names: Vec<String>) -> Result<String, String> {
+  if names.iter().any(|known| known == name) {
+    Ok(name.to_string())
+  } else {
+    Err(format!("expected one of {}", names.join(", ")))
+  }
+}
+
+#[cfg(feature = "bignum")]
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Numeral {
+  /// `λf.λx.f (f x)` for 2
+  Church,
+  /// `λs.λz.s (λs.λz.s (λs.λz.z))` for 2
+  Scott,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Suite {
+  /// Church factorial, Ackermann, insertion sort over Scott lists, and SKI
+  Standard,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum List {
+  /// `λc.λn.c a (c b n)` for a, b
+  Church,
+  /// `λc.λn.c a (λc.λn.c b (λc.λn.n))` for a, b
+  Scott,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Audit {
+  /// Evaluate the input several times, on more than one thread, and fail unless
+  /// every run prints the same normal form
+  Determinism,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Run the `.camel` files under a directory, checking each term against the
+  /// `-- expect: <term>` line after it
+  TestSuite {
+    /// Directory to search for `.camel` files, or a single file
+    dir: PathBuf,
+
+    /// Beta reductions allowed for each term
+    #[arg(long, default_value_t = 100_000)]
+    fuel: usize,
+
+    /// Also write the results to a JUnit XML (.xml) or JSON (.json) file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Print the results as a table or JSON instead of TAP
+    #[arg(long, value_enum)]
+    output: Option<Output>,
+  },
+  /// Evaluate random closed terms with both camel and a reference evaluator, and
+  /// report the smallest terms they disagree on
+  Differential {
+    /// Shell command reading a term on its standard input and writing the normal
+    /// form to its standard output
+    command: String,
+
+    /// How terms are written to the reference and read back from it, by the name
+    /// of a format listed in --capabilities
+    #[arg(long, default_value = "text", value_parser = back_end)]
+    format: String,
+
+    /// How camel evaluates each term, by the name of a strategy listed in
+    /// --capabilities
+    #[arg(long, default_value_t = EvalStrategy::default().name().to_string(), value_parser = strategy)]
+    strategy: String,
+
+    /// Number of terms to try
+    #[arg(long, default_value_t = 100)]
+    count: usize,
+
+    /// Nodes in each generated term
+    #[arg(long, default_value_t = 20)]
+    size: usize,
+
+    /// Seed for generating terms, to repeat an earlier run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Beta reductions camel is allowed for each term
+    #[arg(long, default_value_t = 10_000)]
+    fuel: usize,
+
+    /// Seconds the reference is allowed for each term
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+  },
+  /// Run a fixed suite of benchmarks under every strategy, for numbers which can
+  /// be compared across versions
+  Bench {
+    /// Suite to run
+    #[arg(long, value_enum, default_value_t = Suite::Standard)]
+    suite: Suite,
+
+    /// Times to run each benchmark under each strategy, keeping the fastest
+    #[arg(long, default_value_t = 5)]
+    runs: usize,
+
+    /// Beta reductions allowed for each run
+    #[arg(long, default_value_t = 1_000_000)]
+    fuel: usize,
+
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t = Output::Table)]
+    output: Output,
+  },
+  /// Print the first elements of a list term one at a time, forcing no more of the
+  /// list than that, so that infinite lists can be read from the front
+  Take {
+    /// Number of elements to print
+    count: usize,
+
+    /// The list
+    term: String,
+
+    /// How the list is encoded
+    #[arg(long, value_enum, default_value_t = List::Scott)]
+    encoding: List,
+
+    /// Beta reductions allowed for finding each element, and again for evaluating it
+    #[arg(long, default_value_t = 100_000)]
+    fuel: usize,
+  },
+  /// Print the graph of every way a term reduces in Graphviz's DOT language, with
+  /// a node for each term and an edge for each beta reduction
+  Graph {
+    /// The starting term
+    term: String,
+
+    /// Terms to explore before leaving the rest of the graph out
+    #[arg(long, default_value_t = 100)]
+    max_terms: usize,
+
+    /// Steps from the starting term to follow before leaving the rest of the graph
+    /// out
+    #[arg(long)]
+    depth: Option<usize>,
+  },
+}
+
+pub fn run(args: Args) -> Result<(), anyhow::Error> {
+  // Ctrl-C aborts the running evaluation rather than the whole process
+  let cancel = CancelToken::new();
+  ctrlc::set_handler({
+    let cancel = cancel.clone();
+    move || cancel.cancel()
+  })?;
+
+  if args.capabilities {
+    let capabilities = camel::capabilities();
+    match args.format.as_str() {
+      "json" => println!("{}", capabilities.to_json()),
+      _ => println!("{}", capabilities),
+    }
+    return Ok(());
+  }
+
+  match args.command {
+    Some(Command::TestSuite {
+      dir,
+      fuel,
+      report,
+      output,
+    }) => {
+      let evaluator = Evaluator::default().with_cancel(&cancel).with_fuel(fuel);
+      return crate::suite::run(&dir, &evaluator, output, report.as_deref());
+    }
+    Some(Command::Differential {
+      command,
+      format,
+      strategy,
+      count,
+      size,
+      seed,
+      fuel,
+      timeout,
+    }) => {
+      let options = crate::differential::Options {
+        command,
+        registry: Registry::builtin(),
+        format,
+        strategy,
+        count,
+        size,
+        seed,
+        fuel,
+        timeout: Duration::from_secs(timeout),
+      };
+      return crate::differential::run(&options, &cancel);
+    }
+    Some(Command::Bench {
+      suite,
+      runs,
+      fuel,
+      output,
+    }) => {
+      let source = match suite {
+        Suite::Standard => crate::bench::STANDARD,
+      };
+      let name = suite.to_possible_value().expect("no variant is skipped");
+      let options = crate::bench::Options { runs, fuel, output };
+      return crate::bench::run(name.get_name(), source, &options, &cancel);
+    }
+    Some(Command::Take {
+      count,
+      term,
+      encoding,
+      fuel,
+    }) => {
+      let evaluator = Evaluator::default().with_cancel(&cancel).with_fuel(fuel);
+      return take(&term, count, encoding, &evaluator);
+    }
+    Some(Command::Graph {
+      term,
+      max_terms,
+      depth,
+    }) => {
+      let term = Rc::new(TermParser::new(&term).parse()?);
+      let max_depth = depth.unwrap_or(usize::MAX);
+      let graph = graph::explore_within(term, max_terms, max_depth, &cancel)?;
+      println!("{}", graph.to_dot());
+      if graph.truncated {
+        let bound = match depth {
+          Some(depth) => format!("{} terms or at depth {}", max_terms, depth),
+          None => format!("{} terms", max_terms),
+        };
+        eprintln!("warning: stopped after {}, graph is incomplete", bound);
+      }
+      return Ok(());
+    }
+    None => (),
+  }
+
+  let mut times = PhaseTimes::default();
+  if let Some(path) = &args.resume_from {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path))?;
+    let term = times
+      .time(Phase::Parse, || binary::decode(&bytes))
+      .with_context(|| format!("failed to decode {}", path))?;
+    return eval(term, &args, &cancel, &mut times);
+  }
+  let source = match (&args.path, &args.raw) {
+    (Some(path), _) => {
+      fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?
+    }
+    (None, Some(raw)) => raw.clone(),
+    #[cfg(feature = "repl")]
+    (None, None) => return crate::repl::run(cancel),
+    #[cfg(not(feature = "repl"))]
+    (None, None) => bail!("no input given, and this build does not include the REPL"),
+  };
+  if let Some(Audit::Determinism) = args.audit {
+    println!("{}", crate::audit::determinism(&source, &cancel)?);
+    return Ok(());
+  }
+  let term = match args.input_format.as_str() {
+    // lexing and parsing are timed apart for text
+    "text" => Rc::new(timing::parse(&source, &mut times)?),
+    name => {
+      let registry = Registry::builtin();
+      let front_end = registry.front_end(name).expect("checked by clap");
+      times.time(Phase::Parse, || front_end.read(source.as_bytes()))?
+    }
+  };
+  #[cfg(feature = "bignum")]
+  if let Some(numeral) = args.decode {
+    let fuel = args.fuel.unwrap_or(big::DECODE_FUEL);
+    let number = times.time(Phase::Decode, || match numeral {
+      Numeral::Church => big::church(&term, fuel),
+      Numeral::Scott => big::scott(&term, fuel),
+    })?;
+    times.time(Phase::Print, || println!("{}", number));
+    if args.timings {
+      print_timings(&times, args.output);
+    }
+    return Ok(());
+  }
+  eval(term, &args, &cancel, &mut times)
+}
+
+/// Print the first `count` elements of a list, each as soon as it is found
+fn take(
+  source: &str,
+  count: usize,
+  encoding: List,
+  evaluator: &Evaluator<'_>,
+) -> Result<(), anyhow::Error> {
+  let list = Rc::new(TermParser::new(source).parse()?);
+  let encoding = match encoding {
+    List::Church => ListEncoding::Church,
+    List::Scott => ListEncoding::Scott,
+  };
+  for element in list::elements(list, encoding, evaluator).take(count) {
+    let normal = evaluator.eval(element?)?;
+    match numeral::church(&normal, u64::MAX) {
+      Some(Magnitude::Exact(n)) => println!("{}", n),
+      _ => println!("{}", normal),
+    }
+  }
+  Ok(())
+}
+
+/// Evaluate a term under the limits given, if any, and write out its normal form
+fn eval(
+  term: Rc<Node<'_>>,
+  args: &Args,
+  cancel: &CancelToken,
+  times: &mut PhaseTimes,
+) -> Result<(), anyhow::Error> {
+  let progress = Progress::new();
+  let mut hooks = match &args.hook {
+    Some(command) => crate::hooks::shell(command, args.hook_every),
+    None => Hooks::new(),
+  };
+  if let Some(progress) = &progress {
+    progress.attach(&mut hooks);
+  }
+  let registry = Registry::builtin();
+  let limits = crate::limits::Limits {
+    fuel: args.fuel,
+    timeout: args.timeout.map(Duration::from_secs),
+    checkpoint: args.checkpoint.clone(),
+  };
+  let normal = times.time(Phase::Normalize, || {
+    if limits.is_set() {
+      return crate::limits::eval(term, &limits, cancel, &hooks);
+    }
+    let strategy = registry.strategy(&args.strategy).expect("checked by clap");
+    Ok(
+      Evaluator::default()
+        .with_cancel(cancel)
+        .with_hooks(&hooks)
+        .eval_with(strategy, term)?,
+    )
+  });
+  if let Some(progress) = &progress {
+    progress.finish();
+  }
+  let normal = normal?;
+  let back_end = registry.back_end(&args.format).expect("checked by clap");
+  times.time(Phase::Print, || -> Result<(), anyhow::Error> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&back_end.write(&normal)?)?;
+    if !back_end.is_binary() {
+      writeln!(stdout)?;
+    }
+    Ok(())
+  })?;
+  if args.format == "text" {
+    match times.time(Phase::Decode, || {
+      numeral::church(&normal, args.numeral_bound)
+    }) {
+      Some(Magnitude::Exact(n)) => eprintln!("Church numeral {}", n),
+      Some(Magnitude::Exceeds(bound)) => {
+        eprintln!("warning: Church numeral larger than {}, not decoded", bound)
+      }
+      None => (),
+    }
+  }
+  if args.timings {
+    print_timings(times, args.output);
+  }
+  Ok(())
+}
+
+/// Print a row to stderr for each phase with its time and share of the total, then
+/// the total
+fn print_timings(times: &PhaseTimes, output: Output) {
+  let mut table = Table::new(&[
+    ("phase", Align::Left),
+    ("time ms", Align::Right),
+    ("share %", Align::Right),
+  ]);
+  for phase in Phase::ALL {
+    table.row(vec![
+      Cell::text(phase.to_string()),
+      Cell::millis(times.get(phase).as_secs_f64()),
+      Cell::number(format!("{:.1}", times.share(phase))),
+    ]);
+  }
+  table.row(vec![
+    Cell::text("total"),
+    Cell::millis(times.total().as_secs_f64()),
+    Cell::number("100.0"),
+  ]);
+  match output {
+    Output::Table => eprint!("{}", table.render(false)),
+    Output::Json => eprintln!("{}", table.to_json()),
+  }
+}
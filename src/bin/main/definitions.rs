@@ -0,0 +1,50 @@
+//! Definitions made by the lines of a session or file, for the terms after them
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use camel::ast::Node;
+use camel::eval::substitute_all;
+
+/// Definitions by name, each stored with the ones before it already substituted in
+pub type Definitions = HashMap<String, Rc<Node<'static>>>;
+
+/// Substitute every definition into `term` at once, so that none is substituted
+/// into the body of another and the result does not depend on the map's order
+pub fn expand<'inp>(definitions: &Definitions, term: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+  let mut bindings: Vec<(&str, Rc<Node<'inp>>)> = definitions
+    .iter()
+    .map(|(name, value)| (name.as_str(), Rc::clone(value) as Rc<Node<'inp>>))
+    .collect();
+  bindings.sort_unstable_by_key(|&(name, _)| name);
+  substitute_all(&term, &bindings)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use camel::Parser as TermParser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(&[], "f x", "f x")]
+  #[case(&[("f", "λy.y")], "f x", "(λy. y) x")]
+  #[case(&[("x", "y"), ("y", "x")], "x y", "y x")]
+  #[case(&[("x", "a")], "λx.x", "(λx. x)")]
+  // values are not expanded again, as each was expanded when it was defined
+  #[case(&[("a", "y"), ("f", "λy.a y")], "f", "(λy. a y)")]
+  fn expands_all_at_once(
+    #[case] bindings: &[(&str, &str)],
+    #[case] term: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let mut definitions = Definitions::new();
+    for (name, source) in bindings {
+      let value = TermParser::new(source).parse()?.to_static();
+      definitions.insert(name.to_string(), Rc::new(value));
+    }
+    let term = Rc::new(TermParser::new(term).parse()?);
+    assert_eq!(expand(&definitions, term).to_string(), expected);
+    Ok(())
+  }
+}
@@ -0,0 +1,275 @@
+//! Differential testing against another lambda calculus implementation
+//!
+//! Random closed terms are piped one at a time to a reference evaluator, run as a
+//! shell command which reads a term on its standard input and writes its normal
+//! form to its standard output. Whenever the reference and camel disagree, the
+//! term is cut down to a smaller one on which they still disagree before being
+//! reported.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+
+use camel::ast::Node;
+use camel::random::{closed_term, seed_from_clock, Xorshift};
+use camel::registry::Registry;
+use camel::shrink::shrink;
+use camel::{CancelToken, Evaluator};
+
+/// How often a running reference evaluator is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub struct Options {
+  /// Shell command running the reference evaluator
+  pub command: String,
+  /// Where the format and strategy are looked up
+  pub registry: Registry,
+  /// Name of the format terms are written to the reference and read back in
+  pub format: String,
+  /// Name of the strategy camel evaluates with
+  pub strategy: String,
+  pub count: usize,
+  /// Nodes in each generated term
+  pub size: usize,
+  pub seed: Option<u64>,
+  /// Beta reductions camel is allowed for each term
+  pub fuel: usize,
+  /// Time the reference is allowed for each term
+  pub timeout: Duration,
+}
+
+/// What an evaluator made of a term, as the normal form it gave or why it gave none
+#[derive(Debug)]
+enum Outcome {
+  Normal(Rc<Node<'static>>),
+  Failed(String),
+}
+
+impl Outcome {
+  /// Two outcomes agree when both are alpha-equivalent normal forms, or both are
+  /// failures, as neither side is expected to fail the same way
+  fn agrees_with(&self, other: &Outcome) -> bool {
+    match (self, other) {
+      (Outcome::Normal(a), Outcome::Normal(b)) => a.alpha_eq(b),
+      (Outcome::Failed(..), Outcome::Failed(..)) => true,
+      _ => false,
+    }
+  }
+}
+
+impl std::fmt::Display for Outcome {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Outcome::Normal(term) => write!(f, "{}", term),
+      Outcome::Failed(reason) => write!(f, "no normal form ({})", reason),
+    }
+  }
+}
+
+/// Evaluate `count` random terms with both camel and the reference, reporting
+/// each disagreement, and fail if there was any
+pub fn run(options: &Options, cancel: &CancelToken) -> Result<(), anyhow::Error> {
+  let mut rng = Xorshift::from_seed(options.seed.unwrap_or_else(seed_from_clock));
+  println!("seed {}", rng.seed());
+  let mut mismatches = 0;
+  for index in 0..options.count {
+    if cancel.is_cancelled() {
+      bail!("cancelled after {} terms", index);
+    }
+    let term = closed_term(&mut rng, options.size);
+    if agree(&term, options, cancel)? {
+      continue;
+    }
+    mismatches += 1;
+    let smallest = shrink(Rc::clone(&term), |term| {
+      !cancel.is_cancelled() && !agree(term, options, cancel).unwrap_or(true)
+    });
+    println!("mismatch on term {}: {}", index + 1, term);
+    println!("  minimized: {}", smallest);
+    println!("  camel:     {}", camel(&smallest, options, cancel));
+    println!("  reference: {}", reference(&smallest, options)?);
+  }
+  println!(
+    "{} of {} terms agreed",
+    options.count - mismatches,
+    options.count
+  );
+  if mismatches > 0 {
+    bail!("{} mismatches against `{}`", mismatches, options.command);
+  }
+  Ok(())
+}
+
+fn agree(
+  term: &Rc<Node<'static>>,
+  options: &Options,
+  cancel: &CancelToken,
+) -> Result<bool, anyhow::Error> {
+  let ours = camel(term, options, cancel);
+  let theirs = reference(term, options)?;
+  Ok(ours.agrees_with(&theirs))
+}
+
+fn camel(term: &Rc<Node<'static>>, options: &Options, cancel: &CancelToken) -> Outcome {
+  let evaluator = Evaluator::default()
+    .with_cancel(cancel)
+    .with_fuel(options.fuel);
+  let Some(strategy) = options.registry.strategy(&options.strategy) else {
+    return Outcome::Failed(format!("unknown strategy {}", options.strategy));
+  };
+  match evaluator.eval_with(strategy, Rc::clone(term)) {
+    Ok(normal) => Outcome::Normal(normal),
+    Err(err) => Outcome::Failed(err.to_string()),
+  }
+}
+
+/// Run the reference on `term`, failing only if it could not be run at all
+fn reference(term: &Rc<Node<'static>>, options: &Options) -> Result<Outcome, anyhow::Error> {
+  let (Some(back_end), Some(front_end)) = (
+    options.registry.back_end(&options.format),
+    options.registry.front_end(&options.format),
+  ) else {
+    bail!("{} cannot be both written and read", options.format);
+  };
+  let mut input = back_end.write(term)?;
+  if !back_end.is_binary() {
+    input.push(b'\n');
+  }
+  let mut child = Command::new("sh")
+    .arg("-c")
+    .arg(&options.command)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .with_context(|| format!("failed to run `{}`", options.command))?;
+  let mut stdin = child.stdin.take().expect("stdin is piped");
+  let mut stdout = child.stdout.take().expect("stdout is piped");
+  // feed and drain the pipes on their own threads so that a reference which stops
+  // reading or writing cannot keep the timeout from being noticed
+  let writer = thread::spawn(move || stdin.write_all(&input));
+  let reader = thread::spawn(move || {
+    let mut output = Vec::new();
+    stdout.read_to_end(&mut output).map(|_| output)
+  });
+  let deadline = Instant::now() + options.timeout;
+  let status = loop {
+    if let Some(status) = child.try_wait()? {
+      break status;
+    }
+    if Instant::now() >= deadline {
+      let _ = child.kill();
+      let _ = child.wait();
+      return Ok(Outcome::Failed("timed out".to_string()));
+    }
+    thread::sleep(POLL_INTERVAL);
+  };
+  // a reference which exits without reading all of its input is not an error here
+  let _ = writer.join();
+  let Ok(output) = reader.join() else {
+    bail!("the output reader panicked");
+  };
+  let output = output?;
+  if !status.success() {
+    return Ok(Outcome::Failed(status.to_string()));
+  }
+  Ok(match front_end.read(&output) {
+    Ok(normal) => Outcome::Normal(normal),
+    Err(err) => Outcome::Failed(format!("unreadable output: {:#}", err)),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use camel::{EvalStrategy, Parser as TermParser};
+  use rstest::rstest;
+
+  fn term(source: &str) -> Rc<Node<'static>> {
+    Rc::new(TermParser::new(source).parse().unwrap().to_static())
+  }
+
+  fn options(command: &str, format: &str) -> Options {
+    Options {
+      command: command.to_string(),
+      registry: Registry::builtin(),
+      format: format.to_string(),
+      strategy: EvalStrategy::default().name().to_string(),
+      count: 1,
+      size: 10,
+      seed: Some(1),
+      fuel: 1000,
+      timeout: Duration::from_secs(5),
+    }
+  }
+
+  #[rstest]
+  #[case(Outcome::Normal(term("λx.x")), Outcome::Normal(term("λy.y")), true)]
+  #[case(
+    Outcome::Normal(term("λx.λy.x")),
+    Outcome::Normal(term("λx.λy.y")),
+    false
+  )]
+  #[case(Outcome::Failed("a".to_string()), Outcome::Failed("b".to_string()), true)]
+  #[case(Outcome::Normal(term("λx.x")), Outcome::Failed("a".to_string()), false)]
+  fn compares_outcomes(#[case] ours: Outcome, #[case] theirs: Outcome, #[case] agree: bool) {
+    assert_eq!(ours.agrees_with(&theirs), agree);
+    assert_eq!(theirs.agrees_with(&ours), agree);
+  }
+
+  #[rstest]
+  #[case("text")]
+  #[cfg_attr(feature = "formats", case("json"))]
+  #[cfg_attr(feature = "formats", case("binary"))]
+  #[cfg_attr(feature = "formats", case("blc"))]
+  #[cfg_attr(feature = "formats", case("sexpr"))]
+  fn reads_back_the_reference(#[case] format: &str) -> Result<(), anyhow::Error> {
+    let normal = term("λx.λy.x (λz.z y)");
+    let outcome = reference(&normal, &options("cat", format))?;
+    assert!(outcome.agrees_with(&Outcome::Normal(normal)), "{}", outcome);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("exit 3", "no normal form (exit status: 3)")]
+  #[case("echo 'λx.'", "no normal form (unreadable output: ")]
+  fn reports_reference_failures(#[case] command: &str, #[case] expected: &str) {
+    let outcome = reference(&term("λx.x"), &options(command, "text")).unwrap();
+    assert!(outcome.to_string().starts_with(expected), "{}", outcome);
+  }
+
+  #[test]
+  fn times_out_the_reference() -> Result<(), anyhow::Error> {
+    let options = Options {
+      timeout: Duration::from_millis(50),
+      ..options("sleep 5", "text")
+    };
+    let outcome = reference(&term("λx.x"), &options)?;
+    assert_eq!(outcome.to_string(), "no normal form (timed out)");
+    Ok(())
+  }
+
+  #[test]
+  fn evaluates_with_the_strategy() {
+    let cancel = CancelToken::new();
+    let head = Options {
+      strategy: EvalStrategy::CallByName.name().to_string(),
+      ..options("cat", "text")
+    };
+    let outcome = camel(&term("λx.(λy.y) x"), &head, &cancel);
+    assert_eq!(outcome.to_string(), "(λx. (λy. y) x)");
+    let unknown = Options {
+      strategy: "nowhere".to_string(),
+      ..options("cat", "text")
+    };
+    let outcome = camel(&term("x"), &unknown, &cancel);
+    assert_eq!(
+      outcome.to_string(),
+      "no normal form (unknown strategy nowhere)"
+    );
+  }
+}
@@ -0,0 +1,60 @@
+//! Shell commands run on evaluation events, given with `--hook`
+//!
+//! The command runs through `sh -c` with `CAMEL_EVENT` set to `steps` or
+//! `normal-form` and `CAMEL_STEPS` to the beta reductions taken so far. For a
+//! normal form, the term is written to its standard input. Evaluation waits for
+//! the command to exit, and a command that fails only gets a warning.
+
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+use camel::eval::hooks::{Event, Hooks};
+
+/// Hooks running `command` on every normal form, and every `every` steps if given
+pub fn shell(command: &str, every: Option<NonZeroUsize>) -> Hooks<'_> {
+  let mut hooks = Hooks::new();
+  hooks.on_normal_form(move |event| run(command, event));
+  if let Some(every) = every {
+    hooks.on_steps(every.get(), move |event| run(command, event));
+  }
+  hooks
+}
+
+fn run(command: &str, event: &Event<'_>) {
+  let (name, steps, input) = match event {
+    Event::Steps { steps, .. } => ("steps", steps, None),
+    Event::NormalForm { term, steps } => ("normal-form", steps, Some(term.to_string())),
+    _ => return,
+  };
+  if let Err(err) = spawn(command, name, *steps, input) {
+    eprintln!("warning: hook for {} failed: {:#}", name, err);
+  }
+}
+
+fn spawn(
+  command: &str,
+  event: &str,
+  steps: usize,
+  input: Option<String>,
+) -> Result<(), anyhow::Error> {
+  let mut child = Command::new("sh")
+    .arg("-c")
+    .arg(command)
+    .env("CAMEL_EVENT", event)
+    .env("CAMEL_STEPS", steps.to_string())
+    .stdin(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("failed to run `{}`", command))?;
+  let mut stdin = child.stdin.take().expect("stdin is piped");
+  if let Some(input) = input {
+    // a hook which does not read its input is not an error
+    let _ = writeln!(stdin, "{}", input);
+  }
+  drop(stdin);
+  let status = child.wait()?;
+  anyhow::ensure!(status.success(), "`{}` exited with {}", command, status);
+  Ok(())
+}
@@ -0,0 +1,204 @@
+//! Evaluation under the limits given on the command line, which saves the term it
+//! reached when a limit stops it so that a later run can carry on from there
+
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+
+use camel::ast::Node;
+use camel::binary;
+use camel::eval::hooks::Hooks;
+use camel::eval::step;
+use camel::{CancelToken, EvalError};
+
+/// Characters of the term reached shown in the error, before it is cut off
+const SHOWN_CHARS: usize = 200;
+
+pub struct Limits {
+  pub fuel: Option<usize>,
+  pub timeout: Option<Duration>,
+  /// Where the term reached is saved when a limit is hit, in the binary format
+  pub checkpoint: Option<PathBuf>,
+}
+
+impl Limits {
+  /// Whether any limit or checkpoint was asked for, without which there is no need
+  /// to step through the term
+  pub fn is_set(&self) -> bool {
+    self.fuel.is_some() || self.timeout.is_some() || self.checkpoint.is_some()
+  }
+}
+
+/// Reduce a term one step at a time, in the same order as the default evaluator,
+/// until it reaches normal form or a limit is hit, firing `hooks` along the way
+///
+/// Stepping keeps the whole current term at hand, which is what gets saved to the
+/// checkpoint, if there is one, when Ctrl-C, the timeout or the fuel stops
+/// evaluation
+pub fn eval<'inp>(
+  node: Rc<Node<'inp>>,
+  limits: &Limits,
+  cancel: &CancelToken,
+  hooks: &Hooks<'_>,
+) -> Result<Rc<Node<'inp>>, anyhow::Error> {
+  let deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+  let checkpoint = limits.checkpoint.as_deref();
+  let mut current = node;
+  let mut steps = 0;
+  loop {
+    let Some(next) = step(&current) else {
+      hooks.normal_form(&current, steps);
+      return Ok(current);
+    };
+    if cancel.is_cancelled() {
+      return Err(stopped(EvalError::Cancelled, steps, &current, checkpoint));
+    }
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+      let timeout = limits.timeout.expect("a deadline comes from a timeout");
+      let error = format!("Time limit of {:?} exceeded", timeout);
+      return Err(stopped(error, steps, &current, checkpoint));
+    }
+    if limits.fuel.is_some_and(|fuel| steps >= fuel) {
+      return Err(stopped(
+        EvalError::StepLimitExceeded,
+        steps,
+        &current,
+        checkpoint,
+      ));
+    }
+    current = next;
+    steps += 1;
+    hooks.stepped(steps, Some(&current));
+  }
+}
+
+/// Describe how far evaluation got before `error` stopped it, saving the term
+/// reached to `checkpoint` if given
+fn stopped(
+  error: impl Display,
+  steps: usize,
+  term: &Rc<Node<'_>>,
+  checkpoint: Option<&Path>,
+) -> anyhow::Error {
+  let text = term.to_string();
+  let shown = match text.char_indices().nth(SHOWN_CHARS) {
+    Some((end, _)) => format!("{}…", &text[..end]),
+    None => text.clone(),
+  };
+  let saved = match checkpoint.map(|path| (path, fs::write(path, binary::encode(term)))) {
+    Some((path, Ok(()))) => format!(
+      "the term reached is saved in {0}, continue with --resume-from {0}",
+      path.display()
+    ),
+    Some((path, Err(err))) => format!(
+      "the term reached could not be saved in {}: {}",
+      path.display(),
+      err
+    ),
+    None => "save the term reached with --checkpoint to continue from it".to_string(),
+  };
+  anyhow!(
+    "{} after {} steps, at a term of {} nodes:\n  {}\n{}",
+    error,
+    steps,
+    term.size(),
+    shown,
+    saved
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use camel::Parser as TermParser;
+  use std::env;
+  use std::process;
+
+  fn omega() -> Rc<Node<'static>> {
+    Rc::new(
+      TermParser::new("(λx.x x) (λx.x x)")
+        .parse()
+        .unwrap()
+        .to_static(),
+    )
+  }
+
+  fn limits(fuel: Option<usize>, timeout: Option<Duration>, checkpoint: Option<PathBuf>) -> Limits {
+    Limits {
+      fuel,
+      timeout,
+      checkpoint,
+    }
+  }
+
+  #[test]
+  fn reaches_normal_forms() -> Result<(), anyhow::Error> {
+    let term = Rc::new(TermParser::new("(λx.λy.x) a b").parse()?);
+    let normal = eval(
+      term,
+      &limits(Some(2), None, None),
+      &CancelToken::new(),
+      &Hooks::new(),
+    )?;
+    assert_eq!(normal.to_string(), "a");
+    Ok(())
+  }
+
+  #[test]
+  fn runs_out_of_fuel() {
+    let limits = limits(Some(3), None, None);
+    let err = eval(omega(), &limits, &CancelToken::new(), &Hooks::new()).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Step limit exceeded after 3 steps, at a term of 9 nodes:\n  (λx. x x) (λx. x x)\n\
+       save the term reached with --checkpoint to continue from it"
+    );
+  }
+
+  #[test]
+  fn times_out() {
+    let limits = limits(None, Some(Duration::ZERO), None);
+    let err = eval(omega(), &limits, &CancelToken::new(), &Hooks::new()).unwrap_err();
+    assert!(err
+      .to_string()
+      .starts_with("Time limit of 0ns exceeded after 0 steps"));
+  }
+
+  #[test]
+  fn cancels() {
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let err = eval(omega(), &limits(None, None, None), &cancel, &Hooks::new()).unwrap_err();
+    assert!(err
+      .to_string()
+      .starts_with("Evaluation was cancelled after 0 steps"));
+  }
+
+  #[test]
+  fn saves_checkpoints() -> Result<(), anyhow::Error> {
+    let path = env::temp_dir().join(format!("camel-{}-limits-test.checkpoint", process::id()));
+    let err = stopped(EvalError::StepLimitExceeded, 5, &omega(), Some(&path));
+    let saved = binary::decode(&fs::read(&path)?);
+    fs::remove_file(&path)?;
+    assert!(saved?.alpha_eq(&omega()));
+    assert!(err.to_string().ends_with(&format!(
+      "the term reached is saved in {0}, continue with --resume-from {0}",
+      path.display()
+    )));
+    Ok(())
+  }
+
+  #[test]
+  fn shortens_long_terms() {
+    let source = format!("{}x", "λx.".repeat(300));
+    let term = Rc::new(TermParser::new(&source).parse().unwrap());
+    let err = stopped(EvalError::Cancelled, 0, &term, None).to_string();
+    let shown = err.lines().nth(1).unwrap().trim();
+    assert_eq!(shown.chars().count(), SHOWN_CHARS + 1);
+    assert!(shown.ends_with('…'));
+  }
+}
@@ -0,0 +1,43 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+
+mod audit;
+mod bench;
+mod cli;
+mod definitions;
+mod differential;
+mod hooks;
+mod limits;
+mod progress;
+#[cfg(feature = "repl")]
+mod repl;
+mod suite;
+mod table;
+
+#[cfg(feature = "profile")]
+#[global_allocator]
+static PROFILER: camel::profile::Profiler = camel::profile::Profiler;
+
+fn main() -> ExitCode {
+  let args = cli::Args::parse();
+  #[cfg(feature = "profile")]
+  let heap_profile = args.heap_profile();
+  #[cfg(feature = "profile")]
+  if let Some(every) = heap_profile {
+    camel::profile::sample_every(every);
+  }
+
+  let result = cli::run(args);
+  #[cfg(feature = "profile")]
+  if heap_profile.is_some() {
+    eprint!("{}", camel::profile::report());
+  }
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("error: {:#}", err);
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,76 @@
+//! A live progress line on stderr for long evaluations, kept up to date by a step
+//! hook and only drawn once an evaluation has run for a while
+
+use std::cell::Cell;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use camel::ast::Node;
+use camel::eval::hooks::{Event, Hooks};
+
+/// Beta reductions between checks of whether to redraw the line
+const CHECK_EVERY: usize = 4096;
+
+/// How long an evaluation runs before the line first appears
+const DELAY: Duration = Duration::from_secs(1);
+
+/// Time between redraws
+const REDRAW: Duration = Duration::from_millis(100);
+
+pub struct Progress {
+  start: Instant,
+  /// When the line was last drawn, if it has been
+  drawn: Cell<Option<Instant>>,
+}
+
+impl Progress {
+  /// A progress line starting now, or `None` if stderr is not a terminal
+  pub fn new() -> Option<Self> {
+    io::stderr().is_terminal().then(|| Progress {
+      start: Instant::now(),
+      drawn: Cell::new(None),
+    })
+  }
+
+  /// Redraw the line as `hooks` reports steps
+  pub fn attach<'p>(&'p self, hooks: &mut Hooks<'p>) {
+    hooks.on_steps(CHECK_EVERY, move |event| {
+      if let Event::Steps { steps, term } = event {
+        self.update(*steps, *term);
+      }
+    });
+  }
+
+  /// Redraw the line if it is due, measuring the term only then
+  fn update(&self, steps: usize, term: Option<&Node<'_>>) {
+    let now = Instant::now();
+    let elapsed = now - self.start;
+    let due = match self.drawn.get() {
+      Some(drawn) => now - drawn >= REDRAW,
+      None => elapsed >= DELAY,
+    };
+    if !due {
+      return;
+    }
+    let rate = steps as f64 / elapsed.as_secs_f64();
+    let size = term.map_or(String::new(), |term| format!(", {} nodes", term.size()));
+    let mut stderr = io::stderr().lock();
+    let _ = write!(
+      stderr,
+      "\r\x1b[K{} steps{}, {:.1}s, {:.0} steps/s",
+      steps,
+      size,
+      elapsed.as_secs_f64(),
+      rate
+    );
+    let _ = stderr.flush();
+    self.drawn.set(Some(now));
+  }
+
+  /// Clear the line if it was drawn, before anything else is printed
+  pub fn finish(&self) {
+    if self.drawn.take().is_some() {
+      eprint!("\r\x1b[K");
+    }
+  }
+}
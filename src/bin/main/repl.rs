@@ -0,0 +1,415 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
+use std::{env, fs, process};
+
+use anyhow::{anyhow, bail, Context};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use camel::ast::debruijn::to_debruijn;
+use camel::ast::Node;
+use camel::eval::{compare_strategies, Comparison, Reducer};
+use camel::lexer::Lexer;
+use camel::parser::Statement;
+use camel::token::TokenKind;
+use camel::{CancelToken, EvalError, EvalStrategy, Evaluator, Parser as TermParser, SourceFile};
+
+use crate::definitions::{self, Definitions};
+use crate::table::{self, Align, Cell, Style, Table};
+
+/// Beta reductions each strategy is allowed by `:compare`
+const COMPARE_FUEL: usize = 10_000;
+
+/// Beta reductions allowed for normalizing a definition when it is entered
+const DEFINITION_FUEL: usize = 10_000;
+
+/// Read statements and meta-commands line by line until end of input or `:quit`
+pub fn run(cancel: CancelToken) -> Result<(), anyhow::Error> {
+  let mut editor = DefaultEditor::new()?;
+  let mut session = Session::new(cancel);
+  loop {
+    match editor.readline("λ> ") {
+      Ok(line) if line.trim().is_empty() => continue,
+      Ok(line) => {
+        editor.add_history_entry(&line)?;
+        match session.handle(&line) {
+          Ok(Reply::Output(output)) => println!("{}", output),
+          Ok(Reply::Silent) => (),
+          Ok(Reply::Quit) => return Ok(()),
+          Err(err) => eprintln!("error: {:#}", err),
+        }
+      }
+      Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+      Err(err) => return Err(err.into()),
+    }
+  }
+}
+
+/// Meta-commands available in the REPL, written as `:name argument`
+enum Command<'l> {
+  Load(&'l str),
+  Edit(&'l str),
+  Step(&'l str),
+  Trace(&'l str),
+  Free(&'l str),
+  Debruijn(&'l str),
+  Compare(&'l str),
+  Quit,
+}
+
+impl<'l> Command<'l> {
+  fn parse(line: &'l str) -> Result<Self, anyhow::Error> {
+    let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let arg = arg.trim();
+    match name {
+      "load" => Ok(Command::Load(arg)),
+      "edit" => Ok(Command::Edit(arg)),
+      "step" => Ok(Command::Step(arg)),
+      "trace" => Ok(Command::Trace(arg)),
+      "free" => Ok(Command::Free(arg)),
+      "debruijn" => Ok(Command::Debruijn(arg)),
+      "compare" => Ok(Command::Compare(arg)),
+      "quit" | "q" => Ok(Command::Quit),
+      _ => Err(anyhow!("unknown command :{}", name)),
+    }
+  }
+}
+
+/// What the REPL should do after handling a line
+enum Reply {
+  Output(String),
+  Silent,
+  Quit,
+}
+
+/// Definitions entered so far, stored with earlier definitions already substituted in
+/// and reduced to normal form where they have one
+struct Session {
+  definitions: Definitions,
+  /// The statement each definition was last entered as, for `:edit`
+  sources: HashMap<String, String>,
+  /// Names in the order they were first defined
+  order: Vec<String>,
+  cancel: CancelToken,
+}
+
+impl Session {
+  fn new(cancel: CancelToken) -> Self {
+    Session {
+      definitions: Definitions::new(),
+      sources: HashMap::new(),
+      order: Vec::new(),
+      cancel,
+    }
+  }
+
+  /// Handle a line of input, which is either a meta-command or a statement
+  fn handle(&mut self, line: &str) -> Result<Reply, anyhow::Error> {
+    self.cancel.reset();
+    match line.trim().strip_prefix(':') {
+      Some(command) => self.command(Command::parse(command)?),
+      None => Ok(self.statement(line)?.map_or(Reply::Silent, Reply::Output)),
+    }
+  }
+
+  fn command(&mut self, command: Command) -> Result<Reply, anyhow::Error> {
+    let output = match command {
+      Command::Load(path) => self.load(Path::new(path))?,
+      Command::Edit(name) => self.edit(name)?,
+      Command::Step(source) => match Evaluator::default()
+        .with_cancel(&self.cancel)
+        .step(&self.term(source)?)?
+      {
+        Some(next) => next.to_string(),
+        None => "already in normal form".to_string(),
+      },
+      Command::Trace(source) => {
+        let term = self.term(source)?;
+        let mut lines = vec![term.to_string()];
+        let mut reducer = Reducer::new(term).with_cancel(&self.cancel);
+        lines.extend(reducer.by_ref().map(|next| format!("→ {}", next)));
+        if reducer.is_cancelled() {
+          return Err(EvalError::Cancelled.into());
+        }
+        lines.join("\n")
+      }
+      Command::Free(source) => {
+        let term = self.term(source)?;
+        let mut vars: Vec<_> = term.free_vars().into_iter().collect();
+        vars.sort_unstable();
+        format!("{{{}}}", vars.join(", "))
+      }
+      Command::Debruijn(source) => to_debruijn(&*self.term(source)?).to_string(),
+      Command::Compare(source) => {
+        let comparison = compare_strategies(&self.term(source)?, EvalStrategy::ALL, COMPARE_FUEL);
+        compare_table(&comparison)
+      }
+      Command::Quit => return Ok(Reply::Quit),
+    };
+    if output.is_empty() {
+      return Ok(Reply::Silent);
+    }
+    Ok(Reply::Output(output))
+  }
+
+  /// Run every line of a file as a statement, collecting the output of its terms
+  fn load(&mut self, path: &Path) -> Result<String, anyhow::Error> {
+    let source =
+      SourceFile::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut outputs = Vec::new();
+    for (number, line) in source.lines() {
+      if line.trim().is_empty() {
+        continue;
+      }
+      let output = self
+        .statement(line)
+        .with_context(|| format!("{}:{}", source.name(), number))?;
+      outputs.extend(output);
+    }
+    Ok(outputs.join("\n"))
+  }
+
+  /// Open a definition in the user's editor, then load the file back once the editor
+  /// exits, starting from an empty definition if there is none yet
+  ///
+  /// Definitions made after it which use it are entered again, so that they pick
+  /// up the new version
+  fn edit(&mut self, name: &str) -> Result<String, anyhow::Error> {
+    if name.is_empty() {
+      bail!(":edit needs the name of a definition");
+    }
+    if !is_identifier(name) {
+      bail!("{} is not a name a definition can have", name);
+    }
+    let path = env::temp_dir().join(format!("camel-{}-{}.lc", process::id(), name));
+    let source = match self.sources.get(name) {
+      Some(source) => format!("{}\n", source),
+      None => format!("{} = \n", name),
+    };
+    fs::write(&path, source).with_context(|| format!("failed to write {}", path.display()))?;
+    let result = open_editor(&path).and_then(|()| self.load(&path));
+    let _ = fs::remove_file(&path);
+    let mut output = result?;
+    let updated = self.redefine_dependents(name)?;
+    if !updated.is_empty() {
+      if !output.is_empty() {
+        output.push('\n');
+      }
+      output.push_str(&format!("updated {}", updated.join(", ")));
+    }
+    Ok(output)
+  }
+
+  /// Enter again, in the order they were first defined, the definitions made after
+  /// `name` which use it or another definition entered again, returning their names
+  fn redefine_dependents(&mut self, name: &str) -> Result<Vec<String>, anyhow::Error> {
+    let Some(start) = self.order.iter().position(|defined| defined == name) else {
+      return Ok(Vec::new());
+    };
+    let mut changed = HashSet::from([name.to_string()]);
+    let mut updated = Vec::new();
+    let later = self.order[start + 1..].to_vec();
+    for dependent in later {
+      let source = self.sources[&dependent].clone();
+      let Statement::Definition(def) = TermParser::new(&source).parse_statement()? else {
+        unreachable!("only definitions are kept as sources");
+      };
+      if !def
+        .term
+        .free_vars()
+        .iter()
+        .any(|var| changed.contains(*var))
+      {
+        continue;
+      }
+      self
+        .statement(&source)
+        .with_context(|| format!("failed to update {}", dependent))?;
+      changed.insert(dependent.clone());
+      updated.push(dependent);
+    }
+    Ok(updated)
+  }
+
+  /// Handle a statement, returning the normal form of a term or nothing for a definition
+  fn statement(&mut self, line: &str) -> Result<Option<String>, anyhow::Error> {
+    match TermParser::new(line).parse_statement()? {
+      Statement::Definition(def) => {
+        let term = self.elaborate(self.expand(Rc::new(def.term.to_static())))?;
+        if self
+          .definitions
+          .insert(def.name.to_string(), term)
+          .is_none()
+        {
+          self.order.push(def.name.to_string());
+        }
+        self
+          .sources
+          .insert(def.name.to_string(), line.trim().to_string());
+        Ok(None)
+      }
+      Statement::Term(term) => {
+        let normal = Evaluator::default()
+          .with_cancel(&self.cancel)
+          .eval(self.expand(Rc::new(term)))?;
+        Ok(Some(normal.to_string()))
+      }
+    }
+  }
+
+  /// Parse a term given to a meta-command, with the session's definitions in scope
+  fn term<'l>(&self, source: &'l str) -> Result<Rc<Node<'l>>, anyhow::Error> {
+    match TermParser::new(source).parse_statement()? {
+      Statement::Term(term) => Ok(self.expand(Rc::new(term))),
+      Statement::Definition(def) => {
+        Err(anyhow!("expected a term, found definition of {}", def.name))
+      }
+    }
+  }
+
+  /// The normal form of a definition, so that terms using it do not reduce its body
+  /// again every time, or the definition as it is if it has no normal form within
+  /// `DEFINITION_FUEL`, like a fixed point combinator
+  fn elaborate(&self, term: Rc<Node<'static>>) -> Result<Rc<Node<'static>>, anyhow::Error> {
+    let evaluator = Evaluator::default()
+      .with_cancel(&self.cancel)
+      .with_fuel(DEFINITION_FUEL);
+    match evaluator.eval(Rc::clone(&term)) {
+      Ok(normal) => Ok(normal),
+      Err(EvalError::StepLimitExceeded) => Ok(term),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  fn expand<'inp>(&self, term: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    definitions::expand(&self.definitions, term)
+  }
+}
+
+/// Whether `name` lexes as a single identifier, and so is safe to put in a file name
+fn is_identifier(name: &str) -> bool {
+  let mut lexer = Lexer::new(name);
+  matches!(
+    lexer.next_token(),
+    Some(token) if token.kind == TokenKind::LowercaseId && token.text == name
+  ) && lexer.next_token().is_none()
+}
+
+/// A row for each strategy with its steps and result, then whether they agree
+fn compare_table(comparison: &Comparison<'_>) -> String {
+  let mut table = Table::new(&[
+    ("strategy", Align::Left),
+    ("steps", Align::Right),
+    ("result", Align::Left),
+  ]);
+  for run in &comparison.runs {
+    let result = match &run.result {
+      Ok(term) => Cell::text(term.to_string()),
+      Err(err) => Cell::text(format!("error: {}", err)).styled(Style::Bad),
+    };
+    table.row(vec![
+      Cell::text(format!("{:?}", run.strategy)),
+      Cell::number(run.steps),
+      result,
+    ]);
+  }
+  let verdict = if comparison.agree() {
+    "all strategies agree"
+  } else {
+    "strategies disagree"
+  };
+  format!("{}{}", table.render(table::use_color()), verdict)
+}
+
+/// Run `$VISUAL` or `$EDITOR` on a file and wait for it to exit, falling back to `vi`
+fn open_editor(path: &Path) -> Result<(), anyhow::Error> {
+  let editor = ["VISUAL", "EDITOR"]
+    .into_iter()
+    .filter_map(|var| env::var(var).ok())
+    .find(|editor| !editor.trim().is_empty())
+    .unwrap_or_else(|| "vi".to_string());
+  let mut words = editor.split_whitespace();
+  let program = words.next().context("the editor command is empty")?;
+  let status = process::Command::new(program)
+    .args(words)
+    .arg(path)
+    .status()
+    .with_context(|| format!("failed to launch {}", editor))?;
+  if !status.success() {
+    bail!("{} exited with {}", editor, status);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  /// What a line gives when handled in a fresh session after `setup`
+  fn reply(setup: &[&str], line: &str) -> Result<String, String> {
+    let mut session = Session::new(CancelToken::new());
+    for line in setup {
+      session.handle(line).map_err(|err| format!("{:#}", err))?;
+    }
+    match session.handle(line) {
+      Ok(Reply::Output(output)) => Ok(output),
+      Ok(Reply::Silent) => Ok(String::new()),
+      Ok(Reply::Quit) => Ok("quit".to_string()),
+      Err(err) => Err(format!("{:#}", err)),
+    }
+  }
+
+  #[rstest]
+  #[case(&[], "(λx.x) y", Ok("y"))]
+  #[case(&[], "id = λx.x", Ok(""))]
+  #[case(&["id = λx.x"], "id y", Ok("y"))]
+  #[case(&["id = λx.x"], ":step id (id y)", Ok("(λx. x) y"))]
+  #[case(&[], ":step y", Ok("already in normal form"))]
+  #[case(&[], ":trace (λx.x) ((λy.y) z)", Ok("(λx. x) ((λy. y) z)\n→ (λx. x) z\n→ z"))]
+  #[case(&["k = λx.λy.x"], ":free k a (b c)", Ok("{a, b, c}"))]
+  #[case(&[], ":debruijn λx.λy.x", Ok("(λ (λ 1))"))]
+  #[case(&[], ":quit", Ok("quit"))]
+  #[case(&[], ":q", Ok("quit"))]
+  #[case(&[], ":frob x", Err("unknown command :frob"))]
+  #[case(&[], ":free id = λx.x", Err("expected a term, found definition of id"))]
+  #[case(&[], ":edit", Err(":edit needs the name of a definition"))]
+  #[case(&[], ":edit ../id", Err("../id is not a name a definition can have"))]
+  #[case(&[], ":edit a b", Err("a b is not a name a definition can have"))]
+  fn handles_lines(
+    #[case] setup: &[&str],
+    #[case] line: &str,
+    #[case] expected: Result<&str, &str>,
+  ) {
+    assert_eq!(
+      reply(setup, line),
+      expected.map(str::to_string).map_err(str::to_string)
+    );
+  }
+
+  #[test]
+  fn updates_dependents_in_order() -> Result<(), anyhow::Error> {
+    let mut session = Session::new(CancelToken::new());
+    for line in ["a = λx.x", "b = a c", "d = b", "e = q", "f = a"] {
+      session.handle(line)?;
+    }
+    session.statement("a = λy.y y")?;
+    assert_eq!(session.redefine_dependents("a")?, ["b", "d", "f"]);
+    assert_eq!(session.definitions["d"].to_string(), "c c");
+    assert!(session.redefine_dependents("e")?.is_empty());
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("id", true)]
+  #[case("x1", true)]
+  #[case("", false)]
+  #[case("Id", false)]
+  #[case("a b", false)]
+  #[case("../a", false)]
+  #[case("λ", false)]
+  fn checks_identifiers(#[case] name: &str, #[case] expected: bool) {
+    assert_eq!(is_identifier(name), expected);
+  }
+}
@@ -0,0 +1,531 @@
+//! Batch runner for `.camel` files whose terms are annotated with their results
+//!
+//! A suite file holds one statement per line, like a file loaded into the REPL.
+//! Lines starting with `--` are comments, except for `-- expect: <term>`, which
+//! gives the result the term on the line before it should reduce to:
+//!
+//! ```text
+//! id = λx.x
+//! id y
+//! -- expect: y
+//! ```
+//!
+//! Every term is a test: one with an expectation passes when both sides reduce to
+//! the same term up to the names of bound variables, and one without passes when
+//! it reduces at all.
+//!
+//! Results are printed in the Test Anything Protocol, or as a table or JSON with
+//! `--output`, and can also be written as JUnit XML or JSON for other tools to
+//! pick up.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+
+use camel::ast::Node;
+use camel::parser::Statement;
+use camel::{json, Evaluator, Parser as TermParser, SourceFile};
+
+use crate::definitions::{self, Definitions};
+use crate::table::{Align, Cell, Output, Style, Table};
+
+/// The result of a single test, named after where its term appears
+struct TestResult {
+  file: String,
+  line: usize,
+  source: String,
+  outcome: Outcome,
+  /// Beta reductions and time taken to reduce the term, not counting its expectation
+  steps: usize,
+  elapsed: Duration,
+}
+
+impl TestResult {
+  fn name(&self) -> String {
+    format!("{}:{}", self.file, self.line)
+  }
+}
+
+enum Outcome {
+  Pass,
+  Fail { expected: String, found: String },
+  Error(String),
+}
+
+/// Formats a report can be written in, besides the TAP printed as tests run
+enum Report {
+  Junit,
+  Json,
+}
+
+impl Report {
+  /// The format for a report file, going by its extension
+  fn for_path(path: &Path) -> Result<Self, anyhow::Error> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("xml") => Ok(Report::Junit),
+      Some("json") => Ok(Report::Json),
+      _ => bail!(
+        "unknown report format for {}, expected a .xml or .json file",
+        path.display()
+      ),
+    }
+  }
+}
+
+/// Run every `.camel` file under `dir`, printing a report in `output` or TAP and
+/// writing another to `report` if given, and fail if any test did not pass
+pub fn run(
+  dir: &Path,
+  evaluator: &Evaluator,
+  output: Option<Output>,
+  report: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+  let format = report.map(Report::for_path).transpose()?;
+  let mut results = Vec::new();
+  for path in discover(dir)? {
+    let source =
+      SourceFile::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    results.extend(SuiteFile::new(&source, evaluator).run());
+  }
+  match output {
+    None => print!("{}", tap(&results)),
+    Some(Output::Table) => table(&results).print(Output::Table),
+    Some(Output::Json) => print!("{}", json(&results)),
+  }
+  if let (Some(path), Some(format)) = (report, format) {
+    let text = match format {
+      Report::Junit => junit(&results),
+      Report::Json => json(&results),
+    };
+    fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))?;
+  }
+  let failed = results
+    .iter()
+    .filter(|result| !matches!(result.outcome, Outcome::Pass))
+    .count();
+  if failed > 0 {
+    bail!("{} of {} tests failed", failed, results.len());
+  }
+  Ok(())
+}
+
+/// Paths of the `.camel` files under `dir`, in a stable order, or `dir` itself if
+/// it is a file
+fn discover(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+  if dir.is_file() {
+    return Ok(vec![dir.to_path_buf()]);
+  }
+  let mut files = Vec::new();
+  let mut dirs = vec![dir.to_path_buf()];
+  while let Some(dir) = dirs.pop() {
+    let entries =
+      fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+      let path = entry?.path();
+      if path.is_dir() {
+        dirs.push(path);
+      } else if path.extension().is_some_and(|ext| ext == "camel") {
+        files.push(path);
+      }
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+/// A suite file being run, with the definitions made so far
+struct SuiteFile<'s> {
+  source: &'s SourceFile,
+  evaluator: &'s Evaluator<'s>,
+  definitions: Definitions,
+  results: Vec<TestResult>,
+}
+
+impl<'s> SuiteFile<'s> {
+  fn new(source: &'s SourceFile, evaluator: &'s Evaluator<'s>) -> Self {
+    SuiteFile {
+      source,
+      evaluator,
+      definitions: Definitions::new(),
+      results: Vec::new(),
+    }
+  }
+
+  fn run(mut self) -> Vec<TestResult> {
+    // a term waits here until the next line shows whether it has an expectation
+    let mut pending: Option<(usize, &str)> = None;
+    for (number, line) in self.source.lines() {
+      let line = line.trim();
+      if line.is_empty() {
+        continue;
+      }
+      if let Some(comment) = line.strip_prefix("--") {
+        if let Some(expected) = comment.trim().strip_prefix("expect:") {
+          match pending.take() {
+            Some((number, term)) => self.test(number, term, Some(expected.trim())),
+            None => self.error(number, line, anyhow!("no term before this expectation")),
+          }
+        }
+        continue;
+      }
+      if let Some((number, term)) = pending.take() {
+        self.test(number, term, None);
+      }
+      match TermParser::new(line).parse_statement() {
+        Ok(Statement::Definition(def)) => {
+          let term = self.expand(Rc::new(def.term.to_static()));
+          self.definitions.insert(def.name.to_string(), term);
+        }
+        Ok(Statement::Term(..)) => pending = Some((number, line)),
+        Err(err) => self.error(number, line, err),
+      }
+    }
+    if let Some((number, term)) = pending {
+      self.test(number, term, None);
+    }
+    self.results
+  }
+
+  /// Reduce the term on line `number`, comparing it with `expected` if given
+  fn test(&mut self, number: usize, source: &str, expected: Option<&str>) {
+    let found = self.reduce(source);
+    let stats = self.evaluator.stats();
+    let outcome = found.and_then(|found| match expected {
+      Some(expected) => {
+        let expected = self
+          .reduce(expected)
+          .context("failed to reduce the expectation")?;
+        if found.alpha_eq(&expected) {
+          return Ok(Outcome::Pass);
+        }
+        Ok(Outcome::Fail {
+          expected: expected.to_string(),
+          found: found.to_string(),
+        })
+      }
+      None => Ok(Outcome::Pass),
+    });
+    let outcome = outcome.unwrap_or_else(|err| Outcome::Error(format!("{:#}", err)));
+    self.push(number, source, outcome);
+    let result = self.results.last_mut().expect("just pushed");
+    result.steps = stats.beta_reductions;
+    result.elapsed = stats.elapsed;
+  }
+
+  /// The normal form of a term with the definitions so far in scope
+  fn reduce<'l>(&self, source: &'l str) -> Result<Rc<Node<'l>>, anyhow::Error> {
+    let term = TermParser::new(source).parse()?;
+    Ok(self.evaluator.eval(self.expand(Rc::new(term)))?)
+  }
+
+  fn expand<'inp>(&self, term: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    definitions::expand(&self.definitions, term)
+  }
+
+  fn error(&mut self, number: usize, source: &str, err: anyhow::Error) {
+    self.push(number, source, Outcome::Error(format!("{:#}", err)));
+  }
+
+  fn push(&mut self, number: usize, source: &str, outcome: Outcome) {
+    self.results.push(TestResult {
+      file: self.source.name().to_string(),
+      line: number,
+      source: source.to_string(),
+      outcome,
+      steps: 0,
+      elapsed: Duration::ZERO,
+    });
+  }
+}
+
+/// Results in the Test Anything Protocol, with a YAML block under each test that
+/// did not pass
+fn tap(results: &[TestResult]) -> String {
+  let mut tap = format!("TAP version 13\n1..{}\n", results.len());
+  for (index, result) in results.iter().enumerate() {
+    let status = match result.outcome {
+      Outcome::Pass => "ok",
+      _ => "not ok",
+    };
+    let _ = writeln!(
+      tap,
+      "{} {} - {} {}",
+      status,
+      index + 1,
+      result.name(),
+      result.source
+    );
+    let _ = match &result.outcome {
+      Outcome::Pass => Ok(()),
+      Outcome::Fail { expected, found } => writeln!(
+        tap,
+        "  ---\n  expected: '{}'\n  found: '{}'\n  ...",
+        expected, found
+      ),
+      Outcome::Error(message) => writeln!(
+        tap,
+        "  ---\n  error: '{}'\n  ...",
+        message.replace('\'', "''")
+      ),
+    };
+  }
+  tap
+}
+
+/// A row for each test, with what went wrong in the last column
+fn table(results: &[TestResult]) -> Table {
+  let mut table = Table::new(&[
+    ("test", Align::Left),
+    ("status", Align::Left),
+    ("steps", Align::Right),
+    ("time ms", Align::Right),
+    ("detail", Align::Left),
+  ]);
+  for result in results {
+    let (status, detail) = match &result.outcome {
+      Outcome::Pass => (Cell::text("pass").styled(Style::Good), String::new()),
+      Outcome::Fail { expected, found } => (
+        Cell::text("fail").styled(Style::Bad),
+        format!("expected {}, found {}", expected, found),
+      ),
+      Outcome::Error(message) => (Cell::text("error").styled(Style::Bad), message.clone()),
+    };
+    table.row(vec![
+      Cell::text(result.name()),
+      status,
+      Cell::number(result.steps),
+      Cell::millis(result.elapsed.as_secs_f64()),
+      Cell::text(detail).styled(Style::Dim),
+    ]);
+  }
+  table
+}
+
+/// A JUnit XML report, with a test suite for each file
+fn junit(results: &[TestResult]) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+  for file in results.chunk_by(|a, b| a.file == b.file) {
+    let count = |error: bool| {
+      file
+        .iter()
+        .filter(|result| match result.outcome {
+          Outcome::Pass => false,
+          Outcome::Fail { .. } => !error,
+          Outcome::Error(..) => error,
+        })
+        .count()
+    };
+    let time: Duration = file.iter().map(|result| result.elapsed).sum();
+    let _ = writeln!(
+      xml,
+      "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">",
+      xml_escape(&file[0].file),
+      file.len(),
+      count(false),
+      count(true),
+      time.as_secs_f64()
+    );
+    for result in file {
+      // the schema has no attribute for the steps, so they go in a property
+      let _ = writeln!(
+        xml,
+        "    <testcase name=\"{} {}\" classname=\"{}\" time=\"{:.6}\">\n      \
+         <properties><property name=\"steps\" value=\"{}\"/></properties>",
+        xml_escape(&result.name()),
+        xml_escape(&result.source),
+        xml_escape(&result.file),
+        result.elapsed.as_secs_f64(),
+        result.steps
+      );
+      match &result.outcome {
+        Outcome::Pass => (),
+        Outcome::Fail { expected, found } => {
+          let _ = writeln!(
+            xml,
+            "      <failure message=\"expected {}, found {}\"/>",
+            xml_escape(expected),
+            xml_escape(found)
+          );
+        }
+        Outcome::Error(message) => {
+          let _ = writeln!(xml, "      <error message=\"{}\"/>", xml_escape(message));
+        }
+      }
+      xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n");
+  }
+  xml.push_str("</testsuites>\n");
+  xml
+}
+
+/// A JSON report, with an object for each test and totals at the end
+fn json(results: &[TestResult]) -> String {
+  let mut json = String::from("{\n  \"tests\": [");
+  for (index, result) in results.iter().enumerate() {
+    let separator = if index == 0 { "" } else { "," };
+    let _ = write!(
+      json,
+      "{}\n    {{\"file\": {}, \"line\": {}, \"source\": {}, ",
+      separator,
+      json::quoted(&result.file),
+      result.line,
+      json::quoted(&result.source)
+    );
+    let _ = match &result.outcome {
+      Outcome::Pass => write!(json, "\"status\": \"pass\", "),
+      Outcome::Fail { expected, found } => write!(
+        json,
+        "\"status\": \"fail\", \"expected\": {}, \"found\": {}, ",
+        json::quoted(expected),
+        json::quoted(found)
+      ),
+      Outcome::Error(message) => write!(
+        json,
+        "\"status\": \"error\", \"error\": {}, ",
+        json::quoted(message)
+      ),
+    };
+    let _ = write!(
+      json,
+      "\"steps\": {}, \"time\": {:.6}}}",
+      result.steps,
+      result.elapsed.as_secs_f64()
+    );
+  }
+  let passed = results
+    .iter()
+    .filter(|result| matches!(result.outcome, Outcome::Pass))
+    .count();
+  let _ = write!(
+    json,
+    "\n  ],\n  \"passed\": {},\n  \"failed\": {}\n}}\n",
+    passed,
+    results.len() - passed
+  );
+  json
+}
+
+fn xml_escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  /// The results of running `text` as a suite file, each timed as taking no time
+  fn run_suite(text: &str) -> Vec<TestResult> {
+    let source = SourceFile::new("t.camel", text);
+    let evaluator = Evaluator::default().with_fuel(100);
+    let mut results = SuiteFile::new(&source, &evaluator).run();
+    for result in &mut results {
+      result.elapsed = Duration::ZERO;
+    }
+    results
+  }
+
+  #[rstest]
+  #[case("id = λx.x\nid y\n-- expect: y", &["2 pass"])]
+  #[case("(λx.x) a\n-- expect: b", &["1 fail: expected b, found a"])]
+  #[case("λx.x\n-- expect: λy.y", &["1 pass"])]
+  #[case("a\n-- a comment\nb", &["1 pass", "3 pass"])]
+  #[case("-- expect: a", &["1 error: no term before this expectation"])]
+  #[case("λx.", &["1 error: Unexpected end of input, expected one of '(', lambda, identifier"])]
+  #[case("(λx.x x) (λx.x x)", &["1 error: Step limit exceeded"])]
+  // a definition only sees the ones made before it
+  #[case("a = y\ny = λz.z\na\n-- expect: y", &["3 fail: expected (λz. z), found y"])]
+  #[case("x\n-- expect: λ", &["1 error: failed to reduce the expectation: Unexpected end of input, expected identifier"])]
+  fn runs_suite_files(#[case] text: &str, #[case] expected: &[&str]) {
+    let outcomes: Vec<_> = run_suite(text)
+      .iter()
+      .map(|result| match &result.outcome {
+        Outcome::Pass => format!("{} pass", result.line),
+        Outcome::Fail { expected, found } => {
+          format!(
+            "{} fail: expected {}, found {}",
+            result.line, expected, found
+          )
+        }
+        Outcome::Error(message) => format!("{} error: {}", result.line, message),
+      })
+      .collect();
+    assert_eq!(outcomes, expected);
+  }
+
+  #[test]
+  fn writes_junit() {
+    let results = run_suite("a\n-- expect: a\nb\n-- expect: c\nλx.");
+    assert_eq!(
+      junit(&results),
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n\
+       \x20 <testsuite name=\"t.camel\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"0.000000\">\n\
+       \x20   <testcase name=\"t.camel:1 a\" classname=\"t.camel\" time=\"0.000000\">\n\
+       \x20     <properties><property name=\"steps\" value=\"0\"/></properties>\n\
+       \x20   </testcase>\n\
+       \x20   <testcase name=\"t.camel:3 b\" classname=\"t.camel\" time=\"0.000000\">\n\
+       \x20     <properties><property name=\"steps\" value=\"0\"/></properties>\n\
+       \x20     <failure message=\"expected c, found b\"/>\n\
+       \x20   </testcase>\n\
+       \x20   <testcase name=\"t.camel:5 λx.\" classname=\"t.camel\" time=\"0.000000\">\n\
+       \x20     <properties><property name=\"steps\" value=\"0\"/></properties>\n\
+       \x20     <error message=\"Unexpected end of input, expected one of &apos;(&apos;, lambda, identifier\"/>\n\
+       \x20   </testcase>\n\
+       \x20 </testsuite>\n</testsuites>\n"
+    );
+  }
+
+  #[test]
+  fn writes_json() {
+    let results = run_suite("(λx.x) a\n-- expect: \"a\"\nb\n-- expect: c");
+    assert_eq!(
+      json(&results),
+      "{\n  \"tests\": [\n    \
+       {\"file\": \"t.camel\", \"line\": 1, \"source\": \"(λx.x) a\", \"status\": \"error\", \
+       \"error\": \"failed to reduce the expectation: Expected one of '(', lambda, identifier, \
+       found character '\\\"'\", \
+       \"steps\": 1, \"time\": 0.000000},\n    \
+       {\"file\": \"t.camel\", \"line\": 3, \"source\": \"b\", \"status\": \"fail\", \
+       \"expected\": \"c\", \"found\": \"b\", \"steps\": 0, \"time\": 0.000000}\n  ],\n  \
+       \"passed\": 0,\n  \"failed\": 2\n}\n"
+    );
+  }
+
+  #[rstest]
+  #[case("plain", "plain")]
+  #[case("a < b && c > d", "a &lt; b &amp;&amp; c &gt; d")]
+  #[case("say \"it's\"", "say &quot;it&apos;s&quot;")]
+  #[case("λx.x", "λx.x")]
+  fn escapes_xml(#[case] text: &str, #[case] expected: &str) {
+    assert_eq!(xml_escape(text), expected);
+  }
+
+  #[test]
+  fn writes_tap() {
+    let results = run_suite("(λx.x) a\n-- expect: a\nb\n-- expect: c\nλx.");
+    assert_eq!(
+      tap(&results),
+      "TAP version 13\n1..3\n\
+       ok 1 - t.camel:1 (λx.x) a\n\
+       not ok 2 - t.camel:3 b\n  ---\n  expected: 'c'\n  found: 'b'\n  ...\n\
+       not ok 3 - t.camel:5 λx.\n  ---\n  \
+       error: 'Unexpected end of input, expected one of ''('', lambda, identifier'\n  ...\n"
+    );
+  }
+}
@@ -0,0 +1,261 @@
+//! Aligned tables for the reports of the subcommands, colored on a terminal, and
+//! the same rows as JSON for scripts
+
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use clap::ValueEnum;
+
+use camel::json::quoted;
+
+/// How a subcommand writes its report
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+  /// An aligned table
+  Table,
+  /// A JSON array with an object for each row
+  Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+  Left,
+  Right,
+}
+
+/// How a cell is colored on a terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+  Plain,
+  Header,
+  Good,
+  Bad,
+  Dim,
+}
+
+impl Style {
+  /// The escape code starting the style
+  fn code(self) -> Option<&'static str> {
+    match self {
+      Style::Plain => None,
+      Style::Header => Some("\x1b[1m"),
+      Style::Good => Some("\x1b[32m"),
+      Style::Bad => Some("\x1b[31m"),
+      Style::Dim => Some("\x1b[2m"),
+    }
+  }
+}
+
+pub struct Cell {
+  text: String,
+  /// Whether the text is a number, written bare in JSON
+  number: bool,
+  style: Style,
+}
+
+impl Cell {
+  pub fn text(text: impl Into<String>) -> Self {
+    Cell {
+      text: text.into(),
+      number: false,
+      style: Style::Plain,
+    }
+  }
+
+  pub fn number(number: impl ToString) -> Self {
+    Cell {
+      number: true,
+      ..Cell::text(number.to_string())
+    }
+  }
+
+  /// A duration in milliseconds, to three places
+  pub fn millis(seconds: f64) -> Self {
+    Cell::number(format!("{:.3}", seconds * 1000.0))
+  }
+
+  pub fn styled(self, style: Style) -> Self {
+    Cell { style, ..self }
+  }
+}
+
+pub struct Table {
+  columns: Vec<(&'static str, Align)>,
+  rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+  pub fn new(columns: &[(&'static str, Align)]) -> Self {
+    Table {
+      columns: columns.to_vec(),
+      rows: Vec::new(),
+    }
+  }
+
+  /// Add a row, with a cell for each column
+  pub fn row(&mut self, cells: Vec<Cell>) {
+    debug_assert_eq!(cells.len(), self.columns.len());
+    self.rows.push(cells);
+  }
+
+  /// Print the table in `output`, colored if stdout is a terminal that allows it
+  pub fn print(&self, output: Output) {
+    match output {
+      Output::Table => print!("{}", self.render(use_color())),
+      Output::Json => println!("{}", self.to_json()),
+    }
+  }
+
+  /// The header and each row on a line, with the columns padded to line up
+  pub fn render(&self, color: bool) -> String {
+    let mut widths: Vec<_> = self
+      .columns
+      .iter()
+      .map(|(h, _)| h.chars().count())
+      .collect();
+    for row in &self.rows {
+      for (width, cell) in widths.iter_mut().zip(row) {
+        *width = (*width).max(cell.text.chars().count());
+      }
+    }
+    let mut text = String::new();
+    let header = self
+      .columns
+      .iter()
+      .map(|&(header, _)| (header, Style::Header));
+    self.line(&mut text, header, &widths, color);
+    for row in &self.rows {
+      let cells = row.iter().map(|cell| (cell.text.as_str(), cell.style));
+      self.line(&mut text, cells, &widths, color);
+    }
+    text
+  }
+
+  /// Write a line of cells padded to `widths`, without trailing spaces
+  fn line<'c>(
+    &self,
+    text: &mut String,
+    cells: impl Iterator<Item = (&'c str, Style)>,
+    widths: &[usize],
+    color: bool,
+  ) {
+    let mut pending = 0;
+    for (index, ((cell, style), (&width, &(_, align)))) in
+      cells.zip(widths.iter().zip(&self.columns)).enumerate()
+    {
+      let padding = width - cell.chars().count();
+      let (before, after) = match align {
+        Align::Left => (0, padding),
+        Align::Right => (padding, 0),
+      };
+      // padding is only written once something follows it on the line
+      pending += if index > 0 { 2 } else { 0 } + before;
+      if cell.is_empty() {
+        pending += after;
+        continue;
+      }
+      text.extend(std::iter::repeat_n(' ', pending));
+      match style.code().filter(|_| color) {
+        Some(code) => {
+          let _ = write!(text, "{}{}\x1b[0m", code, cell);
+        }
+        None => text.push_str(cell),
+      }
+      pending = after;
+    }
+    text.push('\n');
+  }
+
+  /// An object for each row, keyed by the column headers
+  pub fn to_json(&self) -> String {
+    let mut json = String::from("[");
+    for (index, row) in self.rows.iter().enumerate() {
+      json.push_str(if index == 0 { "\n  {" } else { ",\n  {" });
+      for (column, (cell, (header, _))) in row.iter().zip(&self.columns).enumerate() {
+        if column > 0 {
+          json.push_str(", ");
+        }
+        let value = if cell.number {
+          cell.text.clone()
+        } else {
+          quoted(&cell.text)
+        };
+        let _ = write!(json, "{}: {}", quoted(header), value);
+      }
+      json.push('}');
+    }
+    json.push_str(if self.rows.is_empty() { "]" } else { "\n]" });
+    json
+  }
+}
+
+/// Whether to color output: when stdout is a terminal and `NO_COLOR` is not set
+pub fn use_color() -> bool {
+  io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  fn table() -> Table {
+    let mut table = Table::new(&[
+      ("name", Align::Left),
+      ("steps", Align::Right),
+      ("note", Align::Left),
+    ]);
+    table.row(vec![Cell::text("id"), Cell::number(3), Cell::text("")]);
+    table.row(vec![
+      Cell::text("omega"),
+      Cell::number(1000),
+      Cell::text("no \"normal\" form").styled(Style::Bad),
+    ]);
+    table
+  }
+
+  #[test]
+  fn aligns_columns() {
+    assert_eq!(
+      table().render(false),
+      "name   steps  note\n\
+       id         3\n\
+       omega   1000  no \"normal\" form\n"
+    );
+  }
+
+  #[test]
+  fn colors_cells() {
+    let rendered = table().render(true);
+    let lines: Vec<_> = rendered.lines().collect();
+    assert_eq!(
+      lines[0],
+      "\x1b[1mname\x1b[0m   \x1b[1msteps\x1b[0m  \x1b[1mnote\x1b[0m"
+    );
+    assert_eq!(lines[1], "id         3");
+    assert_eq!(lines[2], "omega   1000  \x1b[31mno \"normal\" form\x1b[0m");
+  }
+
+  #[test]
+  fn writes_json() {
+    assert_eq!(
+      table().to_json(),
+      "[\n  \
+       {\"name\": \"id\", \"steps\": 3, \"note\": \"\"},\n  \
+       {\"name\": \"omega\", \"steps\": 1000, \"note\": \"no \\\"normal\\\" form\"}\n\
+       ]"
+    );
+    assert_eq!(Table::new(&[("name", Align::Left)]).to_json(), "[]");
+  }
+
+  #[rstest]
+  #[case(0.0, "0.000")]
+  #[case(0.0012345, "1.234")]
+  #[case(2.5, "2500.000")]
+  fn writes_millis(#[case] seconds: f64, #[case] expected: &str) {
+    let cell = Cell::millis(seconds);
+    assert_eq!(cell.text, expected);
+    assert!(cell.number);
+  }
+}
@@ -0,0 +1,428 @@
+//! A compact binary encoding of terms, for storing and sending large terms
+//!
+//! Bound variables are written as de Bruijn indices and subterms shared through
+//! the same `Rc` are written once, with later occurrences referring back to them,
+//! so the terms produced by substitution stay small on disk. Names are kept in a
+//! table, which lets decoding give back exactly the term that was encoded.
+//!
+//! # Format
+//!
+//! Every number is an unsigned LEB128 varint. An encoding starts with the magic
+//! bytes `CAML` and a version byte, then the number of names followed by each name
+//! as a length and UTF-8 bytes, and then the term in prefix order:
+//!
+//! | tag | followed by                  | meaning                                 |
+//! |-----|------------------------------|-----------------------------------------|
+//! | 0   | de Bruijn index              | a bound variable, 0 for the innermost   |
+//! | 1   | name                         | a free variable                         |
+//! | 2   | name of the parameter, body  | an abstraction                          |
+//! | 3   | function, argument           | an application                          |
+//! | 4   | back reference               | the `n`th remembered subterm, from 0    |
+//!
+//! A term whose tag has the high bit set is remembered once it is complete, in the
+//! order terms are completed, for back references to refer to.
+//!
+//! Version 1 is the only version so far. Any later version will still decode
+//! version 1, and every change to the format gets a new version.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::binary;
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("λx.λy.x (f y)").parse()?);
+//! let bytes = binary::encode(&term);
+//! assert_eq!(binary::decode(&bytes)?, term);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+const MAGIC: &[u8; 4] = b"CAML";
+const VERSION: u8 = 1;
+
+const BOUND: u8 = 0;
+const FREE: u8 = 1;
+const ABSTRACTION: u8 = 2;
+const APPLICATION: u8 = 3;
+const BACK_REFERENCE: u8 = 4;
+/// Set on the tag of a term to be remembered for back references
+const REMEMBER: u8 = 0x80;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeError {
+  #[error("Not an encoded term, as it does not start with the magic bytes")]
+  BadMagic,
+
+  #[error("Unsupported format version {0}")]
+  UnsupportedVersion(u8),
+
+  #[error("Unexpected end of data at byte {0}")]
+  UnexpectedEnd(usize),
+
+  #[error("Invalid tag {tag} at byte {offset}")]
+  InvalidTag { tag: u8, offset: usize },
+
+  #[error("Number too large at byte {0}")]
+  Overflow(usize),
+
+  #[error("Name is not valid UTF-8 at byte {0}")]
+  InvalidName(usize),
+
+  #[error("Reference to undefined {kind} {index} at byte {offset}")]
+  Undefined {
+    kind: &'static str,
+    index: usize,
+    offset: usize,
+  },
+
+  #[error("Trailing data at byte {0}")]
+  TrailingData(usize),
+}
+
+/// Encode a term in the current version of the format
+pub fn encode(node: &Rc<Node<'_>>) -> Vec<u8> {
+  enum Visit<'n, 'inp> {
+    Enter(&'n Rc<Node<'inp>>),
+    Unbind(&'n str),
+    Remember(*const Node<'inp>),
+  }
+
+  let mut names: HashMap<&str, usize> = HashMap::new();
+  let mut name_list = Vec::new();
+  let mut name_id = |name| {
+    *names.entry(name).or_insert_with(|| {
+      name_list.push(name);
+      name_list.len() - 1
+    })
+  };
+
+  let mut body = Vec::new();
+  // depths of the binders in scope for each name, innermost last
+  let mut binders: HashMap<&str, Vec<usize>> = HashMap::new();
+  let mut depth = 0;
+  let mut remembered: HashMap<*const Node, usize> = HashMap::new();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    let node = match visit {
+      Visit::Enter(node) => node,
+      Visit::Unbind(param) => {
+        binders.get_mut(param).expect("bound on entry").pop();
+        depth -= 1;
+        continue;
+      }
+      Visit::Remember(node) => {
+        let next = remembered.len();
+        remembered.insert(node, next);
+        continue;
+      }
+    };
+    if let Some(&index) = remembered.get(&Rc::as_ptr(node)) {
+      body.push(BACK_REFERENCE);
+      write_varint(&mut body, index);
+      continue;
+    }
+    let flag = if Rc::strong_count(node) > 1 {
+      stack.push(Visit::Remember(Rc::as_ptr(node)));
+      REMEMBER
+    } else {
+      0
+    };
+    match &**node {
+      Node::Identifier(id) => match binders.get(id.name.as_ref()).and_then(|d| d.last()) {
+        Some(&bound) => {
+          body.push(BOUND | flag);
+          write_varint(&mut body, depth - 1 - bound);
+        }
+        None => {
+          body.push(FREE | flag);
+          write_varint(&mut body, name_id(id.name.as_ref()));
+        }
+      },
+      Node::Abstraction(abs) => {
+        body.push(ABSTRACTION | flag);
+        write_varint(&mut body, name_id(abs.param.as_ref()));
+        binders.entry(abs.param.as_ref()).or_default().push(depth);
+        depth += 1;
+        stack.push(Visit::Unbind(abs.param.as_ref()));
+        stack.push(Visit::Enter(&abs.body));
+      }
+      Node::Application(app) => {
+        body.push(APPLICATION | flag);
+        stack.push(Visit::Enter(&app.rhs));
+        stack.push(Visit::Enter(&app.lhs));
+      }
+    }
+  }
+
+  let mut bytes = MAGIC.to_vec();
+  bytes.push(VERSION);
+  write_varint(&mut bytes, name_list.len());
+  for name in name_list {
+    write_varint(&mut bytes, name.len());
+    bytes.extend_from_slice(name.as_bytes());
+  }
+  bytes.extend(body);
+  bytes
+}
+
+/// Decode a term encoded by `encode`, sharing the subterms that were shared when
+/// it was encoded
+pub fn decode(bytes: &[u8]) -> Result<Rc<Node<'static>>, DecodeError> {
+  let mut reader = Reader { bytes, offset: 0 };
+  if bytes.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+    return Err(DecodeError::BadMagic);
+  }
+  reader.offset = MAGIC.len();
+  let version = reader.byte()?;
+  if version != VERSION {
+    return Err(DecodeError::UnsupportedVersion(version));
+  }
+  let count = reader.varint()?;
+  let mut names = Vec::new();
+  for _ in 0..count {
+    let len = reader.varint()?;
+    let offset = reader.offset;
+    let name =
+      std::str::from_utf8(reader.take(len)?).map_err(|_| DecodeError::InvalidName(offset))?;
+    names.push(name.to_string());
+  }
+
+  let name = |index: usize, offset| {
+    names
+      .get(index)
+      .map(|name| Cow::Owned(name.clone()))
+      .ok_or(DecodeError::Undefined {
+        kind: "name",
+        index,
+        offset,
+      })
+  };
+  let mut binders: Vec<Cow<'static, str>> = Vec::new();
+  let mut remembered: Vec<Rc<Node<'static>>> = Vec::new();
+  let mut stack: Vec<DecodeFrame> = Vec::new();
+  loop {
+    let offset = reader.offset;
+    let tag = reader.byte()?;
+    let remember = tag & REMEMBER != 0;
+    let mut done = match tag & !REMEMBER {
+      BOUND => {
+        let index = reader.varint()?;
+        let undefined = DecodeError::Undefined {
+          kind: "bound variable",
+          index,
+          offset,
+        };
+        let param = binders
+          .len()
+          .checked_sub(index)
+          .and_then(|len| len.checked_sub(1))
+          .map(|at| binders[at].clone())
+          .ok_or(undefined)?;
+        leaf(param)
+      }
+      FREE => leaf(name(reader.varint()?, offset)?),
+      ABSTRACTION => {
+        binders.push(name(reader.varint()?, offset)?);
+        stack.push(DecodeFrame::Body { remember });
+        continue;
+      }
+      APPLICATION => {
+        stack.push(DecodeFrame::Lhs { remember });
+        continue;
+      }
+      BACK_REFERENCE if !remember => {
+        let index = reader.varint()?;
+        let node = remembered.get(index).ok_or(DecodeError::Undefined {
+          kind: "subterm",
+          index,
+          offset,
+        })?;
+        Rc::clone(node)
+      }
+      _ => return Err(DecodeError::InvalidTag { tag, offset }),
+    };
+    if remember && matches!(tag & !REMEMBER, BOUND | FREE) {
+      remembered.push(Rc::clone(&done));
+    }
+    // hand the finished term to the frames waiting on it, for as long as that
+    // finishes them too
+    loop {
+      let (node, remember) = match stack.pop() {
+        None if reader.offset == bytes.len() => return Ok(done),
+        None => return Err(DecodeError::TrailingData(reader.offset)),
+        Some(DecodeFrame::Lhs { remember }) => {
+          stack.push(DecodeFrame::Rhs {
+            lhs: done,
+            remember,
+          });
+          break;
+        }
+        Some(DecodeFrame::Rhs { lhs, remember }) => {
+          (Node::Application(Application { lhs, rhs: done }), remember)
+        }
+        Some(DecodeFrame::Body { remember }) => {
+          let param = binders.pop().expect("pushed with the frame");
+          (
+            Node::Abstraction(Abstraction { param, body: done }),
+            remember,
+          )
+        }
+      };
+      done = Rc::new(node);
+      if remember {
+        remembered.push(Rc::clone(&done));
+      }
+    }
+  }
+}
+
+fn leaf(name: Cow<'static, str>) -> Rc<Node<'static>> {
+  Rc::new(Node::Identifier(Identifier { name }))
+}
+
+/// A compound term waiting on its next subterm while decoding
+enum DecodeFrame {
+  Body {
+    remember: bool,
+  },
+  Lhs {
+    remember: bool,
+  },
+  Rhs {
+    lhs: Rc<Node<'static>>,
+    remember: bool,
+  },
+}
+
+struct Reader<'b> {
+  bytes: &'b [u8],
+  offset: usize,
+}
+
+impl<'b> Reader<'b> {
+  fn byte(&mut self) -> Result<u8, DecodeError> {
+    let byte = *self
+      .bytes
+      .get(self.offset)
+      .ok_or(DecodeError::UnexpectedEnd(self.offset))?;
+    self.offset += 1;
+    Ok(byte)
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+    let end = self
+      .offset
+      .checked_add(len)
+      .filter(|&end| end <= self.bytes.len())
+      .ok_or(DecodeError::UnexpectedEnd(self.bytes.len()))?;
+    let taken = &self.bytes[self.offset..end];
+    self.offset = end;
+    Ok(taken)
+  }
+
+  fn varint(&mut self) -> Result<usize, DecodeError> {
+    let start = self.offset;
+    let mut value: usize = 0;
+    let mut shift = 0;
+    loop {
+      let byte = self.byte()?;
+      let bits = usize::from(byte & 0x7f);
+      if shift >= usize::BITS || (bits << shift) >> shift != bits {
+        return Err(DecodeError::Overflow(start));
+      }
+      value |= bits << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+      shift += 7;
+    }
+  }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+  while value >= 0x80 {
+    bytes.push(value as u8 | 0x80);
+    value >>= 7;
+  }
+  bytes.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x")]
+  #[case("λx.x")]
+  #[case("λx.λy.x y z")]
+  #[case("λx.λx.x")]
+  #[case("(λx.x x) (λy.λx.y x)")]
+  #[case("λx.(λx.x) x")]
+  fn round_trip(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(decode(&encode(&term))?, term);
+    Ok(())
+  }
+
+  #[test]
+  fn shared_subterms_are_written_once() -> Result<(), anyhow::Error> {
+    let arg = Rc::new(Parser::new("λa.λb.λc.a b c").parse()?);
+    let term = crate::eval::eval(Rc::new(Parser::new("λf.f x x x x").parse()?));
+    let spread = crate::eval::substitute(&term, "x", &arg);
+    let bytes = encode(&spread);
+    assert!(bytes.len() < encode(&Rc::new(spread.to_static())).len());
+    let decoded = decode(&bytes)?;
+    assert_eq!(decoded, spread);
+    let Node::Abstraction(abs) = &*decoded else {
+      unreachable!("decoded an abstraction");
+    };
+    let Node::Application(app) = &*abs.body else {
+      unreachable!("decoded an application");
+    };
+    assert_eq!(Rc::strong_count(&app.rhs), 4);
+    Ok(())
+  }
+
+  #[test]
+  fn deep_term_does_not_overflow() {
+    let mut term = Rc::new(Node::Identifier(Identifier { name: "x".into() }));
+    for _ in 0..100_000 {
+      term = Rc::new(Node::Abstraction(Abstraction {
+        param: "x".into(),
+        body: Rc::new(Node::Application(Application {
+          lhs: term,
+          rhs: Rc::new(Node::Identifier(Identifier { name: "x".into() })),
+        })),
+      }));
+    }
+    let bytes = encode(&term);
+    assert_eq!(encode(&decode(&bytes).expect("valid encoding")), bytes);
+  }
+
+  #[rstest]
+  #[case(b"", DecodeError::BadMagic)]
+  #[case(b"CAML\x02", DecodeError::UnsupportedVersion(2))]
+  #[case(b"CAML\x01\x00", DecodeError::UnexpectedEnd(6))]
+  #[case(b"CAML\x01\x00\x00\x00", DecodeError::Undefined { kind: "bound variable", index: 0, offset: 6 })]
+  #[case(b"CAML\x01\x00\x01\x00", DecodeError::Undefined { kind: "name", index: 0, offset: 6 })]
+  #[case(b"CAML\x01\x00\x04\x00", DecodeError::Undefined { kind: "subterm", index: 0, offset: 6 })]
+  #[case(b"CAML\x01\x00\x09", DecodeError::InvalidTag { tag: 9, offset: 6 })]
+  #[case(b"CAML\x01\x01\x01x\x01\x00\x01\x00", DecodeError::TrailingData(10))]
+  #[case(b"CAML\x01\x01\x01\xff", DecodeError::InvalidName(7))]
+  #[case(
+    b"CAML\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01",
+    DecodeError::Overflow(5)
+  )]
+  fn invalid_data(#[case] bytes: &[u8], #[case] expected: DecodeError) {
+    assert_eq!(decode(bytes), Err(expected));
+  }
+}
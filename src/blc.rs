@@ -0,0 +1,222 @@
+//! Reading and writing John Tromp's binary lambda calculus
+//!
+//! BLC is the notation of Tromp's `lc` tools and of many other implementations,
+//! which makes it a common ground for checking results against them. Terms are
+//! written nameless, as strings of bits in prefix order:
+//!
+//! | term            | bits                                       |
+//! |-----------------|--------------------------------------------|
+//! | `λ M`           | `00` then `M`                              |
+//! | `M N`           | `01` then `M` then `N`                     |
+//! | de Bruijn index | `n + 1` ones then a zero, from 0 innermost |
+//!
+//! Only closed terms can be written. Binder names are lost on the way, so read
+//! terms name their parameters after how many binders enclose them: `x0`, `x1`
+//! and so on.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::blc;
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("λx.λy.x").parse()?);
+//! assert_eq!(blc::write(&term)?, "0000110");
+//! assert_eq!(blc::read("0000110")?.to_string(), "(λx0. (λx1. x0))");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BlcError {
+  #[error("Free variable {0} cannot be written in binary lambda calculus")]
+  FreeVariable(String),
+
+  #[error("Unexpected character '{found}' at {offset}, expected a bit")]
+  NotABit { found: char, offset: usize },
+
+  #[error("Unexpected end of input")]
+  UnexpectedEnd,
+
+  #[error("Index {index} at {offset} refers past the outermost binder")]
+  UnboundIndex { index: usize, offset: usize },
+
+  #[error("Trailing bits at {0}")]
+  TrailingBits(usize),
+}
+
+/// Write a closed term as a string of `0` and `1`
+pub fn write(node: &Node<'_>) -> Result<String, BlcError> {
+  enum Visit<'n> {
+    Enter(&'n Node<'n>),
+    Unbind(&'n str),
+  }
+
+  let mut bits = String::new();
+  // depths of the binders in scope for each name, innermost last
+  let mut binders: HashMap<&str, Vec<usize>> = HashMap::new();
+  let mut depth = 0;
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        bits.push_str("00");
+        binders.entry(abs.param.as_ref()).or_default().push(depth);
+        depth += 1;
+        stack.push(Visit::Unbind(abs.param.as_ref()));
+        stack.push(Visit::Enter(&abs.body));
+      }
+      Visit::Enter(Node::Application(app)) => {
+        bits.push_str("01");
+        stack.push(Visit::Enter(&app.rhs));
+        stack.push(Visit::Enter(&app.lhs));
+      }
+      Visit::Enter(Node::Identifier(id)) => {
+        let Some(&bound) = binders.get(id.name.as_ref()).and_then(|d| d.last()) else {
+          return Err(BlcError::FreeVariable(id.name.to_string()));
+        };
+        bits.extend(std::iter::repeat_n('1', depth - bound));
+        bits.push('0');
+      }
+      Visit::Unbind(param) => {
+        binders.get_mut(param).expect("bound on entry").pop();
+        depth -= 1;
+      }
+    }
+  }
+  Ok(bits)
+}
+
+/// Read a term from a string of `0` and `1`, ignoring any whitespace between them
+pub fn read(text: &str) -> Result<Rc<Node<'static>>, BlcError> {
+  /// A compound term waiting on its next subterm
+  enum Frame {
+    Body,
+    Lhs,
+    Rhs(Rc<Node<'static>>),
+  }
+
+  let mut bits =
+    text
+      .char_indices()
+      .filter(|(_, c)| !c.is_whitespace())
+      .map(|(offset, c)| match c {
+        '0' => Ok((offset, false)),
+        '1' => Ok((offset, true)),
+        found => Err(BlcError::NotABit { found, offset }),
+      });
+  let mut next = || bits.next().unwrap_or(Err(BlcError::UnexpectedEnd));
+  let mut depth = 0;
+  let mut stack = Vec::new();
+  loop {
+    let (offset, first) = next()?;
+    let mut done = if first {
+      let mut index = 0;
+      while next()?.1 {
+        index += 1;
+      }
+      if index >= depth {
+        return Err(BlcError::UnboundIndex { index, offset });
+      }
+      Rc::new(Node::Identifier(Identifier {
+        name: param(depth - 1 - index),
+      }))
+    } else {
+      match next()?.1 {
+        false => {
+          depth += 1;
+          stack.push(Frame::Body);
+        }
+        true => stack.push(Frame::Lhs),
+      }
+      continue;
+    };
+    loop {
+      done = match stack.pop() {
+        None => {
+          return match next() {
+            Err(BlcError::UnexpectedEnd) => Ok(done),
+            Err(err) => Err(err),
+            Ok((offset, _)) => Err(BlcError::TrailingBits(offset)),
+          }
+        }
+        Some(Frame::Lhs) => {
+          stack.push(Frame::Rhs(done));
+          break;
+        }
+        Some(Frame::Rhs(lhs)) => Rc::new(Node::Application(Application { lhs, rhs: done })),
+        Some(Frame::Body) => {
+          depth -= 1;
+          Rc::new(Node::Abstraction(Abstraction {
+            param: param(depth),
+            body: done,
+          }))
+        }
+      };
+    }
+  }
+}
+
+/// The name given to the parameter of a read binder with `depth` binders around it
+fn param(depth: usize) -> Cow<'static, str> {
+  Cow::Owned(format!("x{}", depth))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("λx.x", "0010")]
+  #[case("λx.λy.x", "0000110")]
+  #[case("λx.λy.y", "000010")]
+  #[case("λf.λx.f (f x)", "0000011100111010")]
+  #[case("(λx.x x) (λx.x x)", "010001101000011010")]
+  #[case("λx.λx.x", "000010")]
+  fn writes_known_encodings(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(write(&term)?, expected);
+    let read_back = read(expected)?;
+    assert_eq!(write(&read_back)?, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("0000110", "(λx0. (λx1. x0))")]
+  #[case("00 01 10 10", "(λx0. x0 x0)")]
+  fn reads_with_generated_names(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    assert_eq!(read(input)?.to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn free_variables_cannot_be_written() -> Result<(), anyhow::Error> {
+    let term = Parser::new("λx.y").parse()?;
+    assert_eq!(write(&term), Err(BlcError::FreeVariable("y".to_string())));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("", BlcError::UnexpectedEnd)]
+  #[case("001", BlcError::UnexpectedEnd)]
+  #[case("0x", BlcError::NotABit { found: 'x', offset: 1 })]
+  #[case("00110", BlcError::UnboundIndex { index: 1, offset: 2 })]
+  #[case("00100", BlcError::TrailingBits(4))]
+  fn invalid_bits(#[case] input: &str, #[case] expected: BlcError) {
+    assert_eq!(read(input), Err(expected));
+  }
+}
@@ -0,0 +1,116 @@
+//! What this build of camel supports, for tools embedding it to find out
+//!
+//! ```
+//! let capabilities = camel::capabilities();
+//! assert!(capabilities.front_ends.iter().any(|name| name == "text"));
+//! assert_eq!(capabilities.strategies.len(), camel::EvalStrategy::ALL.len());
+//! ```
+
+use std::fmt;
+
+#[cfg(feature = "formats")]
+use crate::json;
+use crate::registry::Registry;
+
+/// The features, strategies, formats and syntax of the linked build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+  pub version: &'static str,
+  /// Cargo features the crate was built with
+  pub features: Vec<&'static str>,
+  pub strategies: Vec<String>,
+  /// Formats terms can be read from
+  pub front_ends: Vec<String>,
+  /// Formats terms can be written to
+  pub back_ends: Vec<String>,
+  /// Syntax accepted on top of the plain lambda calculus
+  pub extensions: Vec<&'static str>,
+}
+
+/// Everything supported by this build, with the strategies and formats of
+/// `Registry::builtin`
+pub fn capabilities() -> Capabilities {
+  Registry::builtin().capabilities()
+}
+
+impl Capabilities {
+  /// What is fixed when the crate is built, leaving out what is registered
+  pub(crate) fn build() -> Self {
+    let features = [
+      ("cli", cfg!(feature = "cli")),
+      ("repl", cfg!(feature = "repl")),
+      ("machines", cfg!(feature = "machines")),
+      ("formats", cfg!(feature = "formats")),
+      ("bignum", cfg!(feature = "bignum")),
+      ("profile", cfg!(feature = "profile")),
+      ("serde", cfg!(feature = "serde")),
+    ];
+    Capabilities {
+      version: env!("CARGO_PKG_VERSION"),
+      features: features
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect(),
+      strategies: Vec::new(),
+      front_ends: Vec::new(),
+      back_ends: Vec::new(),
+      extensions: vec!["backslash-lambda", "definitions"],
+    }
+  }
+
+  /// An object with a key for each field
+  #[cfg(feature = "formats")]
+  pub fn to_json(&self) -> String {
+    fn list<S: AsRef<str>>(items: &[S]) -> String {
+      let items: Vec<_> = items
+        .iter()
+        .map(|item| json::quoted(item.as_ref()))
+        .collect();
+      format!("[{}]", items.join(","))
+    }
+
+    format!(
+      r#"{{"version":{},"features":{},"strategies":{},"front_ends":{},"back_ends":{},"extensions":{}}}"#,
+      json::quoted(self.version),
+      list(&self.features),
+      list(&self.strategies),
+      list(&self.front_ends),
+      list(&self.back_ends),
+      list(&self.extensions)
+    )
+  }
+}
+
+impl fmt::Display for Capabilities {
+  /// A line for each field, listing its values
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "version     {}", self.version)?;
+    writeln!(f, "features    {}", self.features.join(", "))?;
+    writeln!(f, "strategies  {}", self.strategies.join(", "))?;
+    writeln!(f, "front ends  {}", self.front_ends.join(", "))?;
+    writeln!(f, "back ends   {}", self.back_ends.join(", "))?;
+    write!(f, "extensions  {}", self.extensions.join(", "))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn describes_the_build() {
+    let capabilities = capabilities();
+    assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+      capabilities.features.contains(&"serde"),
+      cfg!(feature = "serde")
+    );
+    assert_eq!(capabilities.to_string().lines().count(), 6);
+    #[cfg(feature = "formats")]
+    {
+      let json = capabilities.to_json();
+      assert!(json.starts_with(r#"{"version":""#));
+      assert!(json.contains(r#""strategies":["NormalOrder","ApplicativeOrder","#));
+    }
+  }
+}
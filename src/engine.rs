@@ -0,0 +1,339 @@
+//! A self-contained evaluation context, for embedding camel in a host program
+//!
+//! An [`Engine`] owns everything an evaluation needs beyond the term itself: the
+//! definitions in scope and the interner naming them, the registry of strategies
+//! and formats, the limits, a cache of the normal forms already found, and a
+//! hash-cons store through which the definitions and normal forms share their
+//! common subterms. Nothing is shared between engines, so a server or notebook
+//! can give each session its own and they never see each other's definitions. Terms are reference counted without atomics, so an engine
+//! stays on the thread that made it, and each thread makes its own.
+//!
+//! ```
+//! # use camel::engine::Engine;
+//! let mut alice = Engine::new();
+//! let mut bob = Engine::new();
+//! alice.run("id = λx.x")?;
+//! bob.run("id = λx.λy.y")?;
+//! assert_eq!(alice.run("id a b")?.unwrap().to_string(), "a b");
+//! assert_eq!(bob.run("id a b")?.unwrap().to_string(), "b");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+
+use crate::ast::{Alpha, Node};
+use crate::eval::hooks::Hooks;
+use crate::eval::{substitute_all, CancelToken, EvalStats, EvalStrategy, Evaluator};
+use crate::hashcons::HashCons;
+use crate::parser::{Parser, Statement};
+use crate::registry::Registry;
+use crate::symbol::{Interner, Symbol};
+
+/// Definitions, registry, limits and cache for one independent session
+pub struct Engine {
+  registry: Registry,
+  strategy: String,
+  max_steps: Option<usize>,
+  cancel: CancelToken,
+  names: Interner,
+  /// Holds one copy of every definition and normal form, and of their subterms
+  terms: HashCons,
+  /// Each stored with earlier definitions already substituted in
+  definitions: HashMap<Symbol, Rc<Node<'static>>>,
+  /// Normal forms by the strategy in use, keyed by the term with every definition
+  /// substituted in
+  normal_forms: HashMap<Alpha<'static>, Rc<Node<'static>>>,
+  stats: EvalStats,
+  hooks: Hooks<'static>,
+}
+
+impl Default for Engine {
+  fn default() -> Self {
+    Engine::new()
+  }
+}
+
+impl Engine {
+  /// An engine with the built in registry, no definitions and no limits, evaluating
+  /// in the default strategy
+  pub fn new() -> Self {
+    Engine::with_registry(Registry::builtin())
+  }
+
+  pub fn with_registry(registry: Registry) -> Self {
+    Engine {
+      registry,
+      strategy: EvalStrategy::default().name().to_string(),
+      max_steps: None,
+      cancel: CancelToken::new(),
+      names: Interner::new(),
+      terms: HashCons::new(),
+      definitions: HashMap::new(),
+      normal_forms: HashMap::new(),
+      stats: EvalStats::default(),
+      hooks: Hooks::new(),
+    }
+  }
+
+  /// Evaluate by the strategy registered as `name`
+  pub fn with_strategy(mut self, name: &str) -> Result<Self, anyhow::Error> {
+    if self.registry.strategy(name).is_none() {
+      return Err(anyhow!("unknown strategy {}", name));
+    }
+    self.strategy = name.to_string();
+    self.normal_forms.clear();
+    Ok(self)
+  }
+
+  /// Give up on each evaluation after `max_steps` beta reductions
+  pub fn with_fuel(mut self, max_steps: usize) -> Self {
+    self.max_steps = Some(max_steps);
+    self
+  }
+
+  pub fn registry(&self) -> &Registry {
+    &self.registry
+  }
+
+  /// The registry, to register more strategies and formats with. This forgets the
+  /// normal forms found so far, as the strategy in use may be replaced
+  pub fn registry_mut(&mut self) -> &mut Registry {
+    self.normal_forms.clear();
+    &mut self.registry
+  }
+
+  /// The hooks fired by this engine's evaluations, and on each definition expanded
+  /// into a term
+  pub fn hooks_mut(&mut self) -> &mut Hooks<'static> {
+    &mut self.hooks
+  }
+
+  /// The token cancelling this engine's evaluations, which can be cloned and
+  /// triggered from another thread
+  pub fn cancel_token(&self) -> &CancelToken {
+    &self.cancel
+  }
+
+  /// Define `name` as `term`, with the definitions so far substituted in, so that
+  /// later redefining those does not change this one
+  pub fn define(&mut self, name: &str, term: &Node<'_>) {
+    let term = self.terms.intern(&self.expand(Rc::new(term.to_static())));
+    let symbol = self.names.intern(name);
+    self.definitions.insert(symbol, term);
+  }
+
+  pub fn definition(&self, name: &str) -> Option<&Rc<Node<'static>>> {
+    self.definitions.get(&self.names.get(name)?)
+  }
+
+  /// The names of the definitions made so far
+  pub fn interner(&self) -> &Interner {
+    &self.names
+  }
+
+  /// Evaluate `term` with the definitions in scope, reusing the normal form of an
+  /// alpha-equivalent term evaluated before
+  pub fn eval(&mut self, term: &Node<'_>) -> Result<Rc<Node<'static>>, anyhow::Error> {
+    self.cancel.reset();
+    self.stats = EvalStats::default();
+    let term = self.expand(Rc::new(term.to_static()));
+    let key = Alpha(Rc::clone(&term));
+    if let Some(normal) = self.normal_forms.get(&key) {
+      self.hooks.normal_form(normal, 0);
+      return Ok(Rc::clone(normal));
+    }
+    let strategy = self
+      .registry
+      .strategy(&self.strategy)
+      .ok_or_else(|| anyhow!("unknown strategy {}", self.strategy))?;
+    let mut evaluator = Evaluator::default()
+      .with_cancel(&self.cancel)
+      .with_hooks(&self.hooks);
+    if let Some(max_steps) = self.max_steps {
+      evaluator = evaluator.with_fuel(max_steps);
+    }
+    let result = evaluator.eval_with(strategy, term);
+    self.stats = evaluator.stats();
+    let normal = self.terms.intern(&*result?);
+    self.normal_forms.insert(key, Rc::clone(&normal));
+    Ok(normal)
+  }
+
+  /// Run a line of source, returning the normal form of a term or nothing for a
+  /// definition
+  pub fn run(&mut self, source: &str) -> Result<Option<Rc<Node<'static>>>, anyhow::Error> {
+    match Parser::new(source).parse_statement()? {
+      Statement::Definition(def) => {
+        self.define(def.name, &def.term);
+        Ok(None)
+      }
+      Statement::Term(term) => self.eval(&term).map(Some),
+    }
+  }
+
+  /// Figures about the most recent call to `eval`, all zero if the normal form was
+  /// already cached
+  pub fn stats(&self) -> EvalStats {
+    self.stats
+  }
+
+  /// Number of normal forms cached
+  pub fn cached(&self) -> usize {
+    self.normal_forms.len()
+  }
+
+  /// The store sharing subterms between definitions and normal forms
+  pub fn terms(&self) -> &HashCons {
+    &self.terms
+  }
+
+  /// Substitute every definition into `term` at once, in the same way whatever
+  /// order they are stored in
+  fn expand<'inp>(&self, term: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    let mut symbols: Vec<_> = self.definitions.keys().copied().collect();
+    symbols.sort_unstable();
+    let bindings: Vec<(&str, Rc<Node<'inp>>)> = symbols
+      .into_iter()
+      .map(|symbol| {
+        let name = self.names.resolve(symbol).expect("interned by this engine");
+        (
+          name,
+          Rc::clone(&self.definitions[&symbol]) as Rc<Node<'inp>>,
+        )
+      })
+      .collect();
+    if !self.hooks.is_empty() {
+      let free = term.free_vars();
+      for (name, _) in bindings.iter().filter(|(name, _)| free.contains(name)) {
+        self.hooks.definition_expanded(name);
+      }
+    }
+    substitute_all(&term, &bindings)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::eval::hooks::Event;
+  use crate::EvalError;
+  use std::cell::RefCell;
+  use std::thread;
+
+  #[test]
+  fn engines_are_independent() {
+    let results: Vec<_> = thread::scope(|scope| {
+      let handles: Vec<_> = ["λx.λy.x", "λx.λy.y"]
+        .into_iter()
+        .map(|choice| {
+          scope.spawn(move || -> Result<String, String> {
+            let mut engine = Engine::new();
+            engine
+              .run(&format!("pick = {}", choice))
+              .map_err(|e| e.to_string())?;
+            let normal = engine.run("pick a b").map_err(|e| e.to_string())?;
+            Ok(normal.map(|n| n.to_string()).unwrap_or_default())
+          })
+        })
+        .collect();
+      handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(results, [Ok("a".to_string()), Ok("b".to_string())]);
+  }
+
+  #[test]
+  fn caches_normal_forms() -> Result<(), anyhow::Error> {
+    let mut engine = Engine::new();
+    engine.run("two = λf.λx.f (f x)")?;
+    let first = engine.run("two two")?.unwrap();
+    assert!(engine.stats().beta_reductions > 0);
+    let again = engine.eval(&Parser::new("(λg.λy.g (g y)) (λf.λx.f (f x))").parse()?)?;
+    assert!(Rc::ptr_eq(&first, &again));
+    assert_eq!(engine.stats().beta_reductions, 0);
+    assert_eq!(engine.cached(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn fires_hooks() -> Result<(), anyhow::Error> {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    let hooks = engine.hooks_mut();
+    hooks.on_definition({
+      let log = Rc::clone(&log);
+      move |event| {
+        if let Event::DefinitionExpanded { name } = event {
+          log.borrow_mut().push(name.to_string());
+        }
+      }
+    });
+    hooks.on_normal_form({
+      let log = Rc::clone(&log);
+      move |event| log.borrow_mut().push(format!("{:?}", event))
+    });
+    engine.run("id = λx.x")?;
+    engine.run("id y")?;
+    assert_eq!(
+      *log.borrow(),
+      [
+        "id",
+        "NormalForm { term: Identifier(Identifier { name: \"y\" }), steps: 1 }"
+      ]
+    );
+    log.borrow_mut().clear();
+    engine.run("id y")?;
+    assert_eq!(
+      *log.borrow(),
+      [
+        "id",
+        "NormalForm { term: Identifier(Identifier { name: \"y\" }), steps: 0 }"
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn shares_subterms() -> Result<(), anyhow::Error> {
+    let mut engine = Engine::new();
+    engine.run("k = λx.λy.x")?;
+    engine.run("f = a (λx.λy.x)")?;
+    let normal = engine.run("(λz.z) (λx.λy.x)")?.unwrap();
+    let Node::Application(app) = &**engine.definition("f").unwrap() else {
+      panic!("f is an application");
+    };
+    assert!(Rc::ptr_eq(engine.definition("k").unwrap(), &app.rhs));
+    assert!(Rc::ptr_eq(engine.definition("k").unwrap(), &normal));
+    Ok(())
+  }
+
+  #[test]
+  fn expands_definitions_made_before_their_references() -> Result<(), anyhow::Error> {
+    let mut engine = Engine::new();
+    for line in ["a = y", "y = λz.z", "b = w", "w = q", "c = v", "v = r"] {
+      engine.run(line)?;
+    }
+    let normal = engine.run("a b c")?.unwrap();
+    assert_eq!(normal.to_string(), "y w v");
+    assert_eq!(engine.definition("a").unwrap().to_string(), "y");
+    Ok(())
+  }
+
+  #[test]
+  fn keeps_limits_and_strategy() -> Result<(), anyhow::Error> {
+    let mut engine = Engine::new().with_strategy("NormalOrder")?.with_fuel(100);
+    assert_eq!(
+      engine
+        .run("(λx.y) ((λx.x x) (λx.x x))")?
+        .unwrap()
+        .to_string(),
+      "y"
+    );
+    let err = engine.run("(λx.x x) (λx.x x)").unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&EvalError::StepLimitExceeded));
+    assert!(Engine::new().with_strategy("Eager").is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,2069 @@
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::ast::{Abstraction, Alpha, Application, Direction, Identifier, Node, Path};
+use crate::hashcons::HashCons;
+use crate::names::fresh_name;
+use crate::registry::Strategy;
+use hooks::Hooks;
+
+pub mod combinators;
+#[cfg(feature = "machines")]
+pub mod env;
+pub mod graph;
+pub mod hooks;
+#[cfg(feature = "machines")]
+mod krivine;
+#[cfg(feature = "machines")]
+pub mod secd;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvalError {
+  #[error("Evaluation was cancelled")]
+  Cancelled,
+
+  #[error("Step limit exceeded")]
+  StepLimitExceeded,
+
+  /// A term recurred, up to the names of bound variables, so reduction would
+  /// cycle through `cycle` forever
+  #[error("Reduction loops: {} → {}", .cycle.join(" → "), .cycle[0])]
+  LoopDetected { cycle: Vec<String> },
+
+  #[error("Term keeps growing, reaching {size} nodes after {steps} steps")]
+  UnboundedGrowth { steps: usize, size: usize },
+
+  /// The interpreter panicked, with the message it panicked with
+  #[error("Internal error: {0}")]
+  Internal(String),
+}
+
+/// A flag shared between a running evaluation and whoever may want to abort it
+///
+/// Clones refer to the same flag, so a token can be handed to another thread
+/// (or a signal handler) and triggered there
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn new() -> Self {
+    CancelToken::default()
+  }
+
+  /// Ask every evaluation watching this token to stop at its next safepoint
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  /// Clear a previous cancellation so the token can be reused
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// The order in which redexes are contracted, and how far reduction goes
+///
+/// The strategies which stop at weak head normal form, the machines among them,
+/// go on to a full normal form with [`Evaluator::with_strong_reduction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum EvalStrategy {
+  /// Leftmost outermost redex first, reducing under binders, which reaches a normal
+  /// form whenever one exists
+  NormalOrder,
+  /// Arguments are normalized before they are substituted, reducing under binders
+  #[default]
+  ApplicativeOrder,
+  /// Leftmost outermost redex first, stopping at weak head normal form
+  CallByName,
+  /// Arguments are reduced to values before they are substituted, stopping at weak
+  /// head normal form
+  CallByValue,
+  /// Call by name on a Krivine machine, which binds arguments in environments
+  /// instead of substituting them and only rebuilds the term at the end
+  #[cfg(feature = "machines")]
+  Krivine,
+  /// Call by value on the CEK machine of `eval::env`, reading the resulting value
+  /// back into a term
+  #[cfg(feature = "machines")]
+  Cek,
+  /// Call by value by compiling to instructions for the SECD machine of
+  /// `eval::secd`, reading the resulting value back into a term
+  #[cfg(feature = "machines")]
+  Secd,
+}
+
+impl EvalStrategy {
+  /// Every strategy in this build, in the order they are declared
+  pub const ALL: &'static [EvalStrategy] = &[
+    EvalStrategy::NormalOrder,
+    EvalStrategy::ApplicativeOrder,
+    EvalStrategy::CallByName,
+    EvalStrategy::CallByValue,
+    #[cfg(feature = "machines")]
+    EvalStrategy::Krivine,
+    #[cfg(feature = "machines")]
+    EvalStrategy::Cek,
+    #[cfg(feature = "machines")]
+    EvalStrategy::Secd,
+  ];
+}
+
+impl EvalStrategy {
+  /// The name of the variant, as it is debug printed
+  pub fn name(self) -> &'static str {
+    match self {
+      EvalStrategy::NormalOrder => "NormalOrder",
+      EvalStrategy::ApplicativeOrder => "ApplicativeOrder",
+      EvalStrategy::CallByName => "CallByName",
+      EvalStrategy::CallByValue => "CallByValue",
+      #[cfg(feature = "machines")]
+      EvalStrategy::Krivine => "Krivine",
+      #[cfg(feature = "machines")]
+      EvalStrategy::Cek => "Cek",
+      #[cfg(feature = "machines")]
+      EvalStrategy::Secd => "Secd",
+    }
+  }
+
+  /// Whether the strategy reduces under binders and inside arguments, reaching a
+  /// full normal form rather than stopping at weak head normal form
+  pub fn normalizes_fully(self) -> bool {
+    matches!(
+      self,
+      EvalStrategy::NormalOrder | EvalStrategy::ApplicativeOrder
+    )
+  }
+}
+
+/// Evaluate a term to its normal form using applicative order
+///
+/// Both sides of an application are reduced before the argument is substituted
+/// into the body of the abstraction, and reduction continues under binders
+pub fn eval<'inp>(node: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+  eval_with_strategy(node, EvalStrategy::ApplicativeOrder)
+}
+
+/// Evaluate a term, contracting redexes in the order given by `strategy`
+pub fn eval_with_strategy<'inp>(node: Rc<Node<'inp>>, strategy: EvalStrategy) -> Rc<Node<'inp>> {
+  Evaluator::new(strategy)
+    .eval(node)
+    .expect("evaluation without limits cannot fail")
+}
+
+/// Evaluate a term like `eval`, giving up with `EvalError::Cancelled` once `cancel`
+/// is triggered
+pub fn eval_with_cancel<'inp>(
+  node: Rc<Node<'inp>>,
+  cancel: &CancelToken,
+) -> Result<Rc<Node<'inp>>, EvalError> {
+  Evaluator::default().with_cancel(cancel).eval(node)
+}
+
+/// Evaluate a term like `eval`, giving up with `EvalError::StepLimitExceeded` if
+/// more than `max_steps` beta reductions would be needed
+pub fn eval_with_fuel<'inp>(
+  node: Rc<Node<'inp>>,
+  max_steps: usize,
+) -> Result<Rc<Node<'inp>>, EvalError> {
+  Evaluator::default().with_fuel(max_steps).eval(node)
+}
+
+/// How far `normalize` reduces a term before it is considered done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum NormalForm {
+  /// An abstraction, or a variable applied to any arguments
+  WeakHead,
+  /// Like weak head normal form, but also under the binders of an abstraction, so
+  /// the result is `λx1…xn.y M1…Mm` with arbitrary arguments
+  Head,
+  /// No redexes left anywhere
+  #[default]
+  Full,
+}
+
+impl NormalForm {
+  /// Whether `node` is already in this normal form, so that reducing it to the
+  /// form would take no steps
+  pub fn holds_for(self, node: &Node<'_>) -> bool {
+    let mut head = node;
+    match self {
+      NormalForm::Full => return node.redex_subterms().next().is_none(),
+      NormalForm::WeakHead if matches!(node, Node::Abstraction(..)) => return true,
+      NormalForm::Head => {
+        while let Node::Abstraction(abs) = head {
+          head = &abs.body;
+        }
+      }
+      NormalForm::WeakHead => (),
+    }
+    // what is left is a spine of applications, which must not have an abstraction
+    // at its head
+    let mut applied = false;
+    while let Node::Application(app) = head {
+      head = &app.lhs;
+      applied = true;
+    }
+    !(applied && matches!(head, Node::Abstraction(..)))
+  }
+}
+
+/// Whether a term has no redexes left anywhere, which is what every full
+/// normalization should return
+///
+/// This takes time in the size of the term and no reduction, so it is a cheap
+/// check of a result from any evaluator
+pub fn is_normal_form(node: &Node<'_>) -> bool {
+  NormalForm::Full.holds_for(node)
+}
+
+/// Reduce a term in normal order until it reaches the normal form `target`
+///
+/// Normal order finds each kind of normal form whenever the term has one, but
+/// never returns for terms without
+pub fn normalize<'inp>(node: Rc<Node<'inp>>, target: NormalForm) -> Rc<Node<'inp>> {
+  Evaluator::default()
+    .normalize(node, target)
+    .expect("evaluation without limits cannot fail")
+}
+
+/// Evaluate each of `terms` with `evaluator`, evaluating only the first of any
+/// alpha-equivalent terms and keeping a single copy of each subterm of the results
+///
+/// This is for many similar terms at once, such as the answers to an exercise.
+/// Terms are reference counted without atomics, so the batch runs on the calling
+/// thread; run a batch on each thread to spread the work. For terms using shared
+/// definitions, an [`Engine`](crate::Engine) also keeps its cache between calls
+pub fn normalize_many<'inp>(
+  terms: impl IntoIterator<Item = Rc<Node<'inp>>>,
+  evaluator: &Evaluator<'_>,
+) -> Vec<Result<Rc<Node<'inp>>, EvalError>> {
+  let mut store = HashCons::new();
+  let mut results: HashMap<Alpha<'inp>, Result<Rc<Node<'inp>>, EvalError>> = HashMap::new();
+  terms
+    .into_iter()
+    .map(|term| {
+      let key = Alpha(Rc::clone(&term));
+      if let Some(result) = results.get(&key) {
+        return result.clone();
+      }
+      let result = evaluator
+        .eval(term)
+        .map(|normal| store.intern(&normal) as Rc<Node<'inp>>);
+      // another run may get further than a cancelled one
+      if result != Err(EvalError::Cancelled) {
+        results.insert(key, result.clone());
+      }
+      result
+    })
+    .collect()
+}
+
+/// An evaluation configured with a strategy and optional limits
+///
+/// ```
+/// # use std::rc::Rc;
+/// # use camel::eval::{EvalStrategy, Evaluator};
+/// # use camel::parser::Parser;
+/// let term = Rc::new(Parser::new("(λx.λy.y) ((λx.x x) (λx.x x))").parse()?);
+/// let normal = Evaluator::new(EvalStrategy::NormalOrder)
+///   .with_fuel(100)
+///   .eval(term)?;
+/// assert_eq!(normal.to_string(), "(λy. y)");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Evaluator<'c> {
+  strategy: EvalStrategy,
+  cancel: Option<&'c CancelToken>,
+  max_steps: Option<usize>,
+  detect_loops: bool,
+  strong: bool,
+  hooks: Option<&'c Hooks<'c>>,
+  stats: Cell<EvalStats>,
+}
+
+/// Figures about the most recent evaluation run by an `Evaluator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EvalStats {
+  /// Beta reductions contracted
+  pub beta_reductions: usize,
+  /// Arguments substituted into the body of an abstraction, which the environment
+  /// machines never do while evaluating
+  pub substitutions: usize,
+  /// Size in nodes of the largest term seen, out of the input, the result, and
+  /// each intermediate term when reducing one step at a time for loop detection
+  pub max_size: usize,
+  /// Wall-clock time spent evaluating, not counting measuring sizes
+  pub elapsed: Duration,
+}
+
+impl<'c> Evaluator<'c> {
+  pub fn new(strategy: EvalStrategy) -> Self {
+    Evaluator {
+      strategy,
+      ..Evaluator::default()
+    }
+  }
+
+  /// Give up with `EvalError::Cancelled` once `cancel` is triggered
+  pub fn with_cancel(mut self, cancel: &'c CancelToken) -> Self {
+    self.cancel = Some(cancel);
+    self
+  }
+
+  /// Give up with `EvalError::StepLimitExceeded` after `max_steps` beta reductions
+  pub fn with_fuel(mut self, max_steps: usize) -> Self {
+    self.max_steps = Some(max_steps);
+    self
+  }
+
+  /// Fire the step and normal form hooks in `hooks` as evaluation goes along
+  pub fn with_hooks(mut self, hooks: &'c Hooks<'c>) -> Self {
+    self.hooks = Some(hooks);
+    self
+  }
+
+  /// Reduce one redex at a time, in the order the strategy contracts them and as
+  /// far as it goes, giving up with `EvalError::LoopDetected` once a term recurs,
+  /// or with `EvalError::UnboundedGrowth` once the term seems to grow without end
+  ///
+  /// The machine strategies are stepped like the substitution strategies they
+  /// implement: `Krivine` like `CallByName`, and `Cek` and `Secd` like
+  /// `CallByValue`
+  ///
+  /// Every term along the way is remembered, so this is much slower than plain
+  /// evaluation and best kept for terms that are suspected to diverge
+  pub fn with_loop_detection(mut self) -> Self {
+    self.detect_loops = true;
+    self
+  }
+
+  /// Carry on past weak head normal form with a strategy that stops there, reading
+  /// its result back and evaluating again under each binder and inside each
+  /// argument, until no redex is left
+  ///
+  /// This makes the results of the weak strategies, the machines among them,
+  /// comparable with those of the strategies which normalize fully. Loop detection
+  /// steps the weak strategy as it is, and stops at weak head normal form still
+  pub fn with_strong_reduction(mut self) -> Self {
+    self.strong = true;
+    self
+  }
+
+  /// Evaluate a term, with the step count starting from zero on every call
+  pub fn eval<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError> {
+    let result = self.measure(node, |node| {
+      if self.detect_loops {
+        return self.stepwise(node);
+      }
+      if self.strong && !self.strategy.normalizes_fully() {
+        return self.strongly(self.strategy, node);
+      }
+      self.reduce(self.strategy, node)
+    });
+    if self.strategy.normalizes_fully() || (self.strong && !self.detect_loops) {
+      debug_assert_normal(&result, NormalForm::Full);
+    }
+    result
+  }
+
+  /// Evaluate a term by a strategy from a `Registry`, ignoring the evaluator's own
+  /// strategy but keeping the limits
+  pub fn eval_with<'inp>(
+    &self,
+    strategy: &dyn Strategy,
+    node: Rc<Node<'inp>>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    self.measure(node, |node| strategy.eval(node, self))
+  }
+
+  /// Count a beta reduction about to be taken by a strategy defined outside of
+  /// camel, failing instead once the evaluation is cancelled or out of fuel
+  pub fn count_beta(&self) -> Result<(), EvalError> {
+    self.safepoint()
+  }
+
+  /// Reduce a term in normal order until it reaches the normal form `target`,
+  /// ignoring the strategy but keeping the limits
+  pub fn normalize<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    target: NormalForm,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    let result = self.measure(node, |node| match target {
+      NormalForm::WeakHead => self.by_name(node),
+      NormalForm::Head => self.head(node),
+      NormalForm::Full => self.normal(node),
+    });
+    debug_assert_normal(&result, target);
+    result
+  }
+
+  /// Normalize a term with respect to both beta and eta reduction like
+  /// `normalize_beta_eta`, ignoring the strategy but keeping the limits
+  pub fn normalize_beta_eta<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    let normal = self.normalize(node, NormalForm::Full)?;
+    Ok(eta_reduce(&normal))
+  }
+
+  /// Contract a single redex in the order of `step`, failing instead if the
+  /// evaluation is cancelled or out of fuel
+  ///
+  /// The step counts towards the fuel of the evaluations after it, until one of
+  /// them starts again from zero
+  pub fn step<'inp>(&self, node: &Rc<Node<'inp>>) -> Result<Option<Rc<Node<'inp>>>, EvalError> {
+    let Some(next) = step(node) else {
+      return Ok(None);
+    };
+    self.safepoint()?;
+    Ok(Some(next))
+  }
+
+  /// Record every step taken while reducing a term to normal form in the order of
+  /// `step`, like `trace`, ignoring the strategy but keeping the limits
+  pub fn trace<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Trace<'inp>, EvalError> {
+    let mut steps = Vec::new();
+    self.measure(Rc::clone(&node), |mut current| {
+      while let Some((_, redex, term)) = contract(&current) {
+        self.safepoint()?;
+        current = Rc::clone(&term);
+        steps.push(TraceStep { redex, term });
+      }
+      Ok(current)
+    })?;
+    Ok(Trace { start: node, steps })
+  }
+
+  /// Reduce a term to normal form in the order of `step`, calling `observer` before
+  /// and after every contraction like `eval_with_observer`, ignoring the strategy
+  /// but keeping the limits
+  pub fn eval_with_observer<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    mut observer: impl FnMut(&StepInfo<'_, 'inp>),
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    self.measure(node, |mut current| {
+      let mut step = 0;
+      while let Some((path, redex, next)) = contract(&current) {
+        self.safepoint()?;
+        step += 1;
+        for (phase, term) in [(Phase::Before, &current), (Phase::After, &next)] {
+          observer(&StepInfo {
+            phase,
+            step,
+            path: &path,
+            redex: &redex,
+            term,
+          });
+        }
+        current = next;
+      }
+      Ok(current)
+    })
+  }
+
+  /// Figures about the most recent call to `eval` or `normalize`, including one
+  /// that gave up with an error
+  pub fn stats(&self) -> EvalStats {
+    self.stats.get()
+  }
+
+  /// Run an evaluation with the stats starting from zero, timing it and measuring
+  /// the input and the result
+  fn measure<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    run: impl FnOnce(Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    #[cfg(feature = "profile")]
+    let _pass = crate::profile::enter(crate::profile::Pass::Eval);
+    self.stats.set(EvalStats {
+      max_size: node.size(),
+      ..EvalStats::default()
+    });
+    let start = Instant::now();
+    let result = run(node);
+    let elapsed = start.elapsed();
+    let size = result.as_ref().map_or(0, |normal| normal.size());
+    self.record(|stats| {
+      stats.max_size = stats.max_size.max(size);
+      stats.elapsed = elapsed;
+    });
+    if let (Some(hooks), Ok(normal)) = (self.hooks, &result) {
+      hooks.normal_form(normal, self.stats.get().beta_reductions);
+    }
+    result
+  }
+
+  fn record(&self, update: impl FnOnce(&mut EvalStats)) {
+    let mut stats = self.stats.get();
+    update(&mut stats);
+    self.stats.set(stats);
+  }
+
+  /// Substitute an argument into the body of an abstraction, counting it
+  fn substitute<'inp>(&self, abs: &Abstraction<'inp>, arg: &Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    self.record(|stats| stats.substitutions += 1);
+    substitute(&abs.body, &abs.param, arg)
+  }
+
+  /// Reduce by `strategy`, which need not be the evaluator's own
+  pub(crate) fn reduce<'inp>(
+    &self,
+    strategy: EvalStrategy,
+    node: Rc<Node<'inp>>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    match strategy {
+      EvalStrategy::NormalOrder => self.normal(node),
+      EvalStrategy::ApplicativeOrder => self.strict(node, true),
+      EvalStrategy::CallByName => self.by_name(node),
+      EvalStrategy::CallByValue => self.strict(node, false),
+      #[cfg(feature = "machines")]
+      EvalStrategy::Krivine => krivine::whnf(node, || self.safepoint()),
+      #[cfg(feature = "machines")]
+      EvalStrategy::Cek => {
+        env::run(&node, &env::Env::new(), || self.safepoint()).map(|value| value.readback())
+      }
+      #[cfg(feature = "machines")]
+      EvalStrategy::Secd => secd::compile(&node)
+        .run_with(|| self.safepoint())
+        .map(|value| value.readback()),
+    }
+  }
+
+  /// Reduce to weak head normal form by `strategy`, then do the same under each
+  /// binder and inside each argument of the result, rebuilding the term around what
+  /// each gives
+  fn strongly<'inp>(
+    &self,
+    strategy: EvalStrategy,
+    node: Rc<Node<'inp>>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    enum Visit<'inp> {
+      /// A term to reduce to weak head normal form first
+      Reduce(Rc<Node<'inp>>),
+      /// A term already in weak head normal form, whose parts are still to be done
+      Descend(Rc<Node<'inp>>),
+      /// Rebuild a node from the results of its parts
+      Exit(Rc<Node<'inp>>),
+    }
+
+    let mut built: Vec<Rc<Node<'inp>>> = Vec::new();
+    let mut stack = vec![Visit::Reduce(node)];
+    while let Some(visit) = stack.pop() {
+      match visit {
+        Visit::Reduce(term) => stack.push(Visit::Descend(self.reduce(strategy, term)?)),
+        Visit::Descend(term) => match &*term {
+          Node::Abstraction(abs) => {
+            let body = Rc::clone(&abs.body);
+            stack.push(Visit::Exit(term));
+            stack.push(Visit::Reduce(body));
+          }
+          // with the head not an abstraction, the function part is already in weak
+          // head normal form and only the argument needs reducing
+          Node::Application(app) => {
+            let (lhs, rhs) = (Rc::clone(&app.lhs), Rc::clone(&app.rhs));
+            stack.push(Visit::Exit(term));
+            stack.push(Visit::Reduce(rhs));
+            stack.push(Visit::Descend(lhs));
+          }
+          Node::Identifier(..) => built.push(term),
+        },
+        Visit::Exit(term) => {
+          let rebuilt = match &*term {
+            Node::Abstraction(abs) => {
+              let body = built.pop().expect("the body is done first");
+              if Rc::ptr_eq(&body, &abs.body) {
+                term
+              } else {
+                Rc::new(Node::Abstraction(Abstraction {
+                  param: abs.param.clone(),
+                  body,
+                }))
+              }
+            }
+            Node::Application(app) => {
+              let rhs = built.pop().expect("the argument is done last");
+              let lhs = built.pop().expect("the function is done first");
+              if Rc::ptr_eq(&lhs, &app.lhs) && Rc::ptr_eq(&rhs, &app.rhs) {
+                term
+              } else {
+                Rc::new(Node::Application(Application { lhs, rhs }))
+              }
+            }
+            Node::Identifier(..) => unreachable!("identifiers are done on entry"),
+          };
+          built.push(rebuilt);
+        }
+      }
+    }
+    Ok(built.pop().expect("the whole term is done last"))
+  }
+
+  /// Reduce with arguments evaluated before they are substituted, normalizing under
+  /// binders for applicative order, or stopping at values for call by value
+  ///
+  /// Applications are evaluated by walking their left spine: `f a1 a2 … an` is
+  /// unwound into its head and a stack of evaluated arguments, which are then
+  /// consumed one at a time while the head is an abstraction. All pending work
+  /// lives on an explicit stack, so nesting depth is bounded by the heap rather
+  /// than the Rust stack
+  fn strict<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    under_binders: bool,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    let mut stack = Vec::new();
+    let mut control = Control::Eval(node);
+    loop {
+      control = match control {
+        Control::Eval(node) => match &*node {
+          Node::Abstraction(abs) if under_binders => {
+            stack.push(StrictFrame::Abstract(abs.param.clone()));
+            Control::Eval(Rc::clone(&abs.body))
+          }
+          Node::Application(..) => unwind_strict(node, Vec::new(), &mut stack),
+          _ => Control::Return(node),
+        },
+        Control::Return(value) => match stack.pop() {
+          None => return Ok(value),
+          Some(StrictFrame::Abstract(param)) => {
+            Control::Return(Rc::new(Node::Abstraction(Abstraction {
+              param,
+              body: value,
+            })))
+          }
+          Some(StrictFrame::Argument { lhs, mut args }) => {
+            args.push(value);
+            unwind_strict(lhs, args, &mut stack)
+          }
+          Some(StrictFrame::Head { mut args }) => match &*value {
+            Node::Abstraction(abs) => match args.pop() {
+              Some(arg) => {
+                self.safepoint()?;
+                unwind_strict(self.substitute(abs, &arg), args, &mut stack)
+              }
+              None => Control::Return(value),
+            },
+            _ => Control::Return(rebuild_spine(value, args)),
+          },
+        },
+      };
+    }
+  }
+
+  /// Reduce to weak head normal form, then normalize under the binder or the
+  /// arguments of the stuck head, keeping pending work on an explicit stack
+  fn normal<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError> {
+    let mut stack = Vec::new();
+    let mut control = Control::Eval(node);
+    loop {
+      control = match control {
+        Control::Eval(node) => {
+          let whnf = self.by_name(node)?;
+          match &*whnf {
+            Node::Abstraction(abs) => {
+              stack.push(NormalFrame::Abstract(abs.param.clone()));
+              Control::Eval(Rc::clone(&abs.body))
+            }
+            Node::Application(..) => {
+              let (head, mut pending) = unwind_spine(whnf);
+              let first = pending.pop().expect("an application has an argument");
+              stack.push(NormalFrame::Arguments {
+                head,
+                pending,
+                done: Vec::new(),
+              });
+              Control::Eval(first)
+            }
+            Node::Identifier(..) => Control::Return(whnf),
+          }
+        }
+        Control::Return(value) => match stack.pop() {
+          None => return Ok(value),
+          Some(NormalFrame::Abstract(param)) => {
+            Control::Return(Rc::new(Node::Abstraction(Abstraction {
+              param,
+              body: value,
+            })))
+          }
+          Some(NormalFrame::Arguments {
+            head,
+            mut pending,
+            mut done,
+          }) => {
+            done.push(value);
+            match pending.pop() {
+              Some(next) => {
+                stack.push(NormalFrame::Arguments {
+                  head,
+                  pending,
+                  done,
+                });
+                Control::Eval(next)
+              }
+              None => Control::Return(done.into_iter().fold(head, |lhs, rhs| {
+                Rc::new(Node::Application(Application { lhs, rhs }))
+              })),
+            }
+          }
+        },
+      };
+    }
+  }
+
+  /// Reduce to weak head normal form, entering the body of each abstraction that
+  /// results to do the same there
+  fn head<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError> {
+    let mut binders = Vec::new();
+    let mut current = self.by_name(node)?;
+    while let Node::Abstraction(abs) = &*current {
+      binders.push(abs.param.clone());
+      current = self.by_name(Rc::clone(&abs.body))?;
+    }
+    Ok(binders.into_iter().rev().fold(current, |body, param| {
+      Rc::new(Node::Abstraction(Abstraction { param, body }))
+    }))
+  }
+
+  /// Reduce to weak head normal form along the left spine, substituting arguments
+  /// as they are and never entering binders
+  fn by_name<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError> {
+    let (mut head, mut args) = unwind_spine(node);
+    while let Node::Abstraction(abs) = &*head {
+      let Some(arg) = args.pop() else {
+        break;
+      };
+      self.safepoint()?;
+      let (next, next_args) = unwind_spine(self.substitute(abs, &arg));
+      head = next;
+      args.extend(next_args);
+    }
+    Ok(rebuild_spine(head, args))
+  }
+
+  /// Reduce a redex at a time by the strategy, watching the sequence of terms for
+  /// cycles and growth
+  fn stepwise<'inp>(&self, node: Rc<Node<'inp>>) -> Result<Rc<Node<'inp>>, EvalError> {
+    let initial_size = node.size();
+    let mut seen = HashMap::new();
+    let mut history = Vec::new();
+    let mut size = initial_size;
+    let mut growing = 0;
+    let mut current = node;
+    loop {
+      if let Some(&start) = seen.get(&Alpha(Rc::clone(&current))) {
+        let cycle: &[Rc<Node>] = &history[start..];
+        return Err(EvalError::LoopDetected {
+          cycle: cycle.iter().map(ToString::to_string).collect(),
+        });
+      }
+      let Some(next) = step_by(self.strategy, &current) else {
+        return Ok(current);
+      };
+      self.safepoint()?;
+      let next_size = next.size();
+      self.record(|stats| {
+        stats.substitutions += 1;
+        stats.max_size = stats.max_size.max(next_size);
+      });
+      growing = if next_size > size { growing + 1 } else { 0 };
+      size = next_size;
+      if growing >= GROWTH_STEPS && size >= GROWTH_FACTOR * initial_size {
+        return Err(EvalError::UnboundedGrowth {
+          steps: self.stats.get().beta_reductions,
+          size,
+        });
+      }
+      seen.insert(Alpha(Rc::clone(&current)), history.len());
+      history.push(current);
+      current = next;
+    }
+  }
+
+  /// Checked before every beta reduction
+  fn safepoint(&self) -> Result<(), EvalError> {
+    if self.cancel.is_some_and(CancelToken::is_cancelled) {
+      return Err(EvalError::Cancelled);
+    }
+    let steps = self.stats.get().beta_reductions + 1;
+    if self.max_steps.is_some_and(|max_steps| steps > max_steps) {
+      return Err(EvalError::StepLimitExceeded);
+    }
+    self.record(|stats| stats.beta_reductions = steps);
+    if let Some(hooks) = self.hooks {
+      hooks.stepped(steps, None);
+    }
+    Ok(())
+  }
+}
+
+/// Consecutive steps a term must grow for, and how many times its starting size it
+/// must reach, before loop detection decides it grows without end
+const GROWTH_STEPS: usize = 64;
+const GROWTH_FACTOR: usize = 8;
+
+/// What a machine does next: take a term apart, or hand a finished result to the
+/// frame on top of its stack
+enum Control<'inp> {
+  Eval(Rc<Node<'inp>>),
+  Return(Rc<Node<'inp>>),
+}
+
+/// Work waiting on a result in the strict evaluator
+enum StrictFrame<'inp> {
+  /// The result is the body of an abstraction over this parameter
+  Abstract(Cow<'inp, str>),
+  /// The result is the next argument of a spine which continues with `lhs`
+  Argument {
+    lhs: Rc<Node<'inp>>,
+    args: Vec<Rc<Node<'inp>>>,
+  },
+  /// The result is the head of a spine whose arguments are all evaluated
+  Head { args: Vec<Rc<Node<'inp>>> },
+}
+
+/// Work waiting on a result in the normal order evaluator
+enum NormalFrame<'inp> {
+  /// The result is the body of an abstraction over this parameter
+  Abstract(Cow<'inp, str>),
+  /// The result is the next argument of a stuck `head`, with the arguments still to
+  /// normalize on a stack whose top comes first
+  Arguments {
+    head: Rc<Node<'inp>>,
+    pending: Vec<Rc<Node<'inp>>>,
+    done: Vec<Rc<Node<'inp>>>,
+  },
+}
+
+/// Continue walking down the spine of `head`, evaluating the next argument, or the
+/// head itself once the spine is exhausted
+fn unwind_strict<'inp>(
+  head: Rc<Node<'inp>>,
+  args: Vec<Rc<Node<'inp>>>,
+  stack: &mut Vec<StrictFrame<'inp>>,
+) -> Control<'inp> {
+  match &*head {
+    Node::Application(app) => {
+      stack.push(StrictFrame::Argument {
+        lhs: Rc::clone(&app.lhs),
+        args,
+      });
+      Control::Eval(Rc::clone(&app.rhs))
+    }
+    _ => {
+      stack.push(StrictFrame::Head { args });
+      Control::Eval(head)
+    }
+  }
+}
+
+/// Split a term into the head of its left spine and its arguments, on a stack whose
+/// top is the first argument
+fn unwind_spine<'inp>(mut head: Rc<Node<'inp>>) -> (Rc<Node<'inp>>, Vec<Rc<Node<'inp>>>) {
+  let mut args = Vec::new();
+  while let Node::Application(app) = &*head {
+    args.push(Rc::clone(&app.rhs));
+    head = Rc::clone(&app.lhs);
+  }
+  (head, args)
+}
+
+/// Apply `head` to the arguments left on a spine stack, whose top is the first argument
+fn rebuild_spine<'inp>(head: Rc<Node<'inp>>, args: Vec<Rc<Node<'inp>>>) -> Rc<Node<'inp>> {
+  args.into_iter().rev().fold(head, |lhs, rhs| {
+    Rc::new(Node::Application(Application { lhs, rhs }))
+  })
+}
+
+/// Check in debug builds that an evaluation which succeeded reached `target`,
+/// catching an evaluator which stops early
+fn debug_assert_normal(result: &Result<Rc<Node<'_>>, EvalError>, target: NormalForm) {
+  if let Ok(node) = result {
+    debug_assert!(
+      target.holds_for(node),
+      "evaluation stopped before {:?} normal form at {}",
+      target,
+      node
+    );
+  }
+}
+
+/// Contract a single redex, or return `None` if the term is already in normal form
+///
+/// Redexes are chosen in the order `eval` contracts them: the left side of an
+/// application is reduced first, then the right, and only then the application itself
+pub fn step<'inp>(node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>> {
+  contract(node).map(|(_, _, term)| term)
+}
+
+/// Like `step`, but also return the position of the redex and the redex itself
+fn contract<'inp>(node: &Rc<Node<'inp>>) -> Option<(Path, Rc<Node<'inp>>, Rc<Node<'inp>>)> {
+  rewrite_first(node, Order::PostOrder, true, contract_root)
+}
+
+/// Contract the redex that `strategy` would contract next, or return `None` if the
+/// term is already as reduced as the strategy takes it
+fn step_by<'inp>(strategy: EvalStrategy, node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>> {
+  let (order, under_binders) = match strategy {
+    EvalStrategy::NormalOrder => (Order::PreOrder, true),
+    EvalStrategy::ApplicativeOrder => (Order::PostOrder, true),
+    EvalStrategy::CallByValue => (Order::PostOrder, false),
+    #[cfg(feature = "machines")]
+    EvalStrategy::Cek | EvalStrategy::Secd => (Order::PostOrder, false),
+    EvalStrategy::CallByName => return contract_head(node),
+    #[cfg(feature = "machines")]
+    EvalStrategy::Krivine => return contract_head(node),
+  };
+  rewrite_first(node, order, under_binders, contract_root).map(|(_, _, term)| term)
+}
+
+/// Contract the redex at the head of the left spine, if there is one
+fn contract_head<'inp>(node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>> {
+  let (head, mut args) = unwind_spine(Rc::clone(node));
+  let Node::Abstraction(abs) = &*head else {
+    return None;
+  };
+  let arg = args.pop()?;
+  Some(rebuild_spine(substitute(&abs.body, &abs.param, &arg), args))
+}
+
+/// Contract the term itself if it is a redex
+fn contract_root<'inp>(node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>> {
+  match &**node {
+    Node::Application(app) => match &*app.lhs {
+      Node::Abstraction(abs) => Some(substitute(&abs.body, &abs.param, &app.rhs)),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// When a traversal visits a node relative to its subterms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+  /// Before its subterms, so the outermost match wins
+  PreOrder,
+  /// After its subterms, so the innermost match wins
+  PostOrder,
+}
+
+/// Rewrite the first subterm, visiting left before right, which `rewrite` accepts,
+/// returning its position, that subterm, and the whole term with it replaced
+///
+/// Bodies of abstractions are only searched if `under_binders` is set
+///
+/// The search keeps an explicit stack of ancestors, each paired with the number of
+/// children already visited, which is then used to rebuild the path back up to the
+/// root around the rewritten subterm
+fn rewrite_first<'inp>(
+  node: &Rc<Node<'inp>>,
+  order: Order,
+  under_binders: bool,
+  mut rewrite: impl FnMut(&Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>>,
+) -> Option<(Path, Rc<Node<'inp>>, Rc<Node<'inp>>)> {
+  let mut stack = vec![(node, 0)];
+  let (found, term) = loop {
+    let &(current, visited) = stack.last()?;
+    let children = match &**current {
+      Node::Abstraction(..) => usize::from(under_binders),
+      Node::Application(..) => 2,
+      Node::Identifier(..) => 0,
+    };
+    let due = match order {
+      Order::PreOrder => visited == 0,
+      Order::PostOrder => visited == children,
+    };
+    if due {
+      if let Some(result) = rewrite(current) {
+        break (Rc::clone(current), result);
+      }
+    }
+    if visited == children {
+      stack.pop();
+      continue;
+    }
+    let child = match (&**current, visited) {
+      (Node::Abstraction(abs), _) => &abs.body,
+      (Node::Application(app), 0) => &app.lhs,
+      (Node::Application(app), _) => &app.rhs,
+      (Node::Identifier(..), _) => unreachable!("identifiers have no children"),
+    };
+    stack.last_mut().expect("just inspected").1 += 1;
+    stack.push((child, 0));
+  };
+  stack.pop();
+  let ancestors: Vec<_> = stack
+    .into_iter()
+    .map(|(parent, visited)| match (&**parent, visited) {
+      (Node::Abstraction(..), _) => (parent, Direction::Body),
+      (_, 1) => (parent, Direction::Lhs),
+      _ => (parent, Direction::Rhs),
+    })
+    .collect();
+  let path = Path(ancestors.iter().map(|&(_, direction)| direction).collect());
+  let term = replace_along(ancestors, term);
+  Some((path, found, term))
+}
+
+/// Rebuild a term around a replaced subterm, given the ancestors of the subterm from
+/// the root down, each with the direction taken from it
+pub(crate) fn replace_along<'inp>(
+  ancestors: Vec<(&Rc<Node<'inp>>, Direction)>,
+  mut term: Rc<Node<'inp>>,
+) -> Rc<Node<'inp>> {
+  for (parent, direction) in ancestors.into_iter().rev() {
+    term = Rc::new(parent.with_child(direction, term));
+  }
+  term
+}
+
+/// Contract the redex at `path`, such as one of those from `Node::redexes`, or
+/// return `None` if there is no redex there
+pub fn reduce_at<'inp>(node: &Rc<Node<'inp>>, path: &Path) -> Option<Rc<Node<'inp>>> {
+  node.replace_at(path, contract_root(node.at(path)?)?)
+}
+
+/// A complete reduction sequence, from a starting term to its normal form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<'inp> {
+  pub start: Rc<Node<'inp>>,
+  pub steps: Vec<TraceStep<'inp>>,
+}
+
+/// A single beta reduction within a trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep<'inp> {
+  /// The redex that was contracted
+  pub redex: Rc<Node<'inp>>,
+  /// The whole term after the contraction
+  pub term: Rc<Node<'inp>>,
+}
+
+impl<'inp> Trace<'inp> {
+  /// Every term in the sequence, starting term included
+  pub fn terms(&self) -> impl Iterator<Item = &Rc<Node<'inp>>> {
+    std::iter::once(&self.start).chain(self.steps.iter().map(|step| &step.term))
+  }
+
+  /// The last term in the sequence, which is the normal form
+  pub fn normal_form(&self) -> &Rc<Node<'inp>> {
+    self.steps.last().map_or(&self.start, |step| &step.term)
+  }
+}
+
+/// Record every step taken while reducing a term to normal form, in the order of
+/// `step`
+///
+/// Like `eval`, this never returns for terms without a normal form
+pub fn trace<'inp>(node: Rc<Node<'inp>>) -> Trace<'inp> {
+  Evaluator::default()
+    .trace(node)
+    .expect("evaluation without limits cannot fail")
+}
+
+/// Whether an observer is called before or after a redex is contracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+  Before,
+  After,
+}
+
+/// A reduction step as seen by the observer of `eval_with_observer`
+#[derive(Debug, Clone, Copy)]
+pub struct StepInfo<'s, 'inp> {
+  pub phase: Phase,
+  /// The number of the beta reduction, counting from one
+  pub step: usize,
+  /// Where the redex sits in the term before the contraction
+  pub path: &'s Path,
+  pub redex: &'s Rc<Node<'inp>>,
+  /// The whole term, from before the contraction or after it depending on `phase`
+  pub term: &'s Rc<Node<'inp>>,
+}
+
+/// Reduce a term to normal form in the order of `step`, calling `observer` before
+/// and after every contraction
+///
+/// Like `eval`, this never returns for terms without a normal form
+pub fn eval_with_observer<'inp>(
+  node: Rc<Node<'inp>>,
+  observer: impl FnMut(&StepInfo<'_, 'inp>),
+) -> Rc<Node<'inp>> {
+  Evaluator::default()
+    .eval_with_observer(node, observer)
+    .expect("evaluation without limits cannot fail")
+}
+
+/// Iterator over a reduction sequence, yielding the term after each beta reduction
+///
+/// Redexes are contracted in the order of `step`, and iteration ends once the term
+/// is in normal form, which may be never for terms without one, or once the
+/// reducer is cancelled
+pub struct Reducer<'inp> {
+  current: Rc<Node<'inp>>,
+  cancel: Option<CancelToken>,
+}
+
+impl<'inp> Reducer<'inp> {
+  pub fn new(node: Rc<Node<'inp>>) -> Self {
+    Reducer {
+      current: node,
+      cancel: None,
+    }
+  }
+
+  /// End iteration before the next beta reduction once `cancel` is triggered,
+  /// which `is_cancelled` then tells apart from reaching a normal form
+  pub fn with_cancel(mut self, cancel: &CancelToken) -> Self {
+    self.cancel = Some(cancel.clone());
+    self
+  }
+
+  /// Whether iteration ended because the reducer was cancelled
+  pub fn is_cancelled(&self) -> bool {
+    self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+  }
+
+  /// The most recently produced term, or the starting term before any steps
+  pub fn current(&self) -> &Rc<Node<'inp>> {
+    &self.current
+  }
+}
+
+impl<'inp> Iterator for Reducer<'inp> {
+  type Item = Rc<Node<'inp>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.is_cancelled() {
+      return None;
+    }
+    let next = step(&self.current)?;
+    self.current = Rc::clone(&next);
+    Some(next)
+  }
+}
+
+/// Normalize a term with respect to both beta and eta reduction, so that terms
+/// which are extensionally equal end up the same up to renaming
+///
+/// Like `eval`, this never returns for terms without a normal form
+pub fn normalize_beta_eta<'inp>(node: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+  Evaluator::default()
+    .normalize_beta_eta(node)
+    .expect("evaluation without limits cannot fail")
+}
+
+/// Beta reductions allowed for normalizing each side in `equiv` and `equiv_eta`
+pub const EQUIV_FUEL: usize = 100_000;
+
+/// Whether two terms have the same beta normal form up to the names of bound
+/// variables, giving up with `EvalError::StepLimitExceeded` if either side takes
+/// more than `EQUIV_FUEL` beta reductions to normalize
+pub fn equiv(a: &Node<'_>, b: &Node<'_>) -> Result<bool, EvalError> {
+  equiv_up_to(a, b, |node| node)
+}
+
+/// Like `equiv`, but also counting terms as equal when they are eta convertible, so
+/// that `λx.f x` is equivalent to `f`
+pub fn equiv_eta(a: &Node<'_>, b: &Node<'_>) -> Result<bool, EvalError> {
+  equiv_up_to(a, b, |node| eta_reduce(&node))
+}
+
+fn equiv_up_to(
+  a: &Node<'_>,
+  b: &Node<'_>,
+  finish: impl for<'inp> Fn(Rc<Node<'inp>>) -> Rc<Node<'inp>>,
+) -> Result<bool, EvalError> {
+  let evaluator = Evaluator::default().with_fuel(EQUIV_FUEL);
+  let a = finish(evaluator.normalize(Rc::new(a.clone()), NormalForm::Full)?);
+  let b = finish(evaluator.normalize(Rc::new(b.clone()), NormalForm::Full)?);
+  Ok(a.alpha_eq(&b))
+}
+
+/// The evaluations of one term under several strategies, from `compare_strategies`
+#[derive(Debug, Clone)]
+pub struct Comparison<'inp> {
+  pub runs: Vec<StrategyRun<'inp>>,
+}
+
+/// The evaluation of a term under a single strategy
+#[derive(Debug, Clone)]
+pub struct StrategyRun<'inp> {
+  pub strategy: EvalStrategy,
+  pub result: Result<Rc<Node<'inp>>, EvalError>,
+  /// Beta reductions taken, up to the point of giving up if the run failed
+  pub steps: usize,
+}
+
+impl Comparison<'_> {
+  /// Whether every strategy finished, and all of them with the same term up to the
+  /// names of bound variables
+  ///
+  /// Strategies that stop at weak head normal form or at values only agree with
+  /// the others on terms whose results have no redexes under a binder
+  pub fn agree(&self) -> bool {
+    let mut results = self.runs.iter().map(|run| run.result.as_ref());
+    let Some(Ok(first)) = results.next() else {
+      return false;
+    };
+    results.all(|result| result.is_ok_and(|term| term.alpha_eq(first)))
+  }
+}
+
+impl fmt::Display for Comparison<'_> {
+  /// A line for each strategy with its steps and result, then whether they agree
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for run in &self.runs {
+      let name = format!("{:?}", run.strategy);
+      match &run.result {
+        Ok(term) => writeln!(f, "{:<16} {:>6} steps  {}", name, run.steps, term)?,
+        Err(err) => writeln!(f, "{:<16} {:>6} steps  error: {}", name, run.steps, err)?,
+      }
+    }
+    if self.agree() {
+      return write!(f, "all strategies agree");
+    }
+    write!(f, "strategies disagree")
+  }
+}
+
+/// Evaluate a term under each of `strategies`, allowing each `max_steps` beta
+/// reductions, to see whether they reach the same result and how quickly
+pub fn compare_strategies<'inp>(
+  node: &Rc<Node<'inp>>,
+  strategies: &[EvalStrategy],
+  max_steps: usize,
+) -> Comparison<'inp> {
+  let runs = strategies
+    .iter()
+    .map(|&strategy| {
+      let evaluator = Evaluator::new(strategy).with_fuel(max_steps);
+      let result = evaluator.eval(Rc::clone(node));
+      StrategyRun {
+        strategy,
+        result,
+        steps: evaluator.stats().beta_reductions,
+      }
+    })
+    .collect();
+  Comparison { runs }
+}
+
+/// Contract every eta redex `λx.M x`, where `x` is not free in `M`, to `M`
+///
+/// Subterms are reduced before the abstractions around them, so chains such as
+/// `λx.λy.f x y` collapse all the way to `f`
+pub fn eta_reduce<'inp>(node: &Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+  let mut stack = Vec::new();
+  let mut control = Control::Eval(Rc::clone(node));
+  loop {
+    control = match control {
+      Control::Eval(node) => match &*node {
+        Node::Abstraction(abs) => {
+          stack.push(SubstFrame::Abstract(abs.param.clone()));
+          Control::Eval(Rc::clone(&abs.body))
+        }
+        Node::Application(app) => {
+          stack.push(SubstFrame::Rhs(Rc::clone(&app.rhs)));
+          Control::Eval(Rc::clone(&app.lhs))
+        }
+        Node::Identifier(..) => Control::Return(node),
+      },
+      Control::Return(result) => match stack.pop() {
+        None => return result,
+        Some(SubstFrame::Abstract(param)) => match &*result {
+          Node::Application(app)
+            if matches!(&*app.rhs, Node::Identifier(id) if id.name == param)
+              && !app.lhs.free_vars().contains(param.as_ref()) =>
+          {
+            Control::Return(Rc::clone(&app.lhs))
+          }
+          _ => Control::Return(Rc::new(Node::Abstraction(Abstraction {
+            param,
+            body: result,
+          }))),
+        },
+        Some(SubstFrame::Rhs(rhs)) => {
+          stack.push(SubstFrame::Lhs(result));
+          Control::Eval(rhs)
+        }
+        Some(SubstFrame::Lhs(lhs)) => {
+          Control::Return(Rc::new(Node::Application(Application { lhs, rhs: result })))
+        }
+      },
+    };
+  }
+}
+
+/// Replace free occurrences of `name` in `node` with `value`
+///
+/// Occurrences bound by an inner abstraction over the same name are left
+/// untouched, and binders that would capture a free variable of `value` are
+/// renamed to a fresh name first
+pub fn substitute<'inp>(
+  node: &Rc<Node<'inp>>,
+  name: &str,
+  value: &Rc<Node<'inp>>,
+) -> Rc<Node<'inp>> {
+  substitute_avoiding(node, name, value)
+}
+
+/// Work waiting on a result while rebuilding a term bottom up, as when substituting
+enum SubstFrame<'inp> {
+  /// The result is the body of an abstraction over this parameter
+  Abstract(Cow<'inp, str>),
+  /// The result is the left side of an application, whose right side is next
+  Rhs(Rc<Node<'inp>>),
+  /// The result is the right side of an application with this left side
+  Lhs(Rc<Node<'inp>>),
+}
+
+fn substitute_avoiding<'inp>(
+  node: &Rc<Node<'inp>>,
+  name: &str,
+  value: &Rc<Node<'inp>>,
+) -> Rc<Node<'inp>> {
+  // only needed once a binder is met, so substituting into a lone variable stays
+  // constant time however large the value is
+  let value_free = OnceCell::new();
+  let value_free = || value_free.get_or_init(|| value.free_vars());
+  let mut stack = Vec::new();
+  let mut control = Control::Eval(Rc::clone(node));
+  loop {
+    control = match control {
+      Control::Eval(node) => match &*node {
+        Node::Identifier(id) if id.name == name => Control::Return(Rc::clone(value)),
+        Node::Identifier(..) => Control::Return(node),
+        Node::Abstraction(abs) if abs.param == name => Control::Return(node),
+        Node::Abstraction(abs) if value_free().contains(abs.param.as_ref()) => {
+          let body_free = abs.body.free_vars();
+          if !body_free.contains(name) {
+            Control::Return(node)
+          } else {
+            // the binder would capture a free variable of the value, so rename it first
+            let fresh = fresh_name(&abs.param, |candidate| {
+              value_free().contains(candidate) || body_free.contains(candidate)
+            });
+            let renamed = substitute(
+              &abs.body,
+              &abs.param,
+              &Rc::new(Node::Identifier(Identifier {
+                name: Cow::Owned(fresh.clone()),
+              })),
+            );
+            stack.push(SubstFrame::Abstract(Cow::Owned(fresh)));
+            Control::Eval(renamed)
+          }
+        }
+        Node::Abstraction(abs) => {
+          stack.push(SubstFrame::Abstract(abs.param.clone()));
+          Control::Eval(Rc::clone(&abs.body))
+        }
+        Node::Application(app) => {
+          stack.push(SubstFrame::Rhs(Rc::clone(&app.rhs)));
+          Control::Eval(Rc::clone(&app.lhs))
+        }
+      },
+      Control::Return(result) => match stack.pop() {
+        None => return result,
+        Some(SubstFrame::Abstract(param)) => {
+          Control::Return(Rc::new(Node::Abstraction(Abstraction {
+            param,
+            body: result,
+          })))
+        }
+        Some(SubstFrame::Rhs(rhs)) => {
+          stack.push(SubstFrame::Lhs(result));
+          Control::Eval(rhs)
+        }
+        Some(SubstFrame::Lhs(lhs)) => {
+          Control::Return(Rc::new(Node::Application(Application { lhs, rhs: result })))
+        }
+      },
+    };
+  }
+}
+
+/// Substitute the terms of `bindings` into `node` all at once
+///
+/// Every bound name is first renamed apart from anything the terms could mention,
+/// so that substituting one binding can never touch the term of another, and the
+/// result does not depend on the order of `bindings`
+pub fn substitute_all<'inp>(
+  node: &Rc<Node<'inp>>,
+  bindings: &[(&str, Rc<Node<'inp>>)],
+) -> Rc<Node<'inp>> {
+  let mut term = Rc::clone(node);
+  let mut taken: HashSet<String> = bindings
+    .iter()
+    .flat_map(|(_, value)| value.free_vars())
+    .chain(term.free_vars())
+    .map(str::to_string)
+    .collect();
+  let mut renamed = Vec::with_capacity(bindings.len());
+  for (name, value) in bindings {
+    let fresh = fresh_name(name, |candidate| taken.contains(candidate));
+    taken.insert(fresh.clone());
+    let placeholder = Rc::new(Node::Identifier(Identifier {
+      name: Cow::Owned(fresh.clone()),
+    }));
+    term = substitute(&term, name, &placeholder);
+    renamed.push((fresh, value));
+  }
+  for (fresh, value) in renamed {
+    term = substitute(&term, &fresh, value);
+  }
+  term
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", "x")]
+  #[case("λx.x", "(λx. x)")]
+  #[case("(λx.x) y", "y")]
+  #[case("(λx.x x) y", "y y")]
+  #[case("(λx.λy.x) a b", "a")]
+  #[case("(λx.λy.y) a b", "b")]
+  #[case("(λx.(λx.x)) y", "(λx. x)")]
+  #[case("λf.(λx.x) f", "(λf. f)")]
+  #[case("x ((λy.y) z)", "x z")]
+  #[case("x ((λy.y z) w)", "x (w z)")]
+  #[case("(λf.λx.f (f x)) (λy.y)", "(λx. x)")]
+  #[case("f a b c", "f a b c")]
+  #[case("(λx.x) (λx.x) (λx.x) y", "y")]
+  #[case("(λx.λy.x y) f a b", "f a b")]
+  #[case("(λx.x) ((λy.y) a) b", "a b")]
+  #[case("(λx.λy.x) y", "(λy1. y)")]
+  #[case("(λx.λy.x y) y", "(λy1. y y1)")]
+  #[case("(λx.λy.λy1.x y y1) (y y1)", "(λy2. (λy3. y y1 y2 y3))")]
+  #[case("(λx.λy.z) y", "(λy. z)")]
+  #[case("(λx.λx.x) y", "(λx. x)")]
+  fn normal_form(#[case] input: &str, #[case] expected_str: &str) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let ast = parser.parse_term()?;
+    let normal = eval(Rc::new(ast));
+    assert_eq!(normal.to_string(), expected_str);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(EvalStrategy::NormalOrder, "(λx.λy.y) ((λx.x x) (λx.x x))", "(λy. y)")]
+  #[case(EvalStrategy::CallByName, "(λx.λy.y) ((λx.x x) (λx.x x))", "(λy. y)")]
+  #[case(EvalStrategy::NormalOrder, "λx.(λy.y) x", "(λx. x)")]
+  #[case(EvalStrategy::CallByName, "λx.(λy.y) x", "(λx. (λy. y) x)")]
+  #[case(EvalStrategy::CallByValue, "λx.(λy.y) x", "(λx. (λy. y) x)")]
+  #[case(EvalStrategy::NormalOrder, "(λx.λy.x) ((λz.z) a)", "(λy. a)")]
+  #[case(EvalStrategy::ApplicativeOrder, "(λx.λy.x) ((λz.z) a)", "(λy. a)")]
+  #[case(EvalStrategy::CallByName, "(λx.λy.x) ((λz.z) a)", "(λy. (λz. z) a)")]
+  #[case(EvalStrategy::CallByValue, "(λx.λy.x) ((λz.z) a)", "(λy. a)")]
+  #[case(EvalStrategy::NormalOrder, "x ((λy.y) z)", "x z")]
+  #[case(EvalStrategy::CallByName, "x ((λy.y) z)", "x ((λy. y) z)")]
+  #[case(EvalStrategy::CallByValue, "x ((λy.y) z)", "x z")]
+  #[case(
+    EvalStrategy::CallByValue,
+    "(λf.f (λx.(λy.y) x)) (λg.g)",
+    "(λx. (λy. y) x)"
+  )]
+  fn strategies(
+    #[case] strategy: EvalStrategy,
+    #[case] input: &str,
+    #[case] expected_str: &str,
+  ) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let term = Rc::new(parser.parse_term()?);
+    assert_eq!(eval_with_strategy(term, strategy).to_string(), expected_str);
+    Ok(())
+  }
+
+  #[test]
+  fn substitutes_all_at_once() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("a b c").parse()?);
+    let mut bindings = vec![
+      ("a", Rc::new(Parser::new("y").parse()?)),
+      ("y", Rc::new(Parser::new("λz.z").parse()?)),
+      ("b", Rc::new(Parser::new("w").parse()?)),
+      ("w", Rc::new(Parser::new("q").parse()?)),
+      ("c", Rc::new(Parser::new("λy.a y").parse()?)),
+    ];
+    let expected = "y w (λy. a y)";
+    assert_eq!(substitute_all(&term, &bindings).to_string(), expected);
+    bindings.reverse();
+    assert_eq!(substitute_all(&term, &bindings).to_string(), expected);
+    Ok(())
+  }
+
+  /// `n (λy.y) z` for the Church numeral `n`, built directly rather than parsed
+  fn deep_identity_chain(n: usize) -> Rc<Node<'static>> {
+    let var = |name| {
+      Rc::new(Node::Identifier(Identifier {
+        name: Cow::Borrowed(name),
+      }))
+    };
+    let app = |lhs, rhs| Rc::new(Node::Application(Application { lhs, rhs }));
+    let abs = |param, body| {
+      Rc::new(Node::Abstraction(Abstraction {
+        param: Cow::Borrowed(param),
+        body,
+      }))
+    };
+    let body = (0..n).fold(var("x"), |acc, _| app(var("f"), acc));
+    let numeral = abs("f", abs("x", body));
+    app(app(numeral, abs("y", var("y"))), var("z"))
+  }
+
+  #[rstest]
+  #[case(EvalStrategy::NormalOrder)]
+  #[case(EvalStrategy::ApplicativeOrder)]
+  #[case(EvalStrategy::CallByName)]
+  #[case(EvalStrategy::CallByValue)]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Krivine))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Cek))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Secd))]
+  fn deep_term_does_not_overflow(#[case] strategy: EvalStrategy) {
+    let term = deep_identity_chain(100_000);
+    assert_eq!(eval_with_strategy(term, strategy).to_string(), "z");
+  }
+
+  #[test]
+  fn deep_term_steps_without_overflow() {
+    let term = deep_identity_chain(100_000);
+    let next = step(&term).expect("the numeral is applied");
+    assert!(next.free_vars().contains("z"));
+  }
+
+  #[rstest]
+  #[case("(λx.x x) (λx.x x)", &["(λx. x x) (λx. x x)"])]
+  #[case("(λx.x x) (λy.y y)", &["(λx. x x) (λy. y y)"])]
+  fn loop_detected(
+    #[case] input: &str,
+    #[case] expected_cycle: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let result = Evaluator::default().with_loop_detection().eval(term);
+    assert_eq!(
+      result,
+      Err(EvalError::LoopDetected {
+        cycle: expected_cycle.iter().map(ToString::to_string).collect()
+      })
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(EvalStrategy::NormalOrder, Ok("(λy. y)"))]
+  #[case(EvalStrategy::ApplicativeOrder, Err(()))]
+  #[case(EvalStrategy::CallByName, Ok("(λy. y)"))]
+  #[case(EvalStrategy::CallByValue, Err(()))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Krivine, Ok("(λy. y)")))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Cek, Err(())))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Secd, Err(())))]
+  fn loop_detection_follows_the_strategy(
+    #[case] strategy: EvalStrategy,
+    #[case] expected: Result<&str, ()>,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.λy.y) ((λx.x x) (λx.x x))").parse()?);
+    let result = Evaluator::new(strategy).with_loop_detection().eval(term);
+    match expected {
+      Ok(normal) => assert_eq!(result?.to_string(), normal),
+      Err(()) => assert!(matches!(result, Err(EvalError::LoopDetected { .. }))),
+    }
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(EvalStrategy::CallByName, "λx.(λy.y) x", "(λx. (λy. y) x)")]
+  #[case(EvalStrategy::CallByValue, "x ((λy.y) z)", "x z")]
+  #[case(EvalStrategy::CallByName, "x ((λy.y) z)", "x ((λy. y) z)")]
+  fn loop_detection_stops_where_the_strategy_does(
+    #[case] strategy: EvalStrategy,
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let normal = Evaluator::new(strategy).with_loop_detection().eval(term)?;
+    assert_eq!(normal.to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn unbounded_growth_detected() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x x) (λx.x x x)").parse()?);
+    let result = Evaluator::default().with_loop_detection().eval(term);
+    assert!(
+      matches!(result, Err(EvalError::UnboundedGrowth { steps, .. }) if steps >= GROWTH_STEPS)
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.λy.x) a b", "a")]
+  #[case("(λf.λx.f (f x)) (λy.y) z", "z")]
+  fn loop_detection_on_terminating_terms(
+    #[case] input: &str,
+    #[case] expected_str: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let normal = Evaluator::default().with_loop_detection().eval(term)?;
+    assert_eq!(normal.to_string(), expected_str);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λx.f x", "f")]
+  #[case("λx.λy.f x y", "f")]
+  #[case("λx.x", "(λx. x)")]
+  #[case("λx.x x", "(λx. x x)")]
+  #[case("λx.f x x", "(λx. f x x)")]
+  #[case("λf.λx.f x", "(λf. f)")]
+  #[case("(λg.λx.g x) (λy.h y)", "h")]
+  #[case("λx.(λy.f y) x", "f")]
+  fn beta_eta_normal_form(
+    #[case] input: &str,
+    #[case] expected_str: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(normalize_beta_eta(term).to_string(), expected_str);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(NormalForm::WeakHead, "λx.(λy.y) x", "(λx. (λy. y) x)")]
+  #[case(NormalForm::Head, "λx.(λy.y) x", "(λx. x)")]
+  #[case(NormalForm::Full, "λx.(λy.y) x", "(λx. x)")]
+  #[case(NormalForm::WeakHead, "(λx.λy.x) a", "(λy. a)")]
+  #[case(NormalForm::Head, "λx.x ((λy.y) z)", "(λx. x ((λy. y) z))")]
+  #[case(NormalForm::Full, "λx.x ((λy.y) z)", "(λx. x z)")]
+  #[case(NormalForm::Head, "λx.(λy.λz.y) x ((λw.w w) (λw.w w))", "(λx. x)")]
+  #[case(
+    NormalForm::WeakHead,
+    "x ((λx.x x) (λx.x x))",
+    "x ((λx. x x) (λx. x x))"
+  )]
+  #[case(NormalForm::Head, "x ((λx.x x) (λx.x x))", "x ((λx. x x) (λx. x x))")]
+  fn normal_form_targets(
+    #[case] target: NormalForm,
+    #[case] input: &str,
+    #[case] expected_str: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(normalize(term, target).to_string(), expected_str);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λx.(λy.y) x")]
+  #[case("x ((λa.a) b) ((λc.λd.c) e)")]
+  #[case("(λx.λy.x y) (λz.y)")]
+  #[case("λy.(λx.λy.x y) y")]
+  #[case("(λn.λf.λx.f (n f x)) (λf.λx.f x)")]
+  fn strong_reduction(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let expected = normalize(Rc::clone(&term), NormalForm::Full);
+    for &strategy in EvalStrategy::ALL {
+      let normal = Evaluator::new(strategy)
+        .with_strong_reduction()
+        .eval(Rc::clone(&term))?;
+      assert!(normal.alpha_eq(&expected), "{:?} gave {}", strategy, normal);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn normalizes_many() -> Result<(), anyhow::Error> {
+    let terms = [
+      "(λx.x) (λy.y)",
+      "(λz.z) (λw.w)",
+      "(λx.x x) (λx.x x)",
+      "(λa.a) b",
+    ]
+    .into_iter()
+    .map(|source| Ok(Rc::new(Parser::new(source).parse()?)))
+    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    let evaluated = Cell::new(0);
+    let mut hooks = Hooks::new();
+    hooks.on_normal_form(|_| evaluated.set(evaluated.get() + 1));
+    let evaluator = Evaluator::default().with_fuel(100).with_hooks(&hooks);
+    let results = normalize_many(terms, &evaluator);
+    let shown: Vec<_> = results
+      .iter()
+      .map(|result| result.clone().map(|normal| normal.to_string()))
+      .collect();
+    assert_eq!(
+      shown,
+      [
+        Ok("(λy. y)".to_string()),
+        Ok("(λy. y)".to_string()),
+        Err(EvalError::StepLimitExceeded),
+        Ok("b".to_string())
+      ]
+    );
+    // the second term is alpha-equivalent to the first, so is never evaluated
+    assert_eq!(evaluated.get(), 2);
+    assert!(Rc::ptr_eq(
+      results[0].as_ref().unwrap(),
+      results[1].as_ref().unwrap()
+    ));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(EvalStrategy::ApplicativeOrder, "(λx.λy.x) a b", (2, 2, 7))]
+  #[case(EvalStrategy::CallByName, "(λx.λy.x) a b", (2, 2, 7))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Cek, "(λx.λy.x) a b", (2, 0, 7)))]
+  #[cfg_attr(feature = "machines", case(EvalStrategy::Krivine, "(λx.λy.x) a b", (2, 0, 7)))]
+  #[case(EvalStrategy::NormalOrder, "(λx.x x x) (a b c d)", (1, 1, 23))]
+  fn stats(
+    #[case] strategy: EvalStrategy,
+    #[case] input: &str,
+    #[case] expected: (usize, usize, usize),
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let evaluator = Evaluator::new(strategy);
+    evaluator.eval(term)?;
+    let stats = evaluator.stats();
+    assert_eq!(
+      (stats.beta_reductions, stats.substitutions, stats.max_size),
+      expected
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.x) ((λy.y) z)", &["(λy. y) z", "(λx. x) z"])]
+  #[case("(λx.λy.x) ((λz.z) a)", &["(λy. (λz. z) a)", "(λx. (λy. x)) a"])]
+  #[case("λx.x", &[])]
+  fn reduce_each_redex(
+    #[case] input: &str,
+    #[case] expected_strs: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let reduced: Vec<_> = term
+      .redexes()
+      .iter()
+      .map(|path| reduce_at(&term, path).map(|term| term.to_string()))
+      .collect::<Option<_>>()
+      .expect("every redex can be contracted");
+    assert_eq!(reduced, expected_strs);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(Path::default())]
+  #[case(Path(vec![Direction::Lhs]))]
+  #[case(Path(vec![Direction::Body]))]
+  #[case(Path(vec![Direction::Rhs, Direction::Rhs]))]
+  fn reduce_at_without_redex(#[case] path: Path) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("f ((λx.x) y)").parse()?);
+    assert_eq!(reduce_at(&term, &path), None);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.x) y", "y", Ok(true), Ok(true))]
+  #[case("λx.x", "λy.y", Ok(true), Ok(true))]
+  #[case("(λx.λy.x) a", "λz.a", Ok(true), Ok(true))]
+  #[case("λx.f x", "f", Ok(false), Ok(true))]
+  #[case("(λx.λy.x y) g", "g", Ok(false), Ok(true))]
+  #[case("λx.λy.x", "λx.λy.y", Ok(false), Ok(false))]
+  #[case("x", "y", Ok(false), Ok(false))]
+  #[case(
+    "(λx.x x) (λx.x x)",
+    "y",
+    Err(EvalError::StepLimitExceeded),
+    Err(EvalError::StepLimitExceeded)
+  )]
+  fn equivalence(
+    #[case] a: &str,
+    #[case] b: &str,
+    #[case] expected: Result<bool, EvalError>,
+    #[case] expected_eta: Result<bool, EvalError>,
+  ) -> Result<(), anyhow::Error> {
+    let a = Parser::new(a).parse()?;
+    let b = Parser::new(b).parse()?;
+    assert_eq!(equiv(&a, &b), expected);
+    assert_eq!(equiv(&b, &a), expected);
+    assert_eq!(equiv_eta(&a, &b), expected_eta);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.λy.x) a b", EvalStrategy::ALL, true, &[2; EvalStrategy::ALL.len()])]
+  #[case("(λx.y) ((λx.x x) (λx.x x))", &[EvalStrategy::NormalOrder], true, &[1])]
+  #[case(
+    "(λx.y) ((λx.x x) (λx.x x))",
+    &[EvalStrategy::NormalOrder, EvalStrategy::ApplicativeOrder],
+    false,
+    &[1, 50]
+  )]
+  #[case(
+    "λx.(λy.y) x",
+    &[EvalStrategy::NormalOrder, EvalStrategy::CallByName],
+    false,
+    &[1, 0]
+  )]
+  fn strategy_comparison(
+    #[case] input: &str,
+    #[case] strategies: &[EvalStrategy],
+    #[case] expected_agree: bool,
+    #[case] expected_steps: &[usize],
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let comparison = compare_strategies(&term, strategies, 50);
+    assert_eq!(comparison.agree(), expected_agree);
+    let steps: Vec<_> = comparison.runs.iter().map(|run| run.steps).collect();
+    assert_eq!(steps, expected_steps);
+    Ok(())
+  }
+
+  #[test]
+  fn observer_sees_every_step() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x) ((λy.y) z)").parse()?);
+    let mut seen = Vec::new();
+    let normal = eval_with_observer(term, |info| {
+      seen.push(format!(
+        "{:?} {} at {}: {}",
+        info.phase, info.step, info.path, info.term
+      ))
+    });
+    assert_eq!(normal.to_string(), "z");
+    assert_eq!(
+      seen,
+      [
+        "Before 1 at rhs: (λx. x) ((λy. y) z)",
+        "After 1 at rhs: (λx. x) z",
+        "Before 2 at root: (λx. x) z",
+        "After 2 at root: z",
+      ]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn stats_survive_errors() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let evaluator = Evaluator::new(EvalStrategy::NormalOrder).with_fuel(10);
+    assert_eq!(evaluator.eval(term), Err(EvalError::StepLimitExceeded));
+    assert_eq!(evaluator.stats().beta_reductions, 10);
+    assert_eq!(evaluator.stats().substitutions, 10);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x", 0, Ok("x"))]
+  #[case("(λx.x) y", 1, Ok("y"))]
+  #[case("(λx.x) ((λx.x) y)", 1, Err(EvalError::StepLimitExceeded))]
+  #[case("(λx.x) ((λx.x) y)", 2, Ok("y"))]
+  #[case("(λx.x x) (λx.x x)", 1000, Err(EvalError::StepLimitExceeded))]
+  #[case("(λx.x x x) (λx.x x x)", 1000, Err(EvalError::StepLimitExceeded))]
+  fn fuel(
+    #[case] input: &str,
+    #[case] max_steps: usize,
+    #[case] expected: Result<&str, EvalError>,
+  ) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let term = Rc::new(parser.parse_term()?);
+    let result = eval_with_fuel(term, max_steps).map(|normal| normal.to_string());
+    assert_eq!(result, expected.map(str::to_string));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.x x) ((λy.y) z)", &["(λx. x x) z", "z z"])]
+  #[case("λx.x", &[])]
+  fn reducer(#[case] input: &str, #[case] expected_steps: &[&str]) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let term = Rc::new(parser.parse_term()?);
+    let mut reducer = Reducer::new(Rc::clone(&term));
+    let steps: Vec<_> = reducer.by_ref().map(|t| t.to_string()).collect();
+    assert_eq!(steps, expected_steps);
+    assert_eq!(reducer.current(), &eval(term));
+    Ok(())
+  }
+
+  #[test]
+  fn reducer_on_divergent_term() -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new("(λx.x x) (λx.x x)");
+    let term = Rc::new(parser.parse_term()?);
+    let steps: Vec<_> = Reducer::new(Rc::clone(&term)).take(3).collect();
+    assert_eq!(steps, vec![Rc::clone(&term); 3]);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x", &[])]
+  #[case(
+    "(λx.x x) ((λy.y) z)",
+    &[("(λy. y) z", "(λx. x x) z"), ("(λx. x x) z", "z z")]
+  )]
+  #[case(
+    "λa.(λx.λy.x) a b",
+    &[("(λx. (λy. x)) a", "(λa. (λy. a) b)"), ("(λy. a) b", "(λa. a)")]
+  )]
+  fn traces(
+    #[case] input: &str,
+    #[case] expected_steps: &[(&str, &str)],
+  ) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let term = Rc::new(parser.parse_term()?);
+    let trace = trace(Rc::clone(&term));
+    let steps: Vec<_> = trace
+      .steps
+      .iter()
+      .map(|step| (step.redex.to_string(), step.term.to_string()))
+      .collect();
+    let expected_steps: Vec<_> = expected_steps
+      .iter()
+      .map(|&(redex, term)| (redex.to_string(), term.to_string()))
+      .collect();
+    assert_eq!(steps, expected_steps);
+    assert_eq!(trace.terms().count(), steps.len() + 1);
+    assert_eq!(trace.normal_form(), &eval(term));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("(λx.x) y")]
+  #[case("(λx.x x) (λx.x x)")]
+  fn cancelled_before_start(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let term = Rc::new(parser.parse_term()?);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert_eq!(eval_with_cancel(term, &cancel), Err(EvalError::Cancelled));
+    Ok(())
+  }
+
+  #[test]
+  fn cancelled_from_another_thread() -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new("(λx.x x) (λx.x x)");
+    let term = Rc::new(parser.parse_term()?);
+    let cancel = CancelToken::new();
+    let remote = cancel.clone();
+    let handle = std::thread::spawn(move || {
+      std::thread::sleep(std::time::Duration::from_millis(10));
+      remote.cancel();
+    });
+    assert_eq!(eval_with_cancel(term, &cancel), Err(EvalError::Cancelled));
+    handle.join().unwrap();
+    Ok(())
+  }
+
+  #[test]
+  fn stepping_engines_cancel() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let evaluator = Evaluator::default().with_cancel(&cancel);
+    assert_eq!(evaluator.step(&term), Err(EvalError::Cancelled));
+    assert_eq!(evaluator.trace(Rc::clone(&term)), Err(EvalError::Cancelled));
+    let observed = evaluator.eval_with_observer(Rc::clone(&term), |_| ());
+    assert_eq!(observed, Err(EvalError::Cancelled));
+    let normal = evaluator.normalize_beta_eta(Rc::clone(&term));
+    assert_eq!(normal, Err(EvalError::Cancelled));
+    let mut reducer = Reducer::new(Rc::clone(&term)).with_cancel(&cancel);
+    assert_eq!(reducer.next(), None);
+    assert!(reducer.is_cancelled());
+    let normal = Rc::new(Parser::new("λx.x").parse()?);
+    assert_eq!(evaluator.step(&normal), Ok(None));
+    Ok(())
+  }
+
+  #[test]
+  fn stepping_engines_keep_fuel() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let evaluator = Evaluator::default().with_fuel(3);
+    assert_eq!(evaluator.trace(term), Err(EvalError::StepLimitExceeded));
+    assert_eq!(evaluator.stats().beta_reductions, 3);
+    let term = Rc::new(Parser::new("(λf.λx.f x) (λy.y)").parse()?);
+    let trace = evaluator.trace(term)?;
+    assert_eq!(trace.normal_form().to_string(), "(λx. x)");
+    assert_eq!(trace.steps.len(), 2);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x", &[])]
+  #[case("(λx.x) y", &["y"])]
+  #[case("(λx.x x) ((λy.y) z)", &["(λx. x x) z", "z z"])]
+  #[case("λa.(λx.λy.x) a b", &["(λa. (λy. a) b)", "(λa. a)"])]
+  fn single_steps(
+    #[case] input: &str,
+    #[case] expected_steps: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    let mut term = Rc::new(parser.parse_term()?);
+    let mut steps = Vec::new();
+    while let Some(next) = step(&term) {
+      steps.push(next.to_string());
+      term = next;
+    }
+    assert_eq!(steps, expected_steps);
+    assert_eq!(step(&eval(term)), None);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x", true, true, true)]
+  #[case("λx.(λy.y) x", true, false, false)]
+  #[case("λx.x ((λy.y) z)", true, true, false)]
+  #[case("x ((λy.y) z)", true, true, false)]
+  #[case("(λx.x) y", false, false, false)]
+  #[case("f x (λx.x y)", true, true, true)]
+  fn normal_forms_hold(
+    #[case] input: &str,
+    #[case] weak_head: bool,
+    #[case] head: bool,
+    #[case] full: bool,
+  ) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(NormalForm::WeakHead.holds_for(&term), weak_head);
+    assert_eq!(NormalForm::Head.holds_for(&term), head);
+    assert_eq!(NormalForm::Full.holds_for(&term), full);
+    assert_eq!(is_normal_form(&term), full);
+    Ok(())
+  }
+
+  #[test]
+  fn normalization_is_idempotent() {
+    use crate::random::{closed_term, Xorshift};
+
+    let mut rng = Xorshift::from_seed(0x1de);
+    for size in (2..40).cycle().take(400) {
+      let term = closed_term(&mut rng, size);
+      for target in [NormalForm::WeakHead, NormalForm::Head, NormalForm::Full] {
+        let evaluator = Evaluator::default().with_fuel(200);
+        let Ok(normal) = evaluator.normalize(Rc::clone(&term), target) else {
+          continue;
+        };
+        assert!(target.holds_for(&normal), "{:?} of {}", target, term);
+        let again = evaluator.normalize(Rc::clone(&normal), target);
+        assert_eq!(again.as_ref(), Ok(&normal));
+        assert_eq!(evaluator.stats().beta_reductions, 0);
+      }
+    }
+  }
+}
@@ -0,0 +1,171 @@
+//! Combinators for assembling reduction strategies out of single rewrites
+//!
+//! A [`Strategy`] either rewrites a term or fails. The only primitive is [`beta`],
+//! which contracts a term that is itself a redex; everything else decides where
+//! and how often to apply other strategies. Normal order reduction, for example,
+//! is `outermost(beta())`:
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::eval::combinators::{beta, outermost, Strategy};
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("(λx.λy.y) ((λx.x x) (λx.x x))").parse()?);
+//! let normal = outermost(beta()).apply(&term).unwrap();
+//! assert_eq!(normal.to_string(), "(λy. y)");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::rc::Rc;
+
+use crate::ast::Node;
+
+use super::{contract_root, rewrite_first, Order};
+
+/// A rewrite which either produces a new term or fails
+pub trait Strategy<'inp> {
+  fn apply(&self, node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>>;
+}
+
+impl<'inp, F> Strategy<'inp> for F
+where
+  F: Fn(&Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>>,
+{
+  fn apply(&self, node: &Rc<Node<'inp>>) -> Option<Rc<Node<'inp>>> {
+    self(node)
+  }
+}
+
+/// Contract the term if it is itself a redex, failing otherwise
+pub fn beta<'inp>() -> impl Strategy<'inp> {
+  |node: &Rc<Node<'inp>>| contract_root(node)
+}
+
+/// Succeed without changing the term
+pub fn identity<'inp>() -> impl Strategy<'inp> {
+  |node: &Rc<Node<'inp>>| Some(Rc::clone(node))
+}
+
+/// Apply `first` and then `second` to its result, failing if either fails
+pub fn sequence<'inp>(
+  first: impl Strategy<'inp>,
+  second: impl Strategy<'inp>,
+) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| second.apply(&first.apply(node)?)
+}
+
+/// Apply `first`, falling back to `second` if it fails
+pub fn choice<'inp>(
+  first: impl Strategy<'inp>,
+  second: impl Strategy<'inp>,
+) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| first.apply(node).or_else(|| second.apply(node))
+}
+
+/// Apply `strategy`, leaving the term unchanged instead of failing
+pub fn attempt<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| Some(strategy.apply(node).unwrap_or_else(|| Rc::clone(node)))
+}
+
+/// Apply `strategy` until it fails, which never terminates if it always succeeds
+pub fn repeat<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| {
+    let mut current = Rc::clone(node);
+    while let Some(next) = strategy.apply(&current) {
+      current = next;
+    }
+    Some(current)
+  }
+}
+
+/// Apply `strategy` to the leftmost outermost subterm where it succeeds
+pub fn once<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| {
+    rewrite_first(node, Order::PreOrder, true, |node| strategy.apply(node)).map(|(_, _, term)| term)
+  }
+}
+
+/// Apply `strategy` to the leftmost innermost subterm where it succeeds
+pub fn once_innermost<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  move |node: &Rc<Node<'inp>>| {
+    rewrite_first(node, Order::PostOrder, true, |node| strategy.apply(node))
+      .map(|(_, _, term)| term)
+  }
+}
+
+/// Rewrite outermost subterms with `strategy` until it applies nowhere
+pub fn outermost<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  repeat(once(strategy))
+}
+
+/// Rewrite innermost subterms with `strategy` until it applies nowhere
+pub fn innermost<'inp>(strategy: impl Strategy<'inp>) -> impl Strategy<'inp> {
+  repeat(once_innermost(strategy))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::eval::{eval_with_strategy, step, EvalStrategy};
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  fn parse(input: &str) -> Rc<Node<'_>> {
+    Rc::new(Parser::new(input).parse().unwrap())
+  }
+
+  #[rstest]
+  #[case("(λx.λy.y) ((λx.x x) (λx.x x))")]
+  #[case("(λf.λx.f (f x)) (λy.y) z")]
+  #[case("λa.(λx.λy.x) a b")]
+  fn outermost_beta_is_normal_order(#[case] input: &str) {
+    let term = parse(input);
+    assert_eq!(
+      outermost(beta()).apply(&term),
+      Some(eval_with_strategy(term, EvalStrategy::NormalOrder))
+    );
+  }
+
+  #[rstest]
+  #[case("(λx.x x) ((λy.y) z)")]
+  #[case("λa.(λx.λy.x) a b")]
+  fn once_innermost_beta_is_step(#[case] input: &str) {
+    let term = parse(input);
+    assert_eq!(once_innermost(beta()).apply(&term), step(&term));
+  }
+
+  #[rstest]
+  #[case("(λx.x) ((λy.y) z)", Some("(λy. y) z"))]
+  #[case("x ((λy.y) z)", Some("x z"))]
+  #[case("x y", None)]
+  fn once_beta(#[case] input: &str, #[case] expected: Option<&str>) {
+    let result = once(beta()).apply(&parse(input));
+    assert_eq!(result.map(|term| term.to_string()).as_deref(), expected);
+  }
+
+  #[rstest]
+  #[case("(λx.x) y", Some("y"))]
+  #[case("(λx.λy.x) a", Some("(λy. a)"))]
+  #[case("x ((λy.y) z)", None)]
+  fn sequence_and_choice(#[case] input: &str, #[case] expected: Option<&str>) {
+    let term = parse(input);
+    let twice = sequence(beta(), attempt(beta()));
+    assert_eq!(
+      twice.apply(&term).map(|term| term.to_string()).as_deref(),
+      expected
+    );
+    let fallback = choice(beta(), identity());
+    assert!(fallback.apply(&term).is_some());
+    assert_eq!(attempt(beta()).apply(&parse("x")), Some(parse("x")));
+  }
+
+  #[test]
+  fn innermost_reduces_arguments_first() {
+    let term = parse("(λx.λy.y) ((λx.x x) (λx.x x))");
+    let first = once_innermost(beta()).apply(&term).unwrap();
+    assert_eq!(first, term);
+    assert_eq!(
+      innermost(beta()).apply(&parse("(λx.x) ((λy.y) z)")),
+      Some(parse("z"))
+    );
+  }
+}
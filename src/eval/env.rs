@@ -0,0 +1,392 @@
+//! Call by value evaluation with environments of bindings instead of substitution
+//!
+//! Abstractions evaluate to closures which remember the values of their free
+//! variables, so a beta reduction only extends an environment rather than copying
+//! the body of the abstraction. Terms are only rebuilt when a value is read back.
+//!
+//! Evaluation runs on a CEK machine: a control term, the environment it is
+//! evaluated in, and a continuation made of the frames waiting on its value. The
+//! continuation lives on the heap, so evaluation uses constant Rust stack.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+use super::{substitute_all, EvalError};
+
+/// The result of evaluating a term in an environment
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Value<'inp> {
+  /// An abstraction together with the bindings of its free variables
+  Closure(Closure<'inp>),
+  /// A variable without a binding
+  Variable(Cow<'inp, str>),
+  /// An application stuck on a variable without a binding
+  Application(Rc<Value<'inp>>, Rc<Value<'inp>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Closure<'inp> {
+  pub param: Cow<'inp, str>,
+  pub body: Rc<Node<'inp>>,
+  pub env: Env<'inp>,
+}
+
+/// A persistent list of bindings, where later bindings shadow earlier ones
+#[derive(Debug)]
+pub struct Env<'inp, V = Value<'inp>>(Option<Rc<Binding<'inp, V>>>);
+
+#[derive(Debug)]
+struct Binding<'inp, V> {
+  name: Cow<'inp, str>,
+  value: Rc<V>,
+  next: Env<'inp, V>,
+}
+
+impl<'inp, V> Env<'inp, V> {
+  pub fn new() -> Self {
+    Env(None)
+  }
+
+  /// A new environment with `name` bound to `value`, leaving this one untouched
+  pub fn bind(&self, name: Cow<'inp, str>, value: Rc<V>) -> Self {
+    Env(Some(Rc::new(Binding {
+      name,
+      value,
+      next: self.clone(),
+    })))
+  }
+
+  /// The innermost binding of `name`
+  pub fn lookup(&self, name: &str) -> Option<&Rc<V>> {
+    let mut env = self;
+    while let Some(binding) = &env.0 {
+      if binding.name == name {
+        return Some(&binding.value);
+      }
+      env = &binding.next;
+    }
+    None
+  }
+
+  /// Move out the values of every binding which this environment alone keeps alive
+  pub(super) fn take_unshared_values(&mut self, values: &mut Vec<Rc<V>>) {
+    let mut next = self.0.take();
+    while let Some(binding) = next {
+      let Ok(mut binding) = Rc::try_unwrap(binding) else {
+        break;
+      };
+      values.push(binding.value);
+      next = binding.next.0.take();
+    }
+  }
+}
+
+impl<V> Default for Env<'_, V> {
+  fn default() -> Self {
+    Env::new()
+  }
+}
+
+impl<V> Clone for Env<'_, V> {
+  fn clone(&self) -> Self {
+    Env(self.0.clone())
+  }
+}
+
+impl<V> Drop for Env<'_, V> {
+  fn drop(&mut self) {
+    let mut values = Vec::new();
+    self.take_unshared_values(&mut values);
+  }
+}
+
+impl<'inp> Value<'inp> {
+  /// Move out every value which this one alone keeps alive, leaving a shared
+  /// placeholder in its place
+  fn take_unshared_children(&mut self, children: &mut Vec<Rc<Value<'inp>>>) {
+    match self {
+      Value::Closure(closure) => closure.env.take_unshared_values(children),
+      Value::Application(lhs, rhs) => {
+        for child in [lhs, rhs] {
+          if Rc::strong_count(child) == 1 {
+            children.push(mem::replace(child, placeholder()));
+          }
+        }
+      }
+      Value::Variable(..) => (),
+    }
+  }
+
+  /// Convert the value back into a term, closing the body of each closure over the
+  /// values of its bindings
+  pub fn readback(&self) -> Rc<Node<'inp>> {
+    let mut stack = Vec::new();
+    let mut control = Readback::Value(self);
+    loop {
+      control = match control {
+        Readback::Value(Value::Variable(name)) => {
+          Readback::Term(Rc::new(Node::Identifier(Identifier { name: name.clone() })))
+        }
+        Readback::Value(Value::Application(lhs, rhs)) => {
+          stack.push(ReadbackFrame::Rhs(rhs));
+          Readback::Value(lhs)
+        }
+        Readback::Value(Value::Closure(closure)) => {
+          let mut free = closure.body.free_vars();
+          free.remove(closure.param.as_ref());
+          let pending = free
+            .into_iter()
+            .filter_map(|name| Some((name, &**closure.env.lookup(name)?)))
+            .collect();
+          next_binding(closure, pending, Vec::new(), &mut stack)
+        }
+        Readback::Term(term) => match stack.pop() {
+          None => return term,
+          Some(ReadbackFrame::Rhs(rhs)) => {
+            stack.push(ReadbackFrame::Lhs(term));
+            Readback::Value(rhs)
+          }
+          Some(ReadbackFrame::Lhs(lhs)) => {
+            Readback::Term(Rc::new(Node::Application(Application { lhs, rhs: term })))
+          }
+          Some(ReadbackFrame::Binding {
+            closure,
+            name,
+            pending,
+            mut done,
+          }) => {
+            done.push((name, term));
+            next_binding(closure, pending, done, &mut stack)
+          }
+        },
+      };
+    }
+  }
+}
+
+/// A leaf to stand in for values that have been moved out of one being dropped
+fn placeholder<'inp>() -> Rc<Value<'inp>> {
+  thread_local! {
+    static PLACEHOLDER: Rc<Value<'static>> = Rc::new(Value::Variable(Cow::Borrowed("")));
+  }
+  PLACEHOLDER
+    .try_with(Rc::clone)
+    .unwrap_or_else(|_| Rc::new(Value::Variable(Cow::Borrowed(""))))
+}
+
+impl Drop for Value<'_> {
+  /// Free nested values with an explicit stack, as the derived recursive drop would
+  /// overflow the Rust stack on long chains of values
+  fn drop(&mut self) {
+    let mut children = Vec::new();
+    self.take_unshared_children(&mut children);
+    while let Some(child) = children.pop() {
+      if let Ok(mut value) = Rc::try_unwrap(child) {
+        value.take_unshared_children(&mut children);
+      }
+    }
+  }
+}
+
+impl fmt::Display for Value<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.readback())
+  }
+}
+
+/// What the reading back of a value does next
+enum Readback<'v, 'inp> {
+  Value(&'v Value<'inp>),
+  Term(Rc<Node<'inp>>),
+}
+
+/// Work waiting on a term while reading back a value
+enum ReadbackFrame<'v, 'inp> {
+  /// The term is the left side of an application, whose right side is next
+  Rhs(&'v Value<'inp>),
+  /// The term is the right side of an application with this left side
+  Lhs(Rc<Node<'inp>>),
+  /// The term is the value bound to `name` in the environment of `closure`
+  Binding {
+    closure: &'v Closure<'inp>,
+    name: &'v str,
+    pending: Vec<(&'v str, &'v Value<'inp>)>,
+    done: Vec<(&'v str, Rc<Node<'inp>>)>,
+  },
+}
+
+/// Read back the next binding used by `closure`, or close it once none are left
+fn next_binding<'v, 'inp>(
+  closure: &'v Closure<'inp>,
+  mut pending: Vec<(&'v str, &'v Value<'inp>)>,
+  done: Vec<(&'v str, Rc<Node<'inp>>)>,
+  stack: &mut Vec<ReadbackFrame<'v, 'inp>>,
+) -> Readback<'v, 'inp> {
+  match pending.pop() {
+    Some((name, value)) => {
+      stack.push(ReadbackFrame::Binding {
+        closure,
+        name,
+        pending,
+        done,
+      });
+      Readback::Value(value)
+    }
+    None => {
+      let abstraction = Rc::new(Node::Abstraction(Abstraction {
+        param: closure.param.clone(),
+        body: Rc::clone(&closure.body),
+      }));
+      Readback::Term(substitute_all(&abstraction, &done))
+    }
+  }
+}
+
+/// Evaluate a closed or open term in an empty environment
+///
+/// Like `EvalStrategy::CallByValue`, arguments are evaluated before they are
+/// bound and evaluation never enters the body of an abstraction. Variables
+/// without a binding evaluate to themselves
+pub fn eval<'inp>(node: &Rc<Node<'inp>>) -> Rc<Value<'inp>> {
+  eval_in(node, &Env::new())
+}
+
+/// Evaluate a term with its free variables looked up in `env`
+pub fn eval_in<'inp>(node: &Rc<Node<'inp>>, env: &Env<'inp>) -> Rc<Value<'inp>> {
+  run(node, env, || Ok(())).expect("evaluation without limits cannot fail")
+}
+
+/// Run the machine, calling `safepoint` before every beta reduction
+pub(super) fn run<'inp>(
+  node: &Rc<Node<'inp>>,
+  env: &Env<'inp>,
+  mut safepoint: impl FnMut() -> Result<(), EvalError>,
+) -> Result<Rc<Value<'inp>>, EvalError> {
+  let mut stack = Vec::new();
+  let mut control = Control::Eval(Rc::clone(node), env.clone());
+  loop {
+    control = match control {
+      Control::Eval(node, env) => match &*node {
+        Node::Identifier(id) => Control::Return(match env.lookup(&id.name) {
+          Some(value) => Rc::clone(value),
+          None => Rc::new(Value::Variable(id.name.clone())),
+        }),
+        Node::Abstraction(abs) => Control::Return(Rc::new(Value::Closure(Closure {
+          param: abs.param.clone(),
+          body: Rc::clone(&abs.body),
+          env,
+        }))),
+        Node::Application(app) => {
+          stack.push(Frame::Argument {
+            rhs: Rc::clone(&app.rhs),
+            env: env.clone(),
+          });
+          Control::Eval(Rc::clone(&app.lhs), env)
+        }
+      },
+      Control::Return(value) => match stack.pop() {
+        None => return Ok(value),
+        Some(Frame::Argument { rhs, env }) => {
+          stack.push(Frame::Apply(value));
+          Control::Eval(rhs, env)
+        }
+        Some(Frame::Apply(function)) => match &*function {
+          Value::Closure(closure) => {
+            safepoint()?;
+            Control::Eval(
+              Rc::clone(&closure.body),
+              closure.env.bind(closure.param.clone(), value),
+            )
+          }
+          _ => Control::Return(Rc::new(Value::Application(function, value))),
+        },
+      },
+    };
+  }
+}
+
+/// What the machine does next: evaluate a term in an environment, or hand a value
+/// to the continuation on top of its stack
+enum Control<'inp> {
+  Eval(Rc<Node<'inp>>, Env<'inp>),
+  Return(Rc<Value<'inp>>),
+}
+
+/// A continuation, which is work waiting on a value
+enum Frame<'inp> {
+  /// The value is a function, to be applied to `rhs` once it is evaluated
+  Argument { rhs: Rc<Node<'inp>>, env: Env<'inp> },
+  /// The value is the argument of this function
+  Apply(Rc<Value<'inp>>),
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  use crate::eval::{eval_with_strategy, EvalStrategy, Evaluator};
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", "x")]
+  #[case("λx.x", "(λx. x)")]
+  #[case("(λx.x) y", "y")]
+  #[case("(λx.λy.x) a b", "a")]
+  #[case("(λx.λy.x) ((λz.z) a)", "(λy. a)")]
+  #[case("x ((λy.y) z)", "x z")]
+  #[case("λx.(λy.y) x", "(λx. (λy. y) x)")]
+  #[case("(λx.λy.x y) y", "(λy1. y y1)")]
+  #[case("(λv.(λy.λz.v) a) y", "(λz. y)")]
+  #[case("(λg.(λy.(λv.λz.v) (g w)) a) (λk.y)", "(λz. y)")]
+  #[case("(λf.f (λx.(λy.y) x)) (λg.g)", "(λx. (λy. y) x)")]
+  fn values(#[case] input: &str, #[case] expected_str: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(eval(&term).to_string(), expected_str);
+    assert_eq!(
+      eval_with_strategy(Rc::clone(&term), EvalStrategy::Cek).to_string(),
+      expected_str
+    );
+    assert_eq!(
+      eval_with_strategy(term, EvalStrategy::CallByValue).to_string(),
+      expected_str
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn cek_strategy_counts_steps() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let result = Evaluator::new(EvalStrategy::Cek).with_fuel(100).eval(term);
+    assert_eq!(result, Err(EvalError::StepLimitExceeded));
+    Ok(())
+  }
+
+  #[test]
+  fn free_variables_from_the_environment() -> Result<(), anyhow::Error> {
+    let id = eval(&Rc::new(Parser::new("λx.x").parse()?));
+    let env = Env::new().bind("id".into(), id);
+    let term = Rc::new(Parser::new("id id y").parse()?);
+    assert_eq!(eval_in(&term, &env).to_string(), "y");
+    Ok(())
+  }
+
+  #[test]
+  fn deep_values_do_not_overflow() {
+    let var = |name| {
+      Rc::new(Node::Identifier(Identifier {
+        name: Cow::Borrowed(name),
+      }))
+    };
+    let app = |lhs, rhs| Rc::new(Node::Application(Application { lhs, rhs }));
+    let term = (0..100_000).fold(var("x"), |acc, _| app(var("f"), acc));
+    let value = eval(&term);
+    assert_eq!(value.readback().free_vars(), HashSet::from(["f", "x"]));
+  }
+}
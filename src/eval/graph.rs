@@ -0,0 +1,258 @@
+//! The graph of every way a term reduces, for seeing where reductions meet again
+//!
+//! [`explore`] follows every redex of every term reachable from the start, up to a
+//! bound on the number of terms, and [`explore_within`] also up to a number of
+//! steps from the start. [`ReductionGraph::to_dot`] writes the result for
+//! Graphviz. Alpha-equivalent terms are one node, so two reductions that reach
+//! the same term from different sides close a diamond.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::eval::graph;
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("(λx.f x) ((λy.y) z)").parse()?);
+//! let graph = graph::explore(term, 100);
+//! assert_eq!((graph.terms.len(), graph.steps.len()), (4, 4));
+//! assert!(graph.to_dot().starts_with("digraph reductions {"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::ast::{Alpha, Node, Path};
+
+use super::{is_normal_form, reduce_at, CancelToken, EvalError};
+
+/// Terms reachable from a start by beta reduction, and the steps between them
+#[derive(Debug, Clone)]
+pub struct ReductionGraph<'inp> {
+  /// Every term found, the start first, then in the order they were reached
+  pub terms: Vec<Rc<Node<'inp>>>,
+  pub steps: Vec<Step>,
+  /// Whether the bound was hit, leaving some steps out
+  pub truncated: bool,
+}
+
+/// A beta reduction from one term of a graph to another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+  pub from: usize,
+  pub to: usize,
+  /// Position of the redex contracted in `from`
+  pub redex: Path,
+  /// Whether `to` had been reached before, so that the step closes a diamond or a
+  /// cycle instead of finding a new term
+  pub revisit: bool,
+}
+
+/// Follow every redex from `start`, breadth first, keeping at most `max_terms`
+/// terms and leaving out the steps to any further ones
+pub fn explore(start: Rc<Node<'_>>, max_terms: usize) -> ReductionGraph<'_> {
+  explore_with_cancel(start, max_terms, &CancelToken::new()).expect("never cancelled")
+}
+
+/// Explore like `explore`, giving up with `EvalError::Cancelled` once `cancel` is
+/// triggered, checked before every beta reduction
+pub fn explore_with_cancel<'inp>(
+  start: Rc<Node<'inp>>,
+  max_terms: usize,
+  cancel: &CancelToken,
+) -> Result<ReductionGraph<'inp>, EvalError> {
+  explore_within(start, max_terms, usize::MAX, cancel)
+}
+
+/// Explore like `explore_with_cancel`, also leaving out the steps from terms
+/// `max_depth` steps away from the start
+pub fn explore_within<'inp>(
+  start: Rc<Node<'inp>>,
+  max_terms: usize,
+  max_depth: usize,
+  cancel: &CancelToken,
+) -> Result<ReductionGraph<'inp>, EvalError> {
+  let mut graph = ReductionGraph {
+    terms: vec![Rc::clone(&start)],
+    steps: Vec::new(),
+    truncated: false,
+  };
+  let mut index = HashMap::from([(Alpha(start), 0)]);
+  // steps from the start to each term, which breadth first are the fewest
+  let mut depths = vec![0];
+  let mut next = 0;
+  while next < graph.terms.len() {
+    let term = Rc::clone(&graph.terms[next]);
+    if depths[next] >= max_depth {
+      graph.truncated |= !is_normal_form(&term);
+      next += 1;
+      continue;
+    }
+    for redex in term.redexes() {
+      if cancel.is_cancelled() {
+        return Err(EvalError::Cancelled);
+      }
+      let reduct = reduce_at(&term, &redex).expect("a redex is at every path found");
+      let (to, revisit) = match index.get(&Alpha(Rc::clone(&reduct))) {
+        Some(&to) => (to, true),
+        None if graph.terms.len() < max_terms => {
+          index.insert(Alpha(Rc::clone(&reduct)), graph.terms.len());
+          graph.terms.push(reduct);
+          depths.push(depths[next] + 1);
+          (graph.terms.len() - 1, false)
+        }
+        None => {
+          graph.truncated = true;
+          continue;
+        }
+      };
+      graph.steps.push(Step {
+        from: next,
+        to,
+        redex,
+        revisit,
+      });
+    }
+    next += 1;
+  }
+  Ok(graph)
+}
+
+impl ReductionGraph<'_> {
+  /// Indices of the terms with no redex left, which in a truncated graph need not
+  /// be every term without a step from it
+  pub fn normal_forms(&self) -> impl Iterator<Item = usize> + '_ {
+    (0..self.terms.len()).filter(|&term| is_normal_form(&self.terms[term]))
+  }
+
+  /// The graph in Graphviz's DOT language, each term labelled with its text and each
+  /// step with the position of its redex. The start is drawn bold, normal forms
+  /// with a double border, and steps back to a term already reached dashed
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::from("digraph reductions {\n  node [shape=box];\n");
+    let normal: Vec<_> = self.normal_forms().collect();
+    for (id, term) in self.terms.iter().enumerate() {
+      let mut attrs = format!("label={}", quoted(&term.to_string()));
+      if id == 0 {
+        attrs.push_str(", style=bold");
+      }
+      if normal.contains(&id) {
+        attrs.push_str(", peripheries=2");
+      }
+      writeln!(dot, "  t{} [{}];", id, attrs).expect("writing to a string");
+    }
+    for step in &self.steps {
+      writeln!(
+        dot,
+        "  t{} -> t{} [label={}{}];",
+        step.from,
+        step.to,
+        quoted(&step.redex.to_string()),
+        if step.revisit { ", style=dashed" } else { "" }
+      )
+      .expect("writing to a string");
+    }
+    dot.push('}');
+    dot
+  }
+}
+
+/// `text` as a DOT string
+fn quoted(text: &str) -> String {
+  let mut quoted = String::with_capacity(text.len() + 2);
+  quoted.push('"');
+  for c in text.chars() {
+    if c == '"' || c == '\\' {
+      quoted.push('\\');
+    }
+    quoted.push(c);
+  }
+  quoted.push('"');
+  quoted
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", 10, &[], false)]
+  #[case("(λx.f x) ((λy.y) z)", 10, &["0 -> 1 at root", "0 -> 2 at rhs", "1 -> 3 at rhs", "2 -> 3 at root"], false)]
+  #[case("(λx.x x) (λx.x x)", 10, &["0 -> 0 at root"], false)]
+  #[case("(λx.f x) ((λy.y) z)", 2, &["0 -> 1 at root"], true)]
+  fn explores_reductions(
+    #[case] input: &str,
+    #[case] max_terms: usize,
+    #[case] expected: &[&str],
+    #[case] truncated: bool,
+  ) -> Result<(), anyhow::Error> {
+    let graph = explore(Rc::new(Parser::new(input).parse()?), max_terms);
+    let steps: Vec<_> = graph
+      .steps
+      .iter()
+      .map(|step| format!("{} -> {} at {}", step.from, step.to, step.redex))
+      .collect();
+    assert_eq!(steps, expected);
+    assert_eq!(graph.truncated, truncated);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(0, &[], true)]
+  #[case(1, &["0 -> 1 at root", "0 -> 2 at rhs"], true)]
+  #[case(2, &["0 -> 1 at root", "0 -> 2 at rhs", "1 -> 3 at rhs", "2 -> 3 at root"], false)]
+  fn explores_to_a_depth(
+    #[case] max_depth: usize,
+    #[case] expected: &[&str],
+    #[case] truncated: bool,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.f x) ((λy.y) z)").parse()?);
+    let graph = explore_within(term, 10, max_depth, &CancelToken::new())?;
+    let steps: Vec<_> = graph
+      .steps
+      .iter()
+      .map(|step| format!("{} -> {} at {}", step.from, step.to, step.redex))
+      .collect();
+    assert_eq!(steps, expected);
+    assert_eq!(graph.truncated, truncated);
+    Ok(())
+  }
+
+  #[test]
+  fn cancels() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let result = explore_with_cancel(term, 10, &cancel);
+    assert_eq!(result.map(|graph| graph.steps), Err(EvalError::Cancelled));
+    Ok(())
+  }
+
+  #[test]
+  fn writes_dot() -> Result<(), anyhow::Error> {
+    let graph = explore(Rc::new(Parser::new("(λx.x) y").parse()?), 10);
+    assert_eq!(
+      graph.to_dot(),
+      "digraph reductions {\n  node [shape=box];\n  t0 [label=\"(λx. x) y\", style=bold];\n  t1 [label=\"y\", peripheries=2];\n  t0 -> t1 [label=\"root\"];\n}"
+    );
+    assert_eq!(quoted(r#"a"b\c"#), r#""a\"b\\c""#);
+    let graph = explore(Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?), 10);
+    assert!(graph
+      .to_dot()
+      .contains("t0 -> t0 [label=\"root\", style=dashed];"));
+    Ok(())
+  }
+
+  #[test]
+  fn truncated_terms_are_not_normal() -> Result<(), anyhow::Error> {
+    let graph = explore(Rc::new(Parser::new("(λx.f x) ((λy.y) z)").parse()?), 2);
+    assert!(graph.truncated);
+    assert_eq!(graph.normal_forms().count(), 0);
+    assert_eq!(
+      graph.to_dot(),
+      "digraph reductions {\n  node [shape=box];\n  t0 [label=\"(λx. f x) ((λy. y) z)\", style=bold];\n  t1 [label=\"f ((λy. y) z)\"];\n  t0 -> t1 [label=\"root\"];\n}"
+    );
+    Ok(())
+  }
+}
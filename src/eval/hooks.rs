@@ -0,0 +1,163 @@
+//! Callbacks fired as evaluation goes along, for progress bars and integrations
+//!
+//! A [`Hooks`] holds the callbacks registered for each kind of [`Event`]. Hand it to
+//! an evaluator with [`Evaluator::with_hooks`](super::Evaluator::with_hooks), or to
+//! an [`Engine`](crate::Engine), which also reports the definitions it expands.
+//!
+//! ```
+//! # use std::cell::Cell;
+//! # use std::rc::Rc;
+//! # use camel::eval::hooks::{Event, Hooks};
+//! # use camel::{Evaluator, Parser};
+//! let fired = Cell::new(0);
+//! let mut hooks = Hooks::new();
+//! hooks.on_steps(2, |_| fired.set(fired.get() + 1));
+//! let term = Parser::new("(λf.λx.f (f (f x))) (λy.y) z").parse()?;
+//! let normal = Evaluator::default().with_hooks(&hooks).eval(Rc::new(term))?;
+//! assert_eq!((normal.to_string(), fired.get()), ("z".to_string(), 2));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fmt;
+
+use crate::ast::Node;
+
+/// Something that happened during evaluation
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum Event<'e> {
+  /// A definition was substituted into a term about to be evaluated
+  DefinitionExpanded { name: &'e str },
+  /// The count of beta reductions reached a multiple of the interval of the hook,
+  /// with the current term where the evaluator has it at hand
+  Steps {
+    steps: usize,
+    term: Option<&'e Node<'e>>,
+  },
+  /// Evaluation finished with `term`
+  NormalForm { term: &'e Node<'e>, steps: usize },
+}
+
+type Callback<'h> = Box<dyn Fn(&Event<'_>) + 'h>;
+
+/// The callbacks for each kind of event, fired in the order registered
+#[derive(Default)]
+pub struct Hooks<'h> {
+  definition: Vec<Callback<'h>>,
+  steps: Vec<(usize, Callback<'h>)>,
+  normal_form: Vec<Callback<'h>>,
+}
+
+impl<'h> Hooks<'h> {
+  pub fn new() -> Self {
+    Hooks::default()
+  }
+
+  /// Call `hook` on `Event::DefinitionExpanded`
+  pub fn on_definition(&mut self, hook: impl Fn(&Event<'_>) + 'h) -> &mut Self {
+    self.definition.push(Box::new(hook));
+    self
+  }
+
+  /// Call `hook` on `Event::Steps` every `every` beta reductions
+  ///
+  /// # Panics
+  ///
+  /// If `every` is zero
+  pub fn on_steps(&mut self, every: usize, hook: impl Fn(&Event<'_>) + 'h) -> &mut Self {
+    assert!(every > 0, "a step hook needs a nonzero interval");
+    self.steps.push((every, Box::new(hook)));
+    self
+  }
+
+  /// Call `hook` on `Event::NormalForm`
+  pub fn on_normal_form(&mut self, hook: impl Fn(&Event<'_>) + 'h) -> &mut Self {
+    self.normal_form.push(Box::new(hook));
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.definition.is_empty() && self.steps.is_empty() && self.normal_form.is_empty()
+  }
+
+  /// Report that the definition `name` was expanded
+  pub fn definition_expanded(&self, name: &str) {
+    let event = Event::DefinitionExpanded { name };
+    self.definition.iter().for_each(|hook| hook(&event));
+  }
+
+  /// Report that `steps` beta reductions have been taken, firing the hooks whose
+  /// interval divides it
+  pub fn stepped(&self, steps: usize, term: Option<&Node<'_>>) {
+    let event = Event::Steps { steps, term };
+    for (every, hook) in &self.steps {
+      if steps.is_multiple_of(*every) {
+        hook(&event);
+      }
+    }
+  }
+
+  /// Report that evaluation reached `term` after `steps` beta reductions
+  pub fn normal_form(&self, term: &Node<'_>, steps: usize) {
+    let event = Event::NormalForm { term, steps };
+    self.normal_form.iter().for_each(|hook| hook(&event));
+  }
+}
+
+impl fmt::Debug for Hooks<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Hooks")
+      .field("definition", &self.definition.len())
+      .field("steps", &self.steps.len())
+      .field("normal_form", &self.normal_form.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use std::cell::RefCell;
+
+  #[test]
+  fn fires_hooks_for_their_events() -> Result<(), anyhow::Error> {
+    let log = RefCell::new(Vec::new());
+    let mut hooks = Hooks::new();
+    hooks
+      .on_definition(|event| log.borrow_mut().push(format!("{:?}", event)))
+      .on_steps(3, |event| {
+        if let Event::Steps { steps, .. } = event {
+          log.borrow_mut().push(format!("every 3: {}", steps));
+        }
+      })
+      .on_steps(2, |event| {
+        if let Event::Steps { steps, .. } = event {
+          log.borrow_mut().push(format!("every 2: {}", steps));
+        }
+      })
+      .on_normal_form(|event| {
+        if let Event::NormalForm { term, steps } = event {
+          log.borrow_mut().push(format!("{} after {}", term, steps));
+        }
+      });
+    let term = Parser::new("x").parse()?;
+    hooks.definition_expanded("id");
+    (1..=6).for_each(|steps| hooks.stepped(steps, None));
+    hooks.normal_form(&term, 6);
+    assert_eq!(
+      *log.borrow(),
+      [
+        "DefinitionExpanded { name: \"id\" }",
+        "every 2: 2",
+        "every 3: 3",
+        "every 2: 4",
+        "every 3: 6",
+        "every 2: 6",
+        "x after 6",
+      ]
+    );
+    assert!(!hooks.is_empty() && Hooks::new().is_empty());
+    Ok(())
+  }
+}
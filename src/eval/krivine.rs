@@ -0,0 +1,161 @@
+//! Call by name evaluation on a Krivine machine
+//!
+//! The machine keeps the term under evaluation together with an environment of
+//! unevaluated arguments, and a stack of arguments waiting for an abstraction to
+//! consume them. Beta reduction only extends an environment, so bodies are never
+//! copied while evaluating.
+
+use std::rc::Rc;
+
+use crate::ast::{Identifier, Node};
+
+use super::env::Env;
+use super::{rebuild_spine, substitute_all, EvalError};
+
+/// A term paired with the environment it is to be evaluated in
+#[derive(Debug, Clone)]
+struct Thunk<'inp> {
+  term: Rc<Node<'inp>>,
+  env: Env<'inp, Thunk<'inp>>,
+}
+
+impl Drop for Thunk<'_> {
+  /// Free nested environments with an explicit stack, as the derived recursive drop
+  /// would overflow the Rust stack on long chains of thunks
+  fn drop(&mut self) {
+    let mut thunks = Vec::new();
+    self.env.take_unshared_values(&mut thunks);
+    while let Some(thunk) = thunks.pop() {
+      if let Ok(mut thunk) = Rc::try_unwrap(thunk) {
+        thunk.env.take_unshared_values(&mut thunks);
+      }
+    }
+  }
+}
+
+impl<'inp> Thunk<'inp> {
+  /// The term with the read back terms of its environment substituted in
+  fn readback(&self) -> Rc<Node<'inp>> {
+    let mut stack: Vec<ReadbackFrame> = Vec::new();
+    let mut thunk = self;
+    loop {
+      let mut pending: Vec<_> = thunk
+        .term
+        .free_vars()
+        .into_iter()
+        .filter_map(|name| Some((name, &**thunk.env.lookup(name)?)))
+        .collect();
+      let mut done = Vec::new();
+      // close every thunk whose bindings are all read back, resuming its parent
+      loop {
+        if let Some((name, next)) = pending.pop() {
+          stack.push(ReadbackFrame {
+            thunk,
+            name,
+            pending,
+            done,
+          });
+          thunk = next;
+          break;
+        }
+        let term = substitute_all(&thunk.term, &done);
+        let Some(parent) = stack.pop() else {
+          return term;
+        };
+        thunk = parent.thunk;
+        pending = parent.pending;
+        done = parent.done;
+        done.push((parent.name, term));
+      }
+    }
+  }
+}
+
+/// A thunk waiting on the read back terms of the bindings it uses
+struct ReadbackFrame<'t, 'inp> {
+  thunk: &'t Thunk<'inp>,
+  /// The binding currently being read back
+  name: &'t str,
+  pending: Vec<(&'t str, &'t Thunk<'inp>)>,
+  done: Vec<(&'t str, Rc<Node<'inp>>)>,
+}
+
+/// Reduce a term to weak head normal form, calling `safepoint` before every beta
+/// reduction
+pub(super) fn whnf<'inp>(
+  node: Rc<Node<'inp>>,
+  mut safepoint: impl FnMut() -> Result<(), EvalError>,
+) -> Result<Rc<Node<'inp>>, EvalError> {
+  let mut current = Thunk {
+    term: node,
+    env: Env::new(),
+  };
+  let mut args: Vec<Rc<Thunk>> = Vec::new();
+  loop {
+    let term = Rc::clone(&current.term);
+    match &*term {
+      Node::Application(app) => {
+        args.push(Rc::new(Thunk {
+          term: Rc::clone(&app.rhs),
+          env: current.env.clone(),
+        }));
+        current.term = Rc::clone(&app.lhs);
+      }
+      Node::Abstraction(abs) => {
+        let Some(arg) = args.pop() else {
+          return Ok(current.readback());
+        };
+        safepoint()?;
+        current.env = current.env.bind(abs.param.clone(), arg);
+        current.term = Rc::clone(&abs.body);
+      }
+      Node::Identifier(id) => match current.env.lookup(&id.name) {
+        Some(thunk) => current = Thunk::clone(thunk),
+        None => {
+          let head = Rc::new(Node::Identifier(Identifier {
+            name: id.name.clone(),
+          }));
+          let args = args.iter().map(|arg| arg.readback()).collect();
+          return Ok(rebuild_spine(head, args));
+        }
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::eval::{eval_with_strategy, EvalStrategy, Evaluator};
+  use crate::parser::Parser;
+  use rstest::rstest;
+  use std::rc::Rc;
+
+  #[rstest]
+  #[case("x")]
+  #[case("λx.(λy.y) x")]
+  #[case("(λx.λy.y) ((λx.x x) (λx.x x))")]
+  #[case("(λx.λy.x) ((λz.z) a)")]
+  #[case("(λx.λy.x y) y")]
+  #[case("(λx.λy.λz.x y z) y z")]
+  #[case("x ((λy.y) z) ((λy.y) w)")]
+  #[case("(λf.λx.f (f x)) (λy.y) z")]
+  #[case("(λn.λf.λx.f (n f x)) (λf.λx.f x) g")]
+  fn agrees_with_call_by_name(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(
+      eval_with_strategy(Rc::clone(&term), EvalStrategy::Krivine),
+      eval_with_strategy(term, EvalStrategy::CallByName)
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn counts_steps() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let result = Evaluator::new(EvalStrategy::Krivine)
+      .with_fuel(100)
+      .eval(term);
+    assert_eq!(result, Err(crate::eval::EvalError::StepLimitExceeded));
+    Ok(())
+  }
+}
@@ -0,0 +1,277 @@
+//! Call by value evaluation by compiling to an SECD machine
+//!
+//! A term is compiled into blocks of instructions, one for the term itself and
+//! one for the body of each abstraction, which are run on a machine with a stack
+//! of values, an environment, the code being run, and a dump of the code and
+//! environments to return to.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::eval::secd;
+//! # use camel::parser::Parser;
+//! let term = Rc::new(Parser::new("(λx.x) y").parse()?);
+//! let program = secd::compile(&term);
+//! assert_eq!(
+//!   program.to_string(),
+//!   "0:\n  closure λx 1\n  access y\n  apply\n  return\n1:\n  access x\n  return\n"
+//! );
+//! assert_eq!(program.run().to_string(), "y");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Node;
+
+use super::env::{Closure, Env, Value};
+use super::EvalError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Instruction<'inp> {
+  /// Push the value bound to a variable, or the variable itself if it is free
+  Access(Cow<'inp, str>),
+  /// Push a closure over the current environment, binding `param` in the code of
+  /// `block`
+  Closure { param: Cow<'inp, str>, block: usize },
+  /// Pop an argument and then a function, and apply the function
+  Apply,
+  /// Return to the code and environment on top of the dump
+  Return,
+}
+
+impl fmt::Display for Instruction<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Instruction::Access(name) => write!(f, "access {}", name),
+      Instruction::Closure { param, block } => write!(f, "closure λ{} {}", param, block),
+      Instruction::Apply => write!(f, "apply"),
+      Instruction::Return => write!(f, "return"),
+    }
+  }
+}
+
+/// The code of the whole term, or of the body of an abstraction
+#[derive(Debug, Clone)]
+pub struct Block<'inp> {
+  pub code: Vec<Instruction<'inp>>,
+  /// The term this block was compiled from
+  source: Rc<Node<'inp>>,
+}
+
+/// A compiled term, whose first block is the entry point
+#[derive(Debug, Clone)]
+pub struct Program<'inp> {
+  blocks: Vec<Block<'inp>>,
+  /// The block compiled from each abstraction body, by address, so that a closure
+  /// can find its code again
+  entries: HashMap<*const Node<'inp>, usize>,
+}
+
+/// Compile a term into a program
+///
+/// Variables are accessed by name, so the code of a body does not depend on where
+/// it appears, and bodies shared between abstractions are compiled only once
+pub fn compile<'inp>(node: &Rc<Node<'inp>>) -> Program<'inp> {
+  let mut program = Program {
+    blocks: vec![Block {
+      code: Vec::new(),
+      source: Rc::clone(node),
+    }],
+    entries: HashMap::new(),
+  };
+  let mut next = 0;
+  while next < program.blocks.len() {
+    let mut code = Vec::new();
+    let mut work = vec![Compile::Term(Rc::clone(&program.blocks[next].source))];
+    while let Some(item) = work.pop() {
+      match item {
+        Compile::Emit(instruction) => code.push(instruction),
+        Compile::Term(term) => match &*term {
+          Node::Identifier(id) => code.push(Instruction::Access(id.name.clone())),
+          Node::Abstraction(abs) => {
+            code.push(Instruction::Closure {
+              param: abs.param.clone(),
+              block: program.entry(&abs.body),
+            });
+          }
+          Node::Application(app) => {
+            work.push(Compile::Emit(Instruction::Apply));
+            work.push(Compile::Term(Rc::clone(&app.rhs)));
+            work.push(Compile::Term(Rc::clone(&app.lhs)));
+          }
+        },
+      }
+    }
+    code.push(Instruction::Return);
+    program.blocks[next].code = code;
+    next += 1;
+  }
+  program
+}
+
+/// Work left while compiling a block
+enum Compile<'inp> {
+  Term(Rc<Node<'inp>>),
+  Emit(Instruction<'inp>),
+}
+
+impl<'inp> Program<'inp> {
+  pub fn blocks(&self) -> &[Block<'inp>] {
+    &self.blocks
+  }
+
+  /// The block for an abstraction body, adding an empty one to be compiled if the
+  /// body has not been seen yet
+  fn entry(&mut self, body: &Rc<Node<'inp>>) -> usize {
+    *self.entries.entry(Rc::as_ptr(body)).or_insert_with(|| {
+      self.blocks.push(Block {
+        code: Vec::new(),
+        source: Rc::clone(body),
+      });
+      self.blocks.len() - 1
+    })
+  }
+
+  /// Run the program to a value
+  pub fn run(&self) -> Rc<Value<'inp>> {
+    self
+      .run_with(|| Ok(()))
+      .expect("evaluation without limits cannot fail")
+  }
+
+  /// Run the program, calling `safepoint` before every beta reduction
+  pub(super) fn run_with(
+    &self,
+    mut safepoint: impl FnMut() -> Result<(), EvalError>,
+  ) -> Result<Rc<Value<'inp>>, EvalError> {
+    let mut stack: Vec<Rc<Value<'inp>>> = Vec::new();
+    let mut env = Env::new();
+    let mut code = &self.blocks[0].code[..];
+    let mut dump = Vec::new();
+    loop {
+      let (instruction, rest) = code.split_first().expect("every block ends in a return");
+      code = rest;
+      match instruction {
+        Instruction::Access(name) => stack.push(match env.lookup(name) {
+          Some(value) => Rc::clone(value),
+          None => Rc::new(Value::Variable(name.clone())),
+        }),
+        Instruction::Closure { param, block } => {
+          stack.push(Rc::new(Value::Closure(Closure {
+            param: param.clone(),
+            body: Rc::clone(&self.blocks[*block].source),
+            env: env.clone(),
+          })));
+        }
+        Instruction::Apply => {
+          let arg = stack.pop().expect("an argument on the stack");
+          let function = stack.pop().expect("a function on the stack");
+          match &*function {
+            Value::Closure(closure) => {
+              safepoint()?;
+              let block = self.entries[&Rc::as_ptr(&closure.body)];
+              let callee = closure.env.bind(closure.param.clone(), arg);
+              dump.push((code, std::mem::replace(&mut env, callee)));
+              code = &self.blocks[block].code;
+            }
+            _ => stack.push(Rc::new(Value::Application(function, arg))),
+          }
+        }
+        Instruction::Return => match dump.pop() {
+          Some((caller, caller_env)) => {
+            code = caller;
+            env = caller_env;
+          }
+          None => return Ok(stack.pop().expect("a result on the stack")),
+        },
+      }
+    }
+  }
+}
+
+impl fmt::Display for Program<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (index, block) in self.blocks.iter().enumerate() {
+      writeln!(f, "{}:", index)?;
+      for instruction in &block.code {
+        writeln!(f, "  {}", instruction)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::eval::{eval_with_strategy, EvalStrategy, Evaluator};
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x")]
+  #[case("λx.x")]
+  #[case("(λx.λy.x) a b")]
+  #[case("(λx.λy.x) ((λz.z) a)")]
+  #[case("x ((λy.y) z)")]
+  #[case("(λx.λy.x y) y")]
+  #[case("(λf.f (λx.(λy.y) x)) (λg.g)")]
+  #[case("(λf.λx.f (f (f x))) (λy.y) z")]
+  fn agrees_with_call_by_value(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    assert_eq!(
+      eval_with_strategy(Rc::clone(&term), EvalStrategy::Secd),
+      eval_with_strategy(term, EvalStrategy::CallByValue)
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn shared_bodies_compile_once() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("λx.λy.y").parse()?);
+    let Node::Abstraction(abs) = &*term else {
+      unreachable!("parsed an abstraction");
+    };
+    let twice = Rc::new(Node::Application(crate::ast::Application {
+      lhs: Rc::clone(&term),
+      rhs: Rc::clone(&abs.body),
+    }));
+    assert_eq!(compile(&twice).blocks().len(), 3);
+    Ok(())
+  }
+
+  #[test]
+  fn shared_bodies_keep_their_parameters() {
+    let var = |name| {
+      Rc::new(Node::Identifier(crate::ast::Identifier {
+        name: Cow::Borrowed(name),
+      }))
+    };
+    let app = |lhs, rhs| Rc::new(Node::Application(crate::ast::Application { lhs, rhs }));
+    let abs = |param, body| {
+      Rc::new(Node::Abstraction(crate::ast::Abstraction {
+        param: Cow::Borrowed(param),
+        body,
+      }))
+    };
+    let body = var("x");
+    let term = app(
+      app(var("f"), app(abs("a", Rc::clone(&body)), var("w"))),
+      app(abs("x", body), var("q")),
+    );
+    assert_eq!(compile(&term).blocks().len(), 2);
+    assert_eq!(compile(&term).run().to_string(), "f x q");
+  }
+
+  #[test]
+  fn counts_steps() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x x) (λx.x x)").parse()?);
+    let result = Evaluator::new(EvalStrategy::Secd).with_fuel(100).eval(term);
+    assert_eq!(result, Err(EvalError::StepLimitExceeded));
+    Ok(())
+  }
+}
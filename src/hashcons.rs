@@ -0,0 +1,151 @@
+//! A store keeping one copy of each distinct term, so that equal subterms share
+//! their nodes
+//!
+//! [`HashCons::intern`] rebuilds a term bottom up out of nodes already in the
+//! store, adding only those it has not seen. Two terms interned into the same store
+//! are equal exactly when they are the same `Rc`, and a subterm repeated across
+//! many definitions is kept in memory once.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::hashcons::HashCons;
+//! # use camel::parser::Parser;
+//! let mut store = HashCons::new();
+//! let a = store.intern(&Parser::new("f (λx.x)").parse()?);
+//! let b = store.intern(&Parser::new("g (λx.x)").parse()?);
+//! assert_eq!(store.len(), 6);
+//! assert!(Rc::ptr_eq(&a, &store.intern(&Parser::new("f (λx.x)").parse()?)));
+//! # let _ = b;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+/// What identifies a node once its children are in the store: its kind, its name,
+/// and the addresses of its children
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+  Abstraction(String, *const Node<'static>),
+  Application(*const Node<'static>, *const Node<'static>),
+  Identifier(String),
+}
+
+/// Every distinct term interned so far, each held once
+///
+/// The store keeps each node alive, so the addresses in its keys stay valid and
+/// are never reused while it holds them
+#[derive(Debug, Default)]
+pub struct HashCons {
+  nodes: HashMap<Key, Rc<Node<'static>>>,
+}
+
+impl HashCons {
+  pub fn new() -> Self {
+    HashCons::default()
+  }
+
+  /// The copy of `term` held by the store, adding whichever of its subterms are
+  /// not there yet
+  pub fn intern(&mut self, term: &Node<'_>) -> Rc<Node<'static>> {
+    enum Visit<'n, 'inp> {
+      Enter(&'n Node<'inp>),
+      Exit(&'n Node<'inp>),
+    }
+
+    let mut built: Vec<Rc<Node<'static>>> = Vec::new();
+    let mut stack = vec![Visit::Enter(term)];
+    while let Some(visit) = stack.pop() {
+      let (key, node) = match visit {
+        Visit::Enter(node @ Node::Abstraction(abs)) => {
+          stack.push(Visit::Exit(node));
+          stack.push(Visit::Enter(&abs.body));
+          continue;
+        }
+        Visit::Enter(node @ Node::Application(app)) => {
+          stack.push(Visit::Exit(node));
+          stack.push(Visit::Enter(&app.rhs));
+          stack.push(Visit::Enter(&app.lhs));
+          continue;
+        }
+        Visit::Enter(Node::Identifier(id)) => {
+          let key = Key::Identifier(id.name.to_string());
+          let node = Node::Identifier(Identifier {
+            name: Cow::Owned(id.name.to_string()),
+          });
+          (key, node)
+        }
+        Visit::Exit(Node::Abstraction(abs)) => {
+          let body = built.pop().expect("the body is built first");
+          let key = Key::Abstraction(abs.param.to_string(), Rc::as_ptr(&body));
+          let node = Node::Abstraction(Abstraction {
+            param: Cow::Owned(abs.param.to_string()),
+            body,
+          });
+          (key, node)
+        }
+        Visit::Exit(Node::Application(..)) => {
+          let rhs = built.pop().expect("the right side is built first");
+          let lhs = built.pop().expect("the left side is built first");
+          let key = Key::Application(Rc::as_ptr(&lhs), Rc::as_ptr(&rhs));
+          (key, Node::Application(Application { lhs, rhs }))
+        }
+        Visit::Exit(Node::Identifier(..)) => unreachable!("identifiers are built on entry"),
+      };
+      let shared = self.nodes.entry(key).or_insert_with(|| Rc::new(node));
+      built.push(Rc::clone(shared));
+    }
+    built.pop().expect("the root is built last")
+  }
+
+  /// Number of distinct nodes held
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Let go of every node, which stay alive only where they are still used
+  pub fn clear(&mut self) {
+    self.nodes.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(&["x"], 1)]
+  #[case(&["x x"], 2)]
+  #[case(&["λx.x", "λy.y"], 4)]
+  #[case(&["f (λx.x)", "g (λx.x)"], 6)]
+  #[case(&["(λx.x) (λx.x)", "λx.x"], 3)]
+  fn keeps_one_copy(#[case] terms: &[&str], #[case] nodes: usize) -> Result<(), anyhow::Error> {
+    let mut store = HashCons::new();
+    for term in terms {
+      let term = Parser::new(term).parse()?;
+      assert_eq!(store.intern(&term).to_string(), term.to_string());
+    }
+    assert_eq!(store.len(), nodes);
+    Ok(())
+  }
+
+  #[test]
+  fn shares_repeated_subterms() -> Result<(), anyhow::Error> {
+    let mut store = HashCons::new();
+    let term = store.intern(&Parser::new("(λx.x) (λx.x)").parse()?);
+    let Node::Application(app) = &*term else {
+      panic!("an application");
+    };
+    assert!(Rc::ptr_eq(&app.lhs, &app.rhs));
+    Ok(())
+  }
+}
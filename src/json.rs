@@ -0,0 +1,416 @@
+//! Reading and writing terms as JSON, for tools which don't speak lambda syntax
+//!
+//! Every term is an object with a single key naming its kind:
+//!
+//! | term    | JSON                                      |
+//! |---------|-------------------------------------------|
+//! | `λx. M` | `{"abs": {"param": "x", "body": M}}`      |
+//! | `M N`   | `{"app": {"fun": M, "arg": N}}`           |
+//! | `x`     | `{"var": "x"}`                            |
+//!
+//! Keys inside `abs` and `app` may come in any order, and names are kept exactly
+//! as written, including ones the parser would not accept. The schema is stable:
+//! any change to it will keep reading what earlier versions wrote. Reading and
+//! writing keep their own stacks, so terms of any depth can be exchanged.
+//!
+//! ```
+//! # use camel::json;
+//! # use camel::parser::Parser;
+//! let term = Parser::new("λx.f x").parse()?;
+//! let text = json::to_json(&term);
+//! assert_eq!(
+//!   text,
+//!   r#"{"abs":{"param":"x","body":{"app":{"fun":{"var":"f"},"arg":{"var":"x"}}}}}"#
+//! );
+//! assert_eq!(*json::from_json(&text)?, term);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonError {
+  #[error("Unexpected character '{found}' at {offset}, expected {expected}")]
+  Unexpected {
+    found: char,
+    offset: usize,
+    expected: &'static str,
+  },
+
+  #[error("Unexpected end of input, expected {0}")]
+  UnexpectedEnd(&'static str),
+
+  #[error("Unknown key \"{key}\" at {offset}")]
+  UnknownKey { key: String, offset: usize },
+
+  #[error("Key \"{key}\" given twice at {offset}")]
+  DuplicateKey { key: &'static str, offset: usize },
+
+  #[error("Object at {offset} is missing \"{key}\"")]
+  MissingKey { key: &'static str, offset: usize },
+
+  #[error("Invalid escape in string at {0}")]
+  InvalidEscape(usize),
+
+  #[error("Trailing input at {0}")]
+  TrailingInput(usize),
+}
+
+/// Write a term as JSON, on a single line and without spaces
+pub fn to_json(node: &Node<'_>) -> String {
+  enum Visit<'n> {
+    Enter(&'n Node<'n>),
+    Text(&'static str),
+  }
+
+  let mut json = String::new();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        json.push_str(r#"{"abs":{"param":"#);
+        quote(&abs.param, &mut json);
+        json.push_str(r#","body":"#);
+        stack.push(Visit::Text("}}"));
+        stack.push(Visit::Enter(&abs.body));
+      }
+      Visit::Enter(Node::Application(app)) => {
+        json.push_str(r#"{"app":{"fun":"#);
+        stack.push(Visit::Text("}}"));
+        stack.push(Visit::Enter(&app.rhs));
+        stack.push(Visit::Text(r#","arg":"#));
+        stack.push(Visit::Enter(&app.lhs));
+      }
+      Visit::Enter(Node::Identifier(id)) => {
+        json.push_str(r#"{"var":"#);
+        quote(&id.name, &mut json);
+        json.push('}');
+      }
+      Visit::Text(text) => json.push_str(text),
+    }
+  }
+  json
+}
+
+/// `text` as a JSON string, with quotes around it
+pub fn quoted(text: &str) -> String {
+  let mut json = String::with_capacity(text.len() + 2);
+  quote(text, &mut json);
+  json
+}
+
+/// Write `text` as a JSON string
+fn quote(text: &str, json: &mut String) {
+  json.push('"');
+  for c in text.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      c if c.is_control() => {
+        let _ = write!(json, "\\u{:04x}", c as u32);
+      }
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+}
+
+/// Read a term written in the schema of this module
+pub fn from_json(text: &str) -> Result<Rc<Node<'static>>, JsonError> {
+  /// An `abs` or `app` object whose keys are still being read
+  struct Frame {
+    kind: Kind,
+    offset: usize,
+    param: Option<String>,
+    /// The body, or the function and the argument
+    children: [Option<Rc<Node<'static>>>; 2],
+    /// Which of the children the term being read belongs in
+    awaiting: usize,
+    /// Whether any key has been read yet
+    started: bool,
+  }
+
+  #[derive(Clone, Copy)]
+  enum Kind {
+    Abs,
+    App,
+  }
+
+  let mut reader = Reader { text, offset: 0 };
+  let mut stack: Vec<Frame> = Vec::new();
+  loop {
+    // a term starts here
+    reader.expect(b'{', "'{'")?;
+    reader.skip_whitespace();
+    let offset = reader.offset;
+    let key = reader.key()?;
+    let mut done = match key.as_str() {
+      "var" => {
+        let name = reader.string()?;
+        reader.expect(b'}', "'}'")?;
+        Some(Rc::new(Node::Identifier(Identifier {
+          name: Cow::Owned(name),
+        })))
+      }
+      "abs" | "app" => {
+        reader.expect(b'{', "'{'")?;
+        stack.push(Frame {
+          kind: if key == "abs" { Kind::Abs } else { Kind::App },
+          offset: reader.offset - 1,
+          param: None,
+          children: [None, None],
+          awaiting: 0,
+          started: false,
+        });
+        None
+      }
+      _ => return Err(JsonError::UnknownKey { key, offset }),
+    };
+    // read keys of the innermost object until one holds a term
+    loop {
+      let Some(frame) = stack.last_mut() else {
+        reader.end()?;
+        return Ok(done.expect("a term is complete once no object is open"));
+      };
+      if let Some(node) = done.take() {
+        frame.children[frame.awaiting] = Some(node);
+      }
+      let closed = if frame.started {
+        reader.peek() == Some(b'}') || {
+          reader.expect(b',', "',' or '}'")?;
+          false
+        }
+      } else {
+        frame.started = true;
+        reader.peek() == Some(b'}')
+      };
+      if closed {
+        reader.expect(b'}', "'}'")?;
+        reader.expect(b'}', "'}'")?;
+        let frame = stack.pop().expect("looked at above");
+        let missing = |key| JsonError::MissingKey {
+          key,
+          offset: frame.offset,
+        };
+        let [first, second] = frame.children;
+        let node = match frame.kind {
+          Kind::Abs => Node::Abstraction(Abstraction {
+            param: Cow::Owned(frame.param.ok_or_else(|| missing("param"))?),
+            body: first.ok_or_else(|| missing("body"))?,
+          }),
+          Kind::App => Node::Application(Application {
+            lhs: first.ok_or_else(|| missing("fun"))?,
+            rhs: second.ok_or_else(|| missing("arg"))?,
+          }),
+        };
+        done = Some(Rc::new(node));
+        continue;
+      }
+      reader.skip_whitespace();
+      let offset = reader.offset;
+      let key = reader.key()?;
+      let (key, index) = match (frame.kind, key.as_str()) {
+        (Kind::Abs, "param") => {
+          if frame.param.is_some() {
+            return Err(JsonError::DuplicateKey {
+              key: "param",
+              offset,
+            });
+          }
+          frame.param = Some(reader.string()?);
+          continue;
+        }
+        (Kind::Abs, "body") => ("body", 0),
+        (Kind::App, "fun") => ("fun", 0),
+        (Kind::App, "arg") => ("arg", 1),
+        _ => return Err(JsonError::UnknownKey { key, offset }),
+      };
+      if frame.children[index].is_some() {
+        return Err(JsonError::DuplicateKey { key, offset });
+      }
+      frame.awaiting = index;
+      break;
+    }
+  }
+}
+
+/// The input being read, skipping whitespace before each token
+struct Reader<'t> {
+  text: &'t str,
+  offset: usize,
+}
+
+impl Reader<'_> {
+  fn skip_whitespace(&mut self) {
+    let rest = &self.text.as_bytes()[self.offset..];
+    self.offset += rest
+      .iter()
+      .take_while(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+      .count();
+  }
+
+  fn peek(&mut self) -> Option<u8> {
+    self.skip_whitespace();
+    self.text.as_bytes().get(self.offset).copied()
+  }
+
+  fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), JsonError> {
+    match self.peek() {
+      Some(found) if found == byte => {
+        self.offset += 1;
+        Ok(())
+      }
+      Some(..) => Err(self.unexpected(expected)),
+      None => Err(JsonError::UnexpectedEnd(expected)),
+    }
+  }
+
+  fn unexpected(&self, expected: &'static str) -> JsonError {
+    JsonError::Unexpected {
+      found: self.text[self.offset..].chars().next().unwrap_or_default(),
+      offset: self.offset,
+      expected,
+    }
+  }
+
+  /// A key and the colon after it
+  fn key(&mut self) -> Result<String, JsonError> {
+    let key = self.string()?;
+    self.expect(b':', "':'")?;
+    Ok(key)
+  }
+
+  fn string(&mut self) -> Result<String, JsonError> {
+    self.expect(b'"', "a string")?;
+    let mut string = String::new();
+    let mut chars = self.text[self.offset..].char_indices();
+    loop {
+      let Some((i, c)) = chars.next() else {
+        return Err(JsonError::UnexpectedEnd("'\"'"));
+      };
+      match c {
+        '"' => {
+          self.offset += i + 1;
+          return Ok(string);
+        }
+        '\\' => {
+          let escape = self.offset + i;
+          let c = match chars.next().map(|(_, c)| c) {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+              let invalid = JsonError::InvalidEscape(escape);
+              let high = hex4(chars.by_ref().map(|(_, c)| c)).ok_or(invalid.clone())?;
+              let code = if (0xd800..0xdc00).contains(&high) {
+                if chars.next().map(|(_, c)| c) != Some('\\')
+                  || chars.next().map(|(_, c)| c) != Some('u')
+                {
+                  return Err(invalid);
+                }
+                let low = hex4(chars.by_ref().map(|(_, c)| c)).ok_or(invalid.clone())?;
+                if !(0xdc00..0xe000).contains(&low) {
+                  return Err(invalid);
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+              } else {
+                high
+              };
+              char::from_u32(code).ok_or(invalid)?
+            }
+            _ => return Err(JsonError::InvalidEscape(escape)),
+          };
+          string.push(c);
+        }
+        '\u{0}'..='\u{1f}' => {
+          self.offset += i;
+          return Err(self.unexpected("a character allowed in a string"));
+        }
+        c => string.push(c),
+      }
+    }
+  }
+
+  /// Nothing but whitespace is left
+  fn end(&mut self) -> Result<(), JsonError> {
+    match self.peek() {
+      None => Ok(()),
+      Some(..) => Err(JsonError::TrailingInput(self.offset)),
+    }
+  }
+}
+
+/// Four hexadecimal digits, as in a `\u` escape
+fn hex4(mut chars: impl Iterator<Item = char>) -> Option<u32> {
+  (0..4).try_fold(0, |code, _| Some(code * 16 + chars.next()?.to_digit(16)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", r#"{"var":"x"}"#)]
+  #[case("f x", r#"{"app":{"fun":{"var":"f"},"arg":{"var":"x"}}}"#)]
+  #[case(
+    "λx.λy.x",
+    r#"{"abs":{"param":"x","body":{"abs":{"param":"y","body":{"var":"x"}}}}}"#
+  )]
+  fn writes_and_reads(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(to_json(&term), expected);
+    assert_eq!(*from_json(expected)?, term);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(
+    r#" { "app" : { "arg" : {"var": "x"}, "fun": {"var": "f"} } } "#,
+    "f x"
+  )]
+  #[case(r#"{"var":"λ\"\n"}"#, "λ\"\n")]
+  #[case(r#"{"var":"🐫"}"#, "🐫")]
+  #[case(r#"{"var":"\ud83d\udc2b\u00e9"}"#, "🐫é")]
+  fn reads_any_layout(#[case] json: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    assert_eq!(from_json(json)?.to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn deep_terms() -> Result<(), anyhow::Error> {
+    let source = format!("{}x", "λx.".repeat(100_000));
+    let term = Parser::new(&source).parse()?;
+    let json = to_json(&term);
+    let back = from_json(&json)?;
+    assert_eq!(to_json(&back), json);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("", JsonError::UnexpectedEnd("'{'"))]
+  #[case(r#"{"var":"x"} y"#, JsonError::TrailingInput(12))]
+  #[case(r#"{"lam":{}}"#, JsonError::UnknownKey { key: "lam".to_string(), offset: 1 })]
+  #[case(r#"{"abs":{"param":"x"}}"#, JsonError::MissingKey { key: "body", offset: 7 })]
+  #[case(r#"{"app":{"fun":{"var":"f"},"fun":{"var":"g"}}}"#, JsonError::DuplicateKey { key: "fun", offset: 26 })]
+  #[case(r#"{"var":"x",}"#, JsonError::Unexpected { found: ',', offset: 10, expected: "'}'" })]
+  #[case(r#"{"var":"\q"}"#, JsonError::InvalidEscape(8))]
+  #[case(r#"{"abs":{"param":"x" "body":{"var":"x"}}}"#, JsonError::Unexpected { found: '"', offset: 20, expected: "',' or '}'" })]
+  fn invalid_json(#[case] json: &str, #[case] expected: JsonError) {
+    assert_eq!(from_json(json), Err(expected));
+  }
+}
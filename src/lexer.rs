@@ -1,10 +1,12 @@
-use std::iter::Peekable;
-use std::str::Chars;
-
+use crate::source::Position;
 use crate::token::{Token, TokenKind};
 
+/// Splits the input into tokens
+///
+/// Scanning works on bytes, since every token but the lambda is ASCII, and only
+/// decodes a character when it meets a byte outside of ASCII
+#[derive(Clone)]
 pub struct Lexer<'inp> {
-  chars: Peekable<Chars<'inp>>,
   buffer: &'inp str,
   pos: usize,
   start: usize,
@@ -13,71 +15,136 @@ pub struct Lexer<'inp> {
 impl<'inp> Lexer<'inp> {
   pub fn new(input: &'inp str) -> Self {
     Lexer {
-      chars: input.chars().peekable(),
       buffer: input,
       pos: 0,
       start: 0,
     }
   }
 
-  fn advance(&mut self) -> Option<char> {
-    let next_char = self.chars.next()?;
-    self.pos += next_char.len_utf8();
-    Some(next_char)
+  /// Byte offset at which the most recently lexed token starts
+  pub fn offset(&self) -> usize {
+    self.start
   }
 
-  fn peek(&mut self) -> Option<&char> {
-    self.chars.peek()
+  /// The line and column of a byte offset into the input
+  ///
+  /// An offset inside a character counts as the start of that character, and one
+  /// past the end as the end of the input
+  pub fn position(&self, offset: usize) -> Position {
+    let before = Span::up_to(self.buffer, offset).text(self.buffer);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+      line: before.matches('\n').count() + 1,
+      column: before[line_start..].chars().count() + 1,
+    }
   }
 
   pub fn next_token(&mut self) -> Option<Token<'inp>> {
     self.skip_whitespace();
     self.start = self.pos;
-    if let Some(&c) = self.peek() {
-      let tok = match c {
-        '(' => self.create_token(TokenKind::LeftParen),
-        ')' => self.create_token(TokenKind::RightParen),
-        'λ' | '\\' => self.create_token(TokenKind::Lambda),
-        '.' => self.create_token(TokenKind::Dot),
-        'a'..='z' => self.read_lcid(), // could add additional logic inside here to return an Unknown
-        _ => self.create_token(TokenKind::Unknown),
-      };
-      Some(tok)
-    } else {
-      None
-    }
+    let &byte = self.buffer.as_bytes().get(self.pos)?;
+    let kind = match byte {
+      b'(' => TokenKind::LeftParen,
+      b')' => TokenKind::RightParen,
+      b'\\' => TokenKind::Lambda,
+      b'.' => TokenKind::Dot,
+      b'=' => TokenKind::Equals,
+      b'a'..=b'z' => return Some(self.read_lcid()),
+      _ if byte.is_ascii() => TokenKind::Unknown,
+      _ => return Some(self.read_char(self.rest().chars().next()?)),
+    };
+    self.advance(1);
+    Some(self.token(kind))
   }
 
-  fn create_token(&mut self, kind: TokenKind) -> Token<'inp> {
-    self.advance();
+  fn token(&self, kind: TokenKind) -> Token<'inp> {
     Token {
       kind,
-      text: &self.buffer[self.start..self.pos],
+      text: Span::new(self.buffer, self.start, self.pos).text(self.buffer),
+    }
+  }
+
+  /// Move past the next `len` bytes, which must end on a character boundary
+  fn advance(&mut self, len: usize) {
+    self.pos += len;
+    debug_assert!(
+      self.buffer.is_char_boundary(self.pos),
+      "lexer stopped inside a character at byte {}",
+      self.pos
+    );
+  }
+
+  /// The next character `c`, which is not ASCII, so either the lambda or unknown
+  fn read_char(&mut self, c: char) -> Token<'inp> {
+    self.advance(c.len_utf8());
+    match c {
+      'λ' => self.token(TokenKind::Lambda),
+      _ => self.token(TokenKind::Unknown),
     }
   }
 
   fn skip_whitespace(&mut self) {
-    while let Some(&c) = self.peek() {
-      if c.is_whitespace() {
-        self.advance();
-      } else {
-        break;
+    while let Some(&byte) = self.buffer.as_bytes().get(self.pos) {
+      match byte {
+        b' ' | b'\t'..=b'\r' => self.advance(1),
+        _ if byte.is_ascii() => break,
+        _ => match self.rest().chars().next() {
+          Some(c) if c.is_whitespace() => self.advance(c.len_utf8()),
+          _ => break,
+        },
       }
     }
   }
 
   fn read_lcid(&mut self) -> Token<'inp> {
-    while let Some(&c) = self.peek() {
-      if c.is_ascii_alphanumeric() {
-        self.advance();
-      } else {
-        break;
-      }
-    }
-    Token {
-      kind: TokenKind::LowercaseId,
-      text: &self.buffer[self.start..self.pos],
+    let len = self
+      .rest()
+      .bytes()
+      .position(|byte| !byte.is_ascii_alphanumeric())
+      .unwrap_or(self.buffer.len() - self.pos);
+    self.advance(len);
+    self.token(TokenKind::LowercaseId)
+  }
+
+  fn rest(&self) -> &'inp str {
+    Span::new(self.buffer, self.pos, self.buffer.len()).text(self.buffer)
+  }
+}
+
+/// A range of bytes of the input, which starts and ends on character boundaries
+///
+/// The lexer slices the input only through spans, so that a mistake in counting
+/// bytes past a multi-byte character is caught where the span is made rather than
+/// as a panic somewhere in slicing. Debug builds check every span as it is made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+  start: usize,
+  end: usize,
+}
+
+impl Span {
+  fn new(text: &str, start: usize, end: usize) -> Self {
+    debug_assert!(
+      start <= end && text.is_char_boundary(start) && text.is_char_boundary(end),
+      "span {}..{} does not lie on character boundaries of the input",
+      start,
+      end
+    );
+    Span { start, end }
+  }
+
+  /// From the start of `text` to `offset`, moved back to the start of the character
+  /// it falls in and kept within the text
+  fn up_to(text: &str, offset: usize) -> Self {
+    let mut end = offset.min(text.len());
+    while !text.is_char_boundary(end) {
+      end -= 1;
     }
+    Span::new(text, 0, end)
+  }
+
+  fn text(self, text: &str) -> &str {
+    &text[self.start..self.end]
   }
 }
 
@@ -92,10 +159,16 @@ mod tests {
   #[case("λ", Some(Token { kind: TokenKind::Lambda, text: "λ" }))]
   #[case("\\", Some(Token { kind: TokenKind::Lambda, text: "\\" }))]
   #[case(".", Some(Token { kind: TokenKind::Dot, text: "." }))]
+  #[case("=", Some(Token { kind: TokenKind::Equals, text: "=" }))]
   #[case("x", Some(Token { kind: TokenKind::LowercaseId, text: "x" }))]
   #[case("xyz", Some(Token { kind: TokenKind::LowercaseId, text: "xyz" }))]
   #[case("  (", Some(Token { kind: TokenKind::LeftParen, text: "(" }))]
+  #[case("\u{3000}\u{b}x", Some(Token { kind: TokenKind::LowercaseId, text: "x" }))]
+  #[case("é", Some(Token { kind: TokenKind::Unknown, text: "é" }))]
+  #[case("3", Some(Token { kind: TokenKind::Unknown, text: "3" }))]
+  #[case("x1y2 ", Some(Token { kind: TokenKind::LowercaseId, text: "x1y2" }))]
   #[case("", None)]
+  #[case(" \n\t", None)]
   fn next_token(#[case] input: &str, #[case] expected_token: Option<Token>) {
     let mut lexer = Lexer::new(input);
     let token = lexer.next_token();
@@ -117,6 +190,13 @@ mod tests {
     Token { kind: TokenKind::Dot, text: "." },
     Token { kind: TokenKind::LowercaseId, text: "x" }
   ])]
+  #[case("xλy\u{3000}éz", vec![
+    Token { kind: TokenKind::LowercaseId, text: "x" },
+    Token { kind: TokenKind::Lambda, text: "λ" },
+    Token { kind: TokenKind::LowercaseId, text: "y" },
+    Token { kind: TokenKind::Unknown, text: "é" },
+    Token { kind: TokenKind::LowercaseId, text: "z" }
+  ])]
   fn tokenize_all(#[case] input: &str, #[case] expected_tokens: Vec<Token>) {
     let mut lexer = Lexer::new(input);
     let mut tokens = Vec::new();
@@ -125,4 +205,15 @@ mod tests {
     }
     assert_eq!(tokens, expected_tokens);
   }
+
+  #[rstest]
+  #[case("x", 0, Position { line: 1, column: 1 })]
+  #[case("λx.x", 3, Position { line: 1, column: 3 })]
+  #[case("x\n  y", 4, Position { line: 2, column: 3 })]
+  #[case("x\n", 2, Position { line: 2, column: 1 })]
+  #[case("λλ", 3, Position { line: 1, column: 2 })]
+  #[case("λ", 9, Position { line: 1, column: 2 })]
+  fn position(#[case] input: &str, #[case] offset: usize, #[case] expected: Position) {
+    assert_eq!(Lexer::new(input).position(offset), expected);
+  }
 }
@@ -1,5 +1,122 @@
+//! A lambda calculus interpreter
+//!
+//! Source text is turned into a [`Node`] by the [`Parser`], and reduced to a normal
+//! form by [`eval`] or a configured [`Evaluator`]. Terms print back in lambda
+//! syntax through their `Display` implementation. For the common case, [`run`] does
+//! all of this at once:
+//!
+//! ```
+//! let normal = camel::run("(λx.λy.x) a b")?;
+//! assert_eq!(normal.to_string(), "a");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+#[macro_use]
+mod macros;
+
+pub mod arena;
 pub mod ast;
+#[cfg(feature = "formats")]
+pub mod binary;
+#[cfg(feature = "formats")]
+pub mod blc;
+pub mod capabilities;
+pub mod engine;
+pub mod eval;
+pub mod hashcons;
+#[cfg(feature = "formats")]
+pub mod json;
 pub mod lexer;
+pub mod list;
+pub mod names;
+pub mod numeral;
 pub mod parser;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod random;
+pub mod registry;
+#[cfg(feature = "formats")]
+pub mod sexpr;
+pub mod shrink;
+pub mod source;
+pub mod symbol;
+pub mod timing;
 pub mod token;
-pub mod eval;
\ No newline at end of file
+
+pub use ast::Node;
+pub use capabilities::{capabilities, Capabilities};
+pub use engine::Engine;
+pub use eval::{
+  equiv, eval, is_normal_form, normalize, normalize_many, CancelToken, EvalError, EvalStats,
+  EvalStrategy, Evaluator, NormalForm, Reducer,
+};
+pub use parser::{Parser, ParserError};
+pub use source::SourceFile;
+
+/// Parse `source` as a single term and evaluate it to normal form in applicative order
+pub fn run(source: &str) -> Result<Rc<Node<'_>>, anyhow::Error> {
+  let term = Parser::new(source).parse()?;
+  Ok(eval(Rc::new(term)))
+}
+
+/// Parse `source` as a single term and evaluate it with `evaluator`, for hosts that
+/// must not crash on untrusted input
+///
+/// A panic anywhere in parsing or evaluation is caught and returned as
+/// [`EvalError::Internal`], though the panic hook still runs. Only the limits set
+/// on `evaluator` bound the work done, so give it fuel or a cancel token
+pub fn evaluate_sandboxed<'inp>(
+  source: &'inp str,
+  evaluator: &Evaluator,
+) -> Result<Rc<Node<'inp>>, anyhow::Error> {
+  panic::catch_unwind(AssertUnwindSafe(|| {
+    let term = Parser::new(source).parse()?;
+    Ok(evaluator.eval(Rc::new(term))?)
+  }))
+  .unwrap_or_else(|payload| Err(EvalError::Internal(panic_message(&*payload)).into()))
+}
+
+/// The message a panic was raised with, if it was given one
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+  if let Some(message) = payload.downcast_ref::<&str>() {
+    message.to_string()
+  } else if let Some(message) = payload.downcast_ref::<String>() {
+    message.clone()
+  } else {
+    "panicked without a message".to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("(λx.x) y", Ok("y"))]
+  #[case(
+    "λx.",
+    Err("Unexpected end of input, expected one of '(', lambda, identifier")
+  )]
+  #[case("(λx.x x) (λx.x x)", Err("Step limit exceeded"))]
+  fn sandboxed(#[case] input: &str, #[case] expected: Result<&str, &str>) {
+    let evaluator = Evaluator::default().with_fuel(100);
+    let result = evaluate_sandboxed(input, &evaluator);
+    assert_eq!(
+      result
+        .map(|term| term.to_string())
+        .map_err(|err| err.to_string()),
+      expected.map(str::to_string).map_err(str::to_string)
+    );
+  }
+
+  #[test]
+  fn panics_become_errors() {
+    let payload = panic::catch_unwind(|| panic!("bad {}", "state")).unwrap_err();
+    assert_eq!(panic_message(&*payload), "bad state");
+  }
+}
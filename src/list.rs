@@ -0,0 +1,217 @@
+//! Reading the elements of lists encoded as terms, one at a time
+//!
+//! Lists come in two encodings here, both taking a function `c` for a cons cell and
+//! a value `n` for the empty list:
+//!
+//! | list        | Church                 | Scott                              |
+//! |-------------|------------------------|------------------------------------|
+//! | `[]`        | `λc.λn.n`              | `λc.λn.n`                          |
+//! | `[a, b]`    | `λc.λn.c a (c b n)`    | `λc.λn.c a (λc.λn.c b (λc.λn.n))`  |
+//!
+//! [`elements`] only ever reduces a list to weak head normal form, which is as far
+//! as it takes to see whether it starts with a cons cell. Each further element is
+//! forced only when asked for, so the elements at the front of an infinite list can
+//! be read as long as nothing else is:
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::list::{elements, ListEncoding};
+//! # use camel::parser::Parser;
+//! # use camel::Evaluator;
+//! // the Scott list a, a, a, ... as a fixed point
+//! let term = Parser::new("(λf.(λx.f (x x)) (λx.f (x x))) (λr.λc.λn.c a r)").parse()?;
+//! let evaluator = Evaluator::default().with_fuel(1_000);
+//! let front: Vec<_> = elements(Rc::new(term), ListEncoding::Scott, &evaluator)
+//!   .take(3)
+//!   .map(|element| element.map(|term| term.to_string()))
+//!   .collect::<Result<_, _>>()?;
+//! assert_eq!(front, ["a", "a", "a"]);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::{Application, Identifier, Node};
+use crate::eval::{EvalError, Evaluator, NormalForm};
+use crate::names::NameSupply;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListEncoding {
+  /// A list as its own right fold
+  Church,
+  /// A list as the case analysis on its first cell
+  Scott,
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ListError {
+  #[error(transparent)]
+  Eval(#[from] EvalError),
+
+  #[error("Term is not a {0} list, reducing to {1}")]
+  NotAList(ListEncoding, String),
+}
+
+/// The elements of `list`, each reduced only as far as it takes to find it
+///
+/// The elements themselves come out as they are in the list, without being
+/// reduced, and every reduction of the list made to find one is done by
+/// `evaluator`, under its limits
+pub fn elements<'e, 'inp>(
+  list: Rc<Node<'inp>>,
+  encoding: ListEncoding,
+  evaluator: &'e Evaluator<'e>,
+) -> Elements<'e, 'inp> {
+  let mut supply = NameSupply::avoiding(&list);
+  let cons = Cow::Owned(supply.fresh("cons"));
+  let nil = Cow::Owned(supply.fresh("nil"));
+  let mut elements = Elements {
+    evaluator,
+    encoding,
+    cons,
+    nil,
+    pending: None,
+  };
+  elements.pending = Some(elements.open(list));
+  elements
+}
+
+/// An iterator over the elements of a list, from `elements`
+pub struct Elements<'e, 'inp> {
+  evaluator: &'e Evaluator<'e>,
+  encoding: ListEncoding,
+  /// Names of the free variables the list is applied to, for cons and for nil
+  cons: Cow<'inp, str>,
+  nil: Cow<'inp, str>,
+  /// The rest of the list, already applied to cons and nil, or `None` once it has
+  /// ended
+  pending: Option<Rc<Node<'inp>>>,
+}
+
+impl<'inp> Elements<'_, 'inp> {
+  /// `list` applied to cons and nil
+  fn open(&self, list: Rc<Node<'inp>>) -> Rc<Node<'inp>> {
+    let var = |name: &Cow<'inp, str>| Rc::new(Node::Identifier(Identifier { name: name.clone() }));
+    let app = |lhs, rhs| Rc::new(Node::Application(Application { lhs, rhs }));
+    app(app(list, var(&self.cons)), var(&self.nil))
+  }
+
+  /// The head and tail of a cell of the list, if `cell` is one
+  fn split_cell(&self, cell: &Node<'inp>) -> Option<(Rc<Node<'inp>>, Rc<Node<'inp>>)> {
+    let Node::Application(outer) = cell else {
+      return None;
+    };
+    let Node::Application(inner) = &*outer.lhs else {
+      return None;
+    };
+    match &*inner.lhs {
+      Node::Identifier(id) if id.name == self.cons => {
+        Some((Rc::clone(&inner.rhs), Rc::clone(&outer.rhs)))
+      }
+      _ => None,
+    }
+  }
+}
+
+impl<'inp> Iterator for Elements<'_, 'inp> {
+  type Item = Result<Rc<Node<'inp>>, ListError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let pending = self.pending.take()?;
+    let cell = match self.evaluator.normalize(pending, NormalForm::WeakHead) {
+      Ok(cell) => cell,
+      Err(err) => return Some(Err(err.into())),
+    };
+    if let Node::Identifier(id) = &*cell {
+      if id.name == self.nil {
+        return None;
+      }
+    }
+    // a cons cell is the cons variable applied to the head and then the tail
+    let Some((head, tail)) = self.split_cell(&cell) else {
+      return Some(Err(ListError::NotAList(self.encoding, cell.to_string())));
+    };
+    self.pending = Some(match self.encoding {
+      // a Church list has already been folded with cons and nil all the way
+      ListEncoding::Church => tail,
+      ListEncoding::Scott => self.open(tail),
+    });
+    Some(Ok(head))
+  }
+}
+
+impl fmt::Display for ListEncoding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ListEncoding::Church => write!(f, "Church"),
+      ListEncoding::Scott => write!(f, "Scott"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  const Y: &str = "(λf.(λx.f (x x)) (λx.f (x x)))";
+
+  fn front(
+    input: &str,
+    encoding: ListEncoding,
+    count: usize,
+  ) -> Result<Vec<String>, anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let evaluator = Evaluator::default().with_fuel(10_000);
+    let terms = elements(term, encoding, &evaluator)
+      .take(count)
+      .map(|element| element.map(|term| term.to_string()))
+      .collect::<Result<_, _>>()?;
+    Ok(terms)
+  }
+
+  #[rstest]
+  #[case("λc.λn.n", ListEncoding::Church, &[])]
+  #[case("λc.λn.c a (c b n)", ListEncoding::Church, &["a", "b"])]
+  #[case("λc.λn.n", ListEncoding::Scott, &[])]
+  #[case("λc.λn.c a (λc.λn.c b (λc.λn.n))", ListEncoding::Scott, &["a", "b"])]
+  #[case("λc.λn.c cons (c nil n)", ListEncoding::Church, &["cons", "nil"])]
+  fn finite_lists(
+    #[case] input: &str,
+    #[case] encoding: ListEncoding,
+    #[case] expected: &[&str],
+  ) -> Result<(), anyhow::Error> {
+    assert_eq!(front(input, encoding, usize::MAX)?, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(&format!("{} (λr.λc.λn.c a (r c n))", Y), ListEncoding::Church)]
+  #[case(&format!("{} (λr.λc.λn.c a r)", Y), ListEncoding::Scott)]
+  fn infinite_lists(
+    #[case] input: &str,
+    #[case] encoding: ListEncoding,
+  ) -> Result<(), anyhow::Error> {
+    assert_eq!(front(input, encoding, 4)?, ["a"; 4]);
+    Ok(())
+  }
+
+  #[test]
+  fn not_a_list() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("λc.λn.c").parse()?);
+    let evaluator = Evaluator::default();
+    let mut elements = elements(term, ListEncoding::Scott, &evaluator);
+    assert!(matches!(
+      elements.next(),
+      Some(Err(ListError::NotAList(..)))
+    ));
+    assert!(elements.next().is_none());
+    Ok(())
+  }
+}
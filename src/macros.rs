@@ -0,0 +1,74 @@
+//! Macros for building terms in Rust without spelling out every node
+//!
+//! [`var!`](crate::var) names a variable, [`lam!`](crate::lam) binds one or more
+//! parameters over a body, and [`app!`](crate::app) applies a function to one or
+//! more arguments, associating to the left as the parser does. Each gives a
+//! [`Node`](crate::ast::Node), so they nest:
+//!
+//! ```
+//! # use camel::{app, lam, var};
+//! # use camel::parser::Parser;
+//! let two = lam!(f x => app!(var!(f), app!(var!(f), var!(x))));
+//! assert_eq!(two, Parser::new("λf.λx.f (f x)").parse()?);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+/// A variable, named by an identifier
+#[macro_export]
+macro_rules! var {
+  ($name:ident) => {
+    $crate::ast::Node::Identifier($crate::ast::Identifier {
+      name: ::std::borrow::Cow::Borrowed(::std::stringify!($name)),
+    })
+  };
+}
+
+/// Abstractions binding each of the parameters in turn, the first outermost
+#[macro_export]
+macro_rules! lam {
+  ($param:ident => $body:expr) => {
+    $crate::ast::Node::Abstraction($crate::ast::Abstraction {
+      param: ::std::borrow::Cow::Borrowed(::std::stringify!($param)),
+      body: ::std::rc::Rc::new($body),
+    })
+  };
+  ($param:ident $($rest:ident)+ => $body:expr) => {
+    $crate::lam!($param => $crate::lam!($($rest)+ => $body))
+  };
+}
+
+/// A function applied to each of the arguments in turn, so that `app!(f, a, b)` is
+/// `(f a) b`
+#[macro_export]
+macro_rules! app {
+  ($fun:expr, $arg:expr $(,)?) => {
+    $crate::ast::Node::Application($crate::ast::Application {
+      lhs: ::std::rc::Rc::new($fun),
+      rhs: ::std::rc::Rc::new($arg),
+    })
+  };
+  ($fun:expr, $arg:expr, $($rest:expr),+ $(,)?) => {
+    $crate::app!($crate::app!($fun, $arg), $($rest),+)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(var!(x), "x")]
+  #[case(lam!(x => var!(x)), "λx.x")]
+  #[case(lam!(x y z => var!(y)), "λx.λy.λz.y")]
+  #[case(app!(var!(f), var!(a), var!(b)), "f a b")]
+  #[case(app!(var!(f), app!(var!(a), var!(b)),), "f (a b)")]
+  #[case(app!(lam!(x => app!(var!(x), var!(x))), lam!(x => app!(var!(x), var!(x)))), "(λx.x x) (λx.x x)")]
+  fn builds_terms(
+    #[case] term: crate::ast::Node<'static>,
+    #[case] source: &str,
+  ) -> Result<(), anyhow::Error> {
+    assert_eq!(term, Parser::new(source).parse()?);
+    Ok(())
+  }
+}
@@ -0,0 +1,210 @@
+//! Minting variable names that do not clash, and renaming bound variables
+//!
+//! Substitution renames binders that would capture a free variable of the value
+//! being substituted, and transformations of terms often need the same: a name
+//! that is not used yet, or a binder renamed without changing what the term means.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+/// Find a variant of `base` with a numeric suffix that is not `taken`
+///
+/// Any digits `base` already ends in are replaced, so fresh names for `x1` are
+/// `x2`, `x3` and so on rather than `x11`
+pub fn fresh_name(base: &str, taken: impl Fn(&str) -> bool) -> String {
+  let stem = base.trim_end_matches(|c: char| c.is_ascii_digit());
+  (1..)
+    .map(|n| format!("{}{}", stem, n))
+    .find(|candidate| !taken(candidate))
+    .expect("some suffix is always free")
+}
+
+/// A source of names that clash neither with a set of reserved names nor with any
+/// name it has handed out before
+#[derive(Debug, Clone, Default)]
+pub struct NameSupply {
+  taken: HashSet<String>,
+}
+
+impl NameSupply {
+  pub fn new() -> Self {
+    NameSupply::default()
+  }
+
+  /// A supply which avoids every name used in `node`, whether free or bound
+  pub fn avoiding(node: &Node<'_>) -> Self {
+    let mut supply = NameSupply::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+      match node {
+        Node::Abstraction(abs) => {
+          supply.reserve(abs.param.as_ref());
+          stack.push(&abs.body);
+        }
+        Node::Application(app) => stack.extend([&*app.lhs, &*app.rhs]),
+        Node::Identifier(id) => supply.reserve(id.name.as_ref()),
+      }
+    }
+    supply
+  }
+
+  /// Keep `name` from being handed out
+  pub fn reserve(&mut self, name: impl Into<String>) {
+    self.taken.insert(name.into());
+  }
+
+  pub fn is_taken(&self, name: &str) -> bool {
+    self.taken.contains(name)
+  }
+
+  /// `base` itself if it is free, or otherwise a variant of it as from `fresh_name`,
+  /// reserving whichever name is returned
+  pub fn fresh(&mut self, base: &str) -> String {
+    let name = if self.is_taken(base) {
+      fresh_name(base, |candidate| self.is_taken(candidate))
+    } else {
+      base.to_string()
+    };
+    self.reserve(name.clone());
+    name
+  }
+}
+
+/// Rename every binder of `old` to `new`, along with the variables it binds, or
+/// return `None` if that would change the meaning of the term
+///
+/// The renaming fails when `new` occurs free under a binder of `old`, where it
+/// would be captured, or when a variable bound by such a binder sits under a binder
+/// of `new`, which would then capture it. Free occurrences of `old` are left alone
+pub fn alpha_rename<'inp>(node: &Rc<Node<'inp>>, old: &str, new: &str) -> Option<Rc<Node<'inp>>> {
+  enum Visit<'inp> {
+    Enter(Rc<Node<'inp>>),
+    /// Leave an abstraction, whose parameter is in `param`
+    Abstract(Cow<'inp, str>),
+    /// Leave an application, whose sides are the top two results
+    Apply,
+  }
+
+  if old == new {
+    return Some(Rc::clone(node));
+  }
+  // depths of the binders of each name in scope, innermost last
+  let mut olds: Vec<usize> = Vec::new();
+  let mut news: Vec<usize> = Vec::new();
+  let mut depth = 0;
+  let mut results: Vec<Rc<Node<'inp>>> = Vec::new();
+  let mut stack = vec![Visit::Enter(Rc::clone(node))];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(node) => match &*node {
+        Node::Abstraction(abs) => {
+          depth += 1;
+          let param = match abs.param.as_ref() {
+            name if name == old => {
+              olds.push(depth);
+              Cow::Owned(new.to_string())
+            }
+            name if name == new => {
+              news.push(depth);
+              abs.param.clone()
+            }
+            _ => abs.param.clone(),
+          };
+          stack.push(Visit::Abstract(param));
+          stack.push(Visit::Enter(Rc::clone(&abs.body)));
+        }
+        Node::Application(app) => {
+          stack.push(Visit::Apply);
+          stack.push(Visit::Enter(Rc::clone(&app.rhs)));
+          stack.push(Visit::Enter(Rc::clone(&app.lhs)));
+        }
+        Node::Identifier(id) => {
+          let (old_binder, new_binder) = (olds.last(), news.last());
+          let result = match id.name.as_ref() {
+            name if name == old => match old_binder {
+              Some(o) if new_binder.is_some_and(|n| n > o) => return None,
+              Some(_) => Rc::new(Node::Identifier(Identifier {
+                name: Cow::Owned(new.to_string()),
+              })),
+              None => node,
+            },
+            name if name == new => match (old_binder, new_binder) {
+              (Some(o), Some(n)) if o > n => return None,
+              (Some(_), None) => return None,
+              _ => node,
+            },
+            _ => node,
+          };
+          results.push(result);
+        }
+      },
+      Visit::Abstract(param) => {
+        if olds.last() == Some(&depth) {
+          olds.pop();
+        } else if news.last() == Some(&depth) {
+          news.pop();
+        }
+        depth -= 1;
+        let body = results.pop().expect("the body was entered");
+        results.push(Rc::new(Node::Abstraction(Abstraction { param, body })));
+      }
+      Visit::Apply => {
+        let rhs = results.pop().expect("the argument was entered");
+        let lhs = results.pop().expect("the function was entered");
+        results.push(Rc::new(Node::Application(Application { lhs, rhs })));
+      }
+    }
+  }
+  results.pop()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", &[], "x1")]
+  #[case("x", &["x1", "x2"], "x3")]
+  #[case("x7", &["x1"], "x2")]
+  fn fresh_names(#[case] base: &str, #[case] taken: &[&str], #[case] expected: &str) {
+    assert_eq!(fresh_name(base, |name| taken.contains(&name)), expected);
+  }
+
+  #[test]
+  fn supply_avoids_the_term_and_itself() -> Result<(), anyhow::Error> {
+    let term = Parser::new("λx.x y x1").parse()?;
+    let mut supply = NameSupply::avoiding(&term);
+    assert_eq!(supply.fresh("z"), "z");
+    assert_eq!(supply.fresh("z"), "z1");
+    assert_eq!(supply.fresh("x"), "x2");
+    assert_eq!(supply.fresh("y"), "y1");
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λx.x", "x", "y", Some("(λy. y)"))]
+  #[case("λx.x z", "x", "y", Some("(λy. y z)"))]
+  #[case("x (λx.x)", "x", "y", Some("x (λy. y)"))]
+  #[case("λx.λx.x", "x", "y", Some("(λy. (λy. y))"))]
+  #[case("λx.λy.y", "x", "y", Some("(λy. (λy. y))"))]
+  #[case("λx.y", "x", "y", None)]
+  #[case("λx.λy.x", "x", "y", None)]
+  #[case("λy.λx.y", "x", "y", None)]
+  #[case("λx.x", "x", "x", Some("(λx. x)"))]
+  fn rename(
+    #[case] input: &str,
+    #[case] old: &str,
+    #[case] new: &str,
+    #[case] expected: Option<&str>,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let renamed = alpha_rename(&term, old, new).map(|term| term.to_string());
+    assert_eq!(renamed.as_deref(), expected);
+    Ok(())
+  }
+}
@@ -0,0 +1,134 @@
+//! Reading numbers back out of terms which encode them
+//!
+//! The Church numeral for `n` applies its first parameter `n` times to its second:
+//! `λf.λx.f (f (f x))` is 3. A numeral is decoded by walking down that chain of
+//! applications one link at a time, so a decoder given a bound can stop as soon as
+//! it passes the bound instead of counting the whole chain.
+//!
+//! ```
+//! # use camel::numeral::{church, Magnitude};
+//! # use camel::parser::Parser;
+//! let three = Parser::new("λf.λx.f (f (f x))").parse()?;
+//! assert_eq!(church(&three, u64::MAX), Some(Magnitude::Exact(3)));
+//! assert_eq!(church(&three, 2), Some(Magnitude::Exceeds(2)));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fmt;
+
+use crate::ast::Node;
+
+#[cfg(feature = "bignum")]
+pub mod big;
+
+/// The size of a decoded numeral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Magnitude {
+  Exact(u64),
+  /// The numeral is larger than this bound, and was not decoded any further
+  Exceeds(u64),
+}
+
+impl fmt::Display for Magnitude {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Magnitude::Exact(n) => write!(f, "{}", n),
+      Magnitude::Exceeds(bound) => write!(f, "more than {}", bound),
+    }
+  }
+}
+
+/// The number a Church numeral stands for, or `None` if the term is not one
+///
+/// Decoding stops once the count passes `bound`, in which case only the part of
+/// the term up to there has been checked to be a numeral
+pub fn church(node: &Node<'_>, bound: u64) -> Option<Magnitude> {
+  let mut decoder = ChurchDecoder::new(node)?;
+  let mut count = 0;
+  while decoder.advance()? {
+    if count == bound {
+      return Some(Magnitude::Exceeds(bound));
+    }
+    count += 1;
+  }
+  Some(Magnitude::Exact(count))
+}
+
+/// A Church numeral being decoded one application at a time
+#[derive(Debug, Clone)]
+pub struct ChurchDecoder<'n> {
+  succ: &'n str,
+  zero: &'n str,
+  rest: &'n Node<'n>,
+}
+
+impl<'n> ChurchDecoder<'n> {
+  /// A decoder for `node`, or `None` if it does not have the two binders of a numeral
+  pub fn new(node: &'n Node<'n>) -> Option<Self> {
+    let Node::Abstraction(outer) = node else {
+      return None;
+    };
+    let Node::Abstraction(inner) = &*outer.body else {
+      return None;
+    };
+    Some(ChurchDecoder {
+      succ: &outer.param,
+      zero: &inner.param,
+      rest: &inner.body,
+    })
+  }
+
+  /// Take the next link of the chain: `Some(true)` for one more application,
+  /// `Some(false)` once the chain has ended at zero, and `None` if the rest of the
+  /// term is not part of a numeral
+  pub fn advance(&mut self) -> Option<bool> {
+    match self.rest {
+      Node::Identifier(id) if id.name == self.zero => Some(false),
+      // with both parameters named alike, the inner one shadows the outer
+      Node::Application(app) if self.succ != self.zero => match &*app.lhs {
+        Node::Identifier(id) if id.name == self.succ => {
+          self.rest = &app.rhs;
+          Some(true)
+        }
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("λf.λx.x", Some(Magnitude::Exact(0)))]
+  #[case("λf.λx.f x", Some(Magnitude::Exact(1)))]
+  #[case("λs.λz.s (s (s (s z)))", Some(Magnitude::Exact(4)))]
+  #[case("λf.λf.f", Some(Magnitude::Exact(0)))]
+  #[case("λf.λf.f f", None)]
+  #[case("λf.λx.f (x f)", None)]
+  #[case("λf.λx.f f", None)]
+  #[case("λf.f", None)]
+  #[case("x", None)]
+  fn decodes_church_numerals(
+    #[case] input: &str,
+    #[case] expected: Option<Magnitude>,
+  ) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(church(&term, u64::MAX), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(0, Magnitude::Exceeds(0))]
+  #[case(2, Magnitude::Exceeds(2))]
+  #[case(3, Magnitude::Exact(3))]
+  fn stops_at_bound(#[case] bound: u64, #[case] expected: Magnitude) -> Result<(), anyhow::Error> {
+    let term = Parser::new("λf.λx.f (f (f x))").parse()?;
+    assert_eq!(church(&term, bound), Some(expected));
+    Ok(())
+  }
+}
@@ -0,0 +1,288 @@
+//! Decoding numerals of any size into arbitrary precision integers
+//!
+//! A numeral like `EXP 10 20` has far too many applications in its normal form to
+//! ever build, so these decoders never normalize the term. Instead they run it,
+//! with closures over the term as written, on a native successor and zero. For
+//! Church numerals the successor is an adder, `λx.x + k`, and whenever a term
+//! applied to an adder gives back a function which turns out to only add to its
+//! argument, that function is collapsed into a single adder as well. Iterating an
+//! adder then costs one addition rather than one application per unit, so the
+//! work done follows the size of the term rather than of the number.
+//!
+//! ```
+//! # use camel::numeral::big;
+//! # use camel::parser::Parser;
+//! let term = Parser::new("(λm.λn.n m) (λf.λx.f (f (f (f (f (f (f (f (f (f x)))))))))) (λf.λx.f (f (f (f (f (f (f (f (f (f (f (f (f (f (f (f (f (f (f (f x))))))))))))))))))))").parse()?;
+//! assert_eq!(big::church(&term, big::DECODE_FUEL)?.to_string(), "100000000000000000000");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::ast::Node;
+use crate::eval::env::Env;
+
+/// Applications a decoder makes by default before giving up on a term
+pub const DECODE_FUEL: usize = 10_000_000;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NumeralError {
+  #[error("Term is not a numeral")]
+  NotANumeral,
+
+  #[error("Decoding ran out of fuel")]
+  OutOfFuel,
+}
+
+/// The number a Church numeral, or a term which evaluates to one, stands for
+pub fn church(node: &Node<'_>, fuel: usize) -> Result<BigUint, NumeralError> {
+  let mut machine = Machine::new(fuel);
+  let numeral = machine.eval(Rc::new(node.clone()), Env::new())?;
+  let succ = machine.apply(numeral, Rc::new(Value::Adder(BigUint::from(1u8))))?;
+  let zero = Rc::new(Value::Number {
+    base: ZERO,
+    offset: BigUint::default(),
+  });
+  match &*machine.apply(succ, zero)? {
+    Value::Number { base: ZERO, offset } => Ok(offset.clone()),
+    _ => Err(NumeralError::NotANumeral),
+  }
+}
+
+/// The number a Scott numeral, or a term which evaluates to one, stands for
+///
+/// Scott numerals are `λs.λz.z` for zero and `λs.λz.s n` for the successor of `n`.
+/// They can only be taken apart one unit at a time, so decoding one takes work in
+/// proportion to the number
+pub fn scott(node: &Node<'_>, fuel: usize) -> Result<BigUint, NumeralError> {
+  let mut machine = Machine::new(fuel);
+  let mut numeral = machine.eval(Rc::new(node.clone()), Env::new())?;
+  let mut count = BigUint::default();
+  loop {
+    let case = machine.apply(numeral, Rc::new(Value::Wrap))?;
+    let result = machine.apply(case, Rc::new(Value::Zero))?;
+    match &*result {
+      Value::Zero => return Ok(count),
+      Value::Wrapped(pred) => {
+        count += 1u8;
+        numeral = Rc::clone(pred);
+      }
+      _ => return Err(NumeralError::NotANumeral),
+    }
+  }
+}
+
+/// The base of a number which is a plain count rather than an offset from a probe
+const ZERO: usize = 0;
+
+enum Value<'inp> {
+  Closure {
+    param: Cow<'inp, str>,
+    body: Rc<Node<'inp>>,
+    env: Env<'inp, Value<'inp>>,
+  },
+  /// The function adding this much to a number
+  Adder(BigUint),
+  /// A count added to a base, which is either `ZERO` or an opaque argument given
+  /// to a function to find out whether it is an adder
+  Number {
+    base: usize,
+    offset: BigUint,
+  },
+  /// The successor case given to a Scott numeral, which wraps its argument
+  Wrap,
+  Wrapped(Rc<Value<'inp>>),
+  /// The zero case given to a Scott numeral
+  Zero,
+}
+
+/// What the machine does next: evaluate a term, or return a value to the frame on
+/// top of its stack
+enum Control<'inp> {
+  Eval(Rc<Node<'inp>>, Env<'inp, Value<'inp>>),
+  Return(Rc<Value<'inp>>),
+}
+
+enum Frame<'inp> {
+  /// Evaluate the argument of an application whose function is being evaluated
+  Arg(Rc<Node<'inp>>, Env<'inp, Value<'inp>>),
+  /// Apply the function to the argument being evaluated
+  Call(Rc<Value<'inp>>),
+  /// Collapse the result of applying a function to an adder, if it is one too
+  Collapse,
+}
+
+/// A call by value machine for running terms on native numbers
+struct Machine {
+  fuel: usize,
+  /// Bases handed out to probes so far
+  probes: usize,
+}
+
+impl Machine {
+  fn new(fuel: usize) -> Self {
+    Machine { fuel, probes: ZERO }
+  }
+
+  fn eval<'inp>(
+    &mut self,
+    term: Rc<Node<'inp>>,
+    env: Env<'inp, Value<'inp>>,
+  ) -> Result<Rc<Value<'inp>>, NumeralError> {
+    self.run(Control::Eval(term, env))
+  }
+
+  fn apply<'inp>(
+    &mut self,
+    function: Rc<Value<'inp>>,
+    arg: Rc<Value<'inp>>,
+  ) -> Result<Rc<Value<'inp>>, NumeralError> {
+    let mut stack = Vec::new();
+    let control = self.call(function, arg, &mut stack)?;
+    self.resume(control, stack)
+  }
+
+  fn run<'inp>(&mut self, control: Control<'inp>) -> Result<Rc<Value<'inp>>, NumeralError> {
+    self.resume(control, Vec::new())
+  }
+
+  fn resume<'inp>(
+    &mut self,
+    mut control: Control<'inp>,
+    mut stack: Vec<Frame<'inp>>,
+  ) -> Result<Rc<Value<'inp>>, NumeralError> {
+    loop {
+      control = match control {
+        Control::Eval(term, env) => match &*term {
+          Node::Identifier(id) => match env.lookup(&id.name) {
+            Some(value) => Control::Return(Rc::clone(value)),
+            None => return Err(NumeralError::NotANumeral),
+          },
+          Node::Abstraction(abs) => Control::Return(Rc::new(Value::Closure {
+            param: abs.param.clone(),
+            body: Rc::clone(&abs.body),
+            env,
+          })),
+          Node::Application(app) => {
+            stack.push(Frame::Arg(Rc::clone(&app.rhs), env.clone()));
+            Control::Eval(Rc::clone(&app.lhs), env)
+          }
+        },
+        Control::Return(value) => match stack.pop() {
+          None => return Ok(value),
+          Some(Frame::Arg(term, env)) => {
+            stack.push(Frame::Call(value));
+            Control::Eval(term, env)
+          }
+          Some(Frame::Call(function)) => self.call(function, value, &mut stack)?,
+          Some(Frame::Collapse) => Control::Return(self.collapse(value)?),
+        },
+      };
+    }
+  }
+
+  /// Start applying `function` to `arg`, leaving any work left over on `stack`
+  fn call<'inp>(
+    &mut self,
+    function: Rc<Value<'inp>>,
+    arg: Rc<Value<'inp>>,
+    stack: &mut Vec<Frame<'inp>>,
+  ) -> Result<Control<'inp>, NumeralError> {
+    self.fuel = self.fuel.checked_sub(1).ok_or(NumeralError::OutOfFuel)?;
+    match (&*function, &*arg) {
+      (Value::Closure { param, body, env }, _) => {
+        if let Value::Adder(..) = &*arg {
+          stack.push(Frame::Collapse);
+        }
+        Ok(Control::Eval(Rc::clone(body), env.bind(param.clone(), arg)))
+      }
+      (Value::Adder(k), Value::Number { base, offset }) => {
+        Ok(Control::Return(Rc::new(Value::Number {
+          base: *base,
+          offset: offset + k,
+        })))
+      }
+      (Value::Wrap, _) => Ok(Control::Return(Rc::new(Value::Wrapped(arg)))),
+      _ => Err(NumeralError::NotANumeral),
+    }
+  }
+
+  /// An adder in place of `value`, if it is a closure which only adds to whatever
+  /// it is applied to
+  fn collapse<'inp>(&mut self, value: Rc<Value<'inp>>) -> Result<Rc<Value<'inp>>, NumeralError> {
+    if !matches!(*value, Value::Closure { .. }) {
+      return Ok(value);
+    }
+    self.probes += 1;
+    let base = self.probes;
+    let probe = Rc::new(Value::Number {
+      base,
+      offset: BigUint::default(),
+    });
+    match self.apply(Rc::clone(&value), probe) {
+      Ok(result) => match &*result {
+        Value::Number { base: b, offset } if *b == base => {
+          Ok(Rc::new(Value::Adder(offset.clone())))
+        }
+        _ => Ok(value),
+      },
+      Err(NumeralError::NotANumeral) => Ok(value),
+      Err(err) => Err(err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  const TWO: &str = "(λf.λx.f (f x))";
+  const THREE: &str = "(λf.λx.f (f (f x)))";
+
+  #[rstest]
+  #[case("λf.λx.x", "0")]
+  #[case(THREE, "3")]
+  #[case(&format!("(λm.λn.λf.m (n f)) {} {}", THREE, THREE), "9")]
+  #[case(&format!("(λm.λn.n m) {} {}", TWO, THREE), "8")]
+  #[case(&format!("(λm.λn.n m) {} ((λm.λn.n m) {} {})", TWO, TWO, THREE), "256")]
+  #[case(&format!("(λn.n n) ((λn.n n) {})", THREE), "443426488243037769948249630619149892803")]
+  fn decodes_church(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(church(&term, DECODE_FUEL)?.to_string(), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λs.λz.z", "0")]
+  #[case("λs.λz.s (λs.λz.s (λs.λz.z))", "2")]
+  #[case("(λn.λs.λz.s n) (λs.λz.z)", "1")]
+  fn decodes_scott(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(scott(&term, DECODE_FUEL)?.to_string(), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("λf.λx.f f")]
+  #[case("λf.λx.y")]
+  #[case("λf.λx.x f")]
+  fn rejects_other_terms(#[case] input: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(church(&term, DECODE_FUEL), Err(NumeralError::NotANumeral));
+    Ok(())
+  }
+
+  #[test]
+  fn runs_out_of_fuel() -> Result<(), anyhow::Error> {
+    let term = Parser::new("(λx.x x) (λx.x x)").parse()?;
+    assert_eq!(church(&term, 1_000), Err(NumeralError::OutOfFuel));
+    Ok(())
+  }
+}
@@ -1,24 +1,134 @@
+use std::borrow::Cow;
+use std::mem;
 use std::rc::Rc;
 
 use anyhow::anyhow;
 use thiserror::Error;
 
+use crate::arena::{TermArena, TermId};
 use crate::ast::{Abstraction, Application, Identifier, Node};
 use crate::lexer::Lexer;
-use crate::token::{Token, TokenError, TokenKind};
+use crate::source::Position;
+use crate::token::{Token, TokenError, TokenKind, TokenSet};
 
-#[derive(Debug, Error, PartialEq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ParserError {
-  #[error("Unexpected token: {0:?}")]
+  #[error("Unexpected {0}")]
   UnexpectedToken(TokenError),
 
-  #[error("Unexpected end of input")]
-  UnexpectedEndOfInput,
+  #[error("Expected {expected}, found {found}")]
+  ExpectedToken {
+    expected: TokenSet,
+    found: TokenError,
+  },
+
+  #[error("Unexpected end of input, expected {expected}")]
+  UnexpectedEndOfInput { expected: TokenSet },
+
+  #[error("Unexpected end of input, expected {expected} (the '(' at {open} is never closed)")]
+  UnclosedParen { expected: TokenSet, open: Position },
+}
+
+/// Tokens which can begin an atom
+const ATOM_START: TokenSet = TokenSet::new(&[TokenKind::LeftParen, TokenKind::LowercaseId]);
+
+/// A single line of input, either a named definition or a term to evaluate
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Statement<'inp> {
+  Definition(Definition<'inp>),
+  Term(Node<'inp>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Definition<'inp> {
+  pub name: &'inp str,
+  pub term: Node<'inp>,
+}
+
+/// Builds the terms a parser reads, as trees of `Node` or in a `TermArena`
+pub(crate) trait Build<'inp> {
+  type Term;
+
+  fn abstraction(&mut self, param: Cow<'inp, str>, body: Self::Term) -> Self::Term;
+
+  fn application(&mut self, lhs: Self::Term, rhs: Self::Term) -> Self::Term;
+
+  fn identifier(&mut self, name: Cow<'inp, str>) -> Self::Term;
+}
+
+/// Builds terms as trees of `Node`
+struct Tree;
+
+impl<'inp> Build<'inp> for Tree {
+  type Term = Node<'inp>;
+
+  fn abstraction(&mut self, param: Cow<'inp, str>, body: Node<'inp>) -> Node<'inp> {
+    Node::Abstraction(Abstraction {
+      param,
+      body: Rc::new(body),
+    })
+  }
+
+  fn application(&mut self, lhs: Node<'inp>, rhs: Node<'inp>) -> Node<'inp> {
+    Node::Application(Application {
+      lhs: Rc::new(lhs),
+      rhs: Rc::new(rhs),
+    })
+  }
+
+  fn identifier(&mut self, name: Cow<'inp, str>) -> Node<'inp> {
+    Node::Identifier(Identifier { name })
+  }
+}
+
+/// A term being parsed by `Parser::parse_term`: the parameters of the lambdas it
+/// starts with, and the application of the atoms read so far
+struct Frame<'inp, T> {
+  params: Vec<Cow<'inp, str>>,
+  lhs: Option<T>,
+}
+
+impl<T> Default for Frame<'_, T> {
+  fn default() -> Self {
+    Frame {
+      params: Vec::new(),
+      lhs: None,
+    }
+  }
+}
+
+impl<'inp, T> Frame<'inp, T> {
+  fn push(&mut self, build: &mut impl Build<'inp, Term = T>, atom: T) {
+    self.lhs = Some(self.apply(build, atom));
+  }
+
+  /// The term, ending with `last`
+  fn finish(mut self, build: &mut impl Build<'inp, Term = T>, last: T) -> T {
+    let body = self.apply(build, last);
+    self
+      .params
+      .into_iter()
+      .rev()
+      .fold(body, |body, param| build.abstraction(param, body))
+  }
+
+  /// The atoms so far applied to `atom`
+  fn apply(&mut self, build: &mut impl Build<'inp, Term = T>, atom: T) -> T {
+    match self.lhs.take() {
+      None => atom,
+      Some(lhs) => build.application(lhs, atom),
+    }
+  }
 }
 
 pub struct Parser<'inp> {
   lexer: Lexer<'inp>,
   current_token: Option<Token<'inp>>,
+  /// Every token kind tested for at the current position
+  expected: TokenSet,
+  /// Offsets of the '(' tokens still waiting for their ')', innermost last
+  open_parens: Vec<usize>,
 }
 
 impl<'inp> Parser<'inp> {
@@ -28,132 +138,201 @@ impl<'inp> Parser<'inp> {
     Parser {
       lexer,
       current_token,
+      expected: TokenSet::default(),
+      open_parens: Vec::new(),
     }
   }
 
+  /// Parse a statement, which must span the entire input
+  ///
+  /// statement ::= LCID EQUALS term
+  ///             | term
+  pub fn parse_statement(&mut self) -> Result<Statement<'inp>, anyhow::Error> {
+    let statement = match (&self.current_token, self.peek_kind()) {
+      (
+        Some(Token {
+          kind: TokenKind::LowercaseId,
+          text,
+        }),
+        Some(TokenKind::Equals),
+      ) => {
+        let name = *text;
+        self.advance();
+        self.advance();
+        let term = self.parse_term()?;
+        Statement::Definition(Definition { name, term })
+      }
+      _ => Statement::Term(self.parse_term()?),
+    };
+    self.expect_end()?;
+    Ok(statement)
+  }
+
+  /// Parse a term which must span the entire input
+  pub fn parse(&mut self) -> Result<Node<'inp>, anyhow::Error> {
+    let term = self.parse_term()?;
+    self.expect_end()?;
+    Ok(term)
+  }
+
+  /// Parse a term which must span the entire input into `arena`, returning where
+  /// its root was put
+  pub fn parse_into(&mut self, arena: &mut TermArena<'inp>) -> Result<TermId, anyhow::Error> {
+    let term = self.parse_term_with(arena)?;
+    self.expect_end()?;
+    Ok(term)
+  }
+
   /// Parse a term, which is either a lambda, or an application
   ///
   /// term ::= application
   ///        | LAMBDA LCID DOT term
+  ///
+  /// An application is an atom applied left-associatively to further atoms, and an
+  /// atom is any term between brackets, or a lowercase ID:
+  ///
+  /// application ::= atom atom*
+  /// atom        ::= LPAREN term RPAREN
+  ///               | LCID
+  ///
+  /// Terms between brackets are parsed with an explicit stack rather than by
+  /// recursion, so that no amount of nesting can overflow the Rust stack
   pub fn parse_term(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    match self.current_kind() {
-      Some(TokenKind::Lambda) => self.parse_abstraction(),
-      _ => self.parse_application(),
+    self.parse_term_with(&mut Tree)
+  }
+
+  /// Parse a term as `parse_term` does, building it with `build`
+  pub(crate) fn parse_term_with<B: Build<'inp>>(
+    &mut self,
+    build: &mut B,
+  ) -> Result<B::Term, anyhow::Error> {
+    #[cfg(feature = "profile")]
+    let _pass = crate::profile::enter(crate::profile::Pass::Parse);
+    // the term being parsed, inside the terms around it, each a bracket further out
+    let mut term = Frame::default();
+    let mut outer = Vec::new();
+    'term: loop {
+      while self.check(TokenKind::Lambda) {
+        self.advance();
+        term.params.push(self.parse_param()?);
+        self.expect(TokenKind::Dot)?;
+      }
+      loop {
+        let mut atom = if self.check(TokenKind::LeftParen) {
+          self.open_parens.push(self.lexer.offset());
+          self.advance();
+          outer.push(mem::take(&mut term));
+          continue 'term;
+        } else if self.check(TokenKind::LowercaseId) {
+          let name = self.parse_identifier()?;
+          build.identifier(name)
+        } else {
+          return Err(self.error());
+        };
+        // a term ending here is the atom of the term around it, which may end too
+        while !self.check_any(ATOM_START) {
+          let finished = mem::take(&mut term).finish(build, atom);
+          let Some(around) = outer.pop() else {
+            return Ok(finished);
+          };
+          self.expect(TokenKind::RightParen)?;
+          self.open_parens.pop();
+          term = around;
+          atom = finished;
+        }
+        term.push(build, atom);
+      }
     }
   }
 
-  fn parse_abstraction(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    self.advance();
-    let param = match &self.current_token {
+  fn parse_param(&mut self) -> Result<Cow<'inp, str>, anyhow::Error> {
+    match &self.current_token {
       Some(Token {
         kind: TokenKind::LowercaseId,
         text,
-      }) => *text,
-      Some(..) => {
-        return Err(anyhow!(ParserError::UnexpectedToken(
-          self
-            .current_token
-            .clone()
-            .map(Into::into)
-            .expect("not an eof error")
-        )))
+      }) => {
+        let param = Cow::Borrowed(*text);
+        self.advance();
+        Ok(param)
+      }
+      _ => {
+        self.check(TokenKind::LowercaseId);
+        Err(self.error())
       }
-      None => return Err(anyhow!(ParserError::UnexpectedEndOfInput)),
+    }
+  }
+
+  fn parse_identifier(&mut self) -> Result<Cow<'inp, str>, anyhow::Error> {
+    let id = match &self.current_token {
+      Some(Token { text, .. }) => Cow::Borrowed(*text),
+      None => return Err(self.error()),
     };
     self.advance();
-    self.expect(TokenKind::Dot)?;
-    let body = self.parse_term()?;
-    Ok(Node::Abstraction(Abstraction {
-      param: param,
-      body: Rc::new(body),
-    }))
+    Ok(id)
   }
 
-  /// Parse an application, which is an application applied left-associatively to itself
-  ///
-  /// Originally represented as:
-  /// application ::= application atom
-  ///
-  /// The left recursion was removed with:
-  /// application  ::= atom application'
-  /// application' ::= atom application'
-  ///                | ε
-  fn parse_application(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    let mut lhs = self.parse_atom()?;
-    while matches!(
-      self.current_kind(),
-      Some(TokenKind::LowercaseId | TokenKind::LeftParen)
-    ) {
-      let rhs = self.parse_atom()?;
-      lhs = Node::Application(Application { lhs: Rc::new(lhs), rhs: Rc::new(rhs) });
-    }
-    Ok(lhs)
+  fn advance(&mut self) {
+    self.current_token = self.lexer.next_token();
+    self.expected = TokenSet::default();
   }
 
-  /// Parse an atom, which is any term between brackets, or a lowercase ID
-  ///
-  /// atom ::= LPAREN term RPAREN
-  ///        | LCID
-  fn parse_atom(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    match self.current_kind() {
-      Some(TokenKind::LeftParen) => self.parse_parenthesized(),
-      Some(TokenKind::LowercaseId) => self.parse_identifier(),
-      Some(..) => Err(anyhow!(ParserError::UnexpectedToken(
-        self
-          .current_token
-          .clone()
-          .map(Into::into)
-          .expect("not an eof error")
-      ))),
-      None => Err(anyhow!(ParserError::UnexpectedEndOfInput)),
-    }
+  /// Test whether the current token is of `kind`, recording it as acceptable here
+  fn check(&mut self, kind: TokenKind) -> bool {
+    self.check_any(TokenSet::new(&[kind]))
   }
 
-  fn parse_parenthesized(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    self.advance();
-    let term = self.parse_term()?;
-    self.expect(TokenKind::RightParen)?;
-    Ok(term)
+  fn check_any(&mut self, kinds: TokenSet) -> bool {
+    self.expected = self.expected.union(kinds);
+    self.current_kind().is_some_and(|k| kinds.contains(k))
   }
 
-  fn parse_identifier(&mut self) -> Result<Node<'inp>, anyhow::Error> {
-    let id = match &self.current_token {
-      Some(Token { text, .. }) => *text,
-      None => return Err(anyhow!(ParserError::UnexpectedEndOfInput)),
-    };
-    self.advance();
-    Ok(Node::Identifier(Identifier { name: id }))
+  fn expect(&mut self, kind: TokenKind) -> Result<(), anyhow::Error> {
+    if self.check(kind) {
+      self.advance();
+      Ok(())
+    } else {
+      Err(self.error())
+    }
   }
 
-  fn advance(&mut self) {
-    self.current_token = self.lexer.next_token();
+  /// An error at the current token, listing every kind that would have been accepted
+  ///
+  /// Running out of input inside parentheses points at the innermost unclosed '('
+  fn error(&self) -> anyhow::Error {
+    let expected = self.expected;
+    match (self.current_token.clone(), self.open_parens.last()) {
+      (Some(token), _) => anyhow!(ParserError::ExpectedToken {
+        expected,
+        found: token.into(),
+      }),
+      (None, Some(&offset)) => anyhow!(ParserError::UnclosedParen {
+        expected,
+        open: self.lexer.position(offset),
+      }),
+      (None, None) => anyhow!(ParserError::UnexpectedEndOfInput { expected }),
+    }
   }
 
-  fn expect(&mut self, kind: TokenKind) -> Result<(), anyhow::Error> {
-    match self.current_kind() {
-      Some(k) if k == kind => {
-        self.advance();
-        Ok(())
-      }
-      Some(..) => Err(anyhow!(ParserError::UnexpectedToken(
-        self
-          .current_token
-          .clone()
-          .map(Into::into)
-          .expect("not an eof error")
-      ))),
-      None => Err(anyhow!(ParserError::UnexpectedEndOfInput)),
+  fn expect_end(&self) -> Result<(), anyhow::Error> {
+    match self.current_token.clone() {
+      Some(token) => Err(anyhow!(ParserError::UnexpectedToken(token.into()))),
+      None => Ok(()),
     }
   }
 
   fn current_kind(&self) -> Option<TokenKind> {
     self.current_token.as_ref().map(|t| t.kind)
   }
+
+  fn peek_kind(&self) -> Option<TokenKind> {
+    self.lexer.clone().next_token().map(|t| t.kind)
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::ast::debruijn::to_debruijn;
   use rstest::rstest;
 
   #[rstest]
@@ -161,17 +340,17 @@ mod tests {
     "(λx.x)(λy.(λa.a))",
     Node::Application(Application {
       lhs: Rc::new(Node::Abstraction(Abstraction {
-        param: "x",
+        param: "x".into(),
         body: Rc::new(Node::Identifier(Identifier {
-          name: "x",
+          name: "x".into(),
         })),
       })),
       rhs: Rc::new(Node::Abstraction(Abstraction {
-        param: "y",
+        param: "y".into(),
         body: Rc::new(Node::Abstraction(Abstraction {
-          param: "a",
+          param: "a".into(),
           body: Rc::new(Node::Identifier(Identifier {
-            name: "a",
+            name: "a".into(),
           })),
         })),
       })),
@@ -197,22 +376,22 @@ mod tests {
       // left associative
       lhs: Rc::new(Node::Application(Application {
         lhs: Rc::new(Node::Abstraction(Abstraction {
-          param: "x",
+          param: "x".into(),
           body: Rc::new(Node::Identifier(Identifier {
-            name: "x",
+            name: "x".into(),
           })),
         })),
         rhs: Rc::new(Node::Abstraction(Abstraction {
-          param: "y",
+          param: "y".into(),
           body: Rc::new(Node::Identifier(Identifier {
-            name: "y",
+            name: "y".into(),
           })),
         })),
       })),
       rhs: Rc::new(Node::Abstraction(Abstraction {
-        param: "abc",
+        param: "abc".into(),
         body: Rc::new(Node::Identifier(Identifier {
-          name: "abc",
+          name: "abc".into(),
         })),
       })),
     }),
@@ -230,45 +409,193 @@ mod tests {
     Ok(())
   }
 
+  const TERM_START: &[TokenKind] = &[
+    TokenKind::LeftParen,
+    TokenKind::Lambda,
+    TokenKind::LowercaseId,
+  ];
+
   #[rstest]
-  #[case("(λx.1)", None, "1")]
-  #[case("(λA.a)", None, "A")]
-  #[case("(λAbc.Abc)", None, "A")]
-  #[case("(3 λx.x)", None, "3")]
-  #[case(")λx.x)", Some(TokenKind::RightParen), ")")]
-  #[case("(.x.x)", Some(TokenKind::Dot), ".")]
-  #[case("(x .)", Some(TokenKind::Dot), ".")]
+  #[case("(λx.1)", TERM_START, TokenKind::Unknown, "1")]
+  #[case("(λA.a)", &[TokenKind::LowercaseId], TokenKind::Unknown, "A")]
+  #[case("(λAbc.Abc)", &[TokenKind::LowercaseId], TokenKind::Unknown, "A")]
+  #[case("(3 λx.x)", TERM_START, TokenKind::Unknown, "3")]
+  #[case(")λx.x)", TERM_START, TokenKind::RightParen, ")")]
+  #[case("(.x.x)", TERM_START, TokenKind::Dot, ".")]
+  #[case(
+    "(x .)",
+    &[TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LowercaseId],
+    TokenKind::Dot,
+    "."
+  )]
+  #[case("λx x", &[TokenKind::Dot], TokenKind::LowercaseId, "x")]
+  #[case("λx λ", &[TokenKind::Dot], TokenKind::Lambda, "λ")]
   #[should_panic]
-  #[case("(λaBC.aBC)", None, "")] // first letter must be lower, others are ok
-  fn unexpected_token_error(
+  #[case("(λaBC.aBC)", &[], TokenKind::Unknown, "")] // first letter must be lower, others are ok
+  fn expected_token_error(
     #[case] input: &str,
-    #[case] expected_kind: Option<TokenKind>,
-    #[case] expected_repr: &str,
+    #[case] expected: &[TokenKind],
+    #[case] found_kind: TokenKind,
+    #[case] found_repr: &str,
   ) {
     let mut parser = Parser::new(input);
     let result = parser.parse_term();
-    eprintln!("{:?}", &result);
+    assert!(matches!(
+      result,
+      Err(err) if err.downcast_ref::<ParserError>().unwrap() == &ParserError::ExpectedToken {
+        expected: TokenSet::new(expected),
+        found: TokenError { kind: found_kind, text: found_repr.to_string() },
+      }
+    ));
+  }
+
+  #[rstest]
+  #[case("λx x", "Expected '.', found identifier 'x'")]
+  #[case(
+    "λx.",
+    "Unexpected end of input, expected one of '(', lambda, identifier"
+  )]
+  #[case(
+    "(λx.x",
+    "Unexpected end of input, expected one of '(', ')', identifier (the '(' at 1:1 is never closed)"
+  )]
+  #[case("(x .)", "Expected one of '(', ')', identifier, found '.'")]
+  #[case("(λx.x))", "Unexpected ')'")]
+  #[case("3", "Expected one of '(', lambda, identifier, found character '3'")]
+  fn error_message(#[case] input: &str, #[case] expected_message: &str) {
+    let mut parser = Parser::new(input);
+    let err = parser.parse().unwrap_err();
+    assert_eq!(err.to_string(), expected_message);
+  }
+
+  #[rstest]
+  #[case("x", Statement::Term(Node::Identifier(Identifier { name: "x".into() })))]
+  #[case(
+    "id = λx.x",
+    Statement::Definition(Definition {
+      name: "id",
+      term: Node::Abstraction(Abstraction {
+        param: "x".into(),
+        body: Rc::new(Node::Identifier(Identifier { name: "x".into() })),
+      }),
+    })
+  )]
+  #[case(
+    "f x",
+    Statement::Term(Node::Application(Application {
+      lhs: Rc::new(Node::Identifier(Identifier { name: "f".into() })),
+      rhs: Rc::new(Node::Identifier(Identifier { name: "x".into() })),
+    }))
+  )]
+  fn statement(
+    #[case] input: &str,
+    #[case] expected_statement: Statement,
+  ) -> Result<(), anyhow::Error> {
+    let mut parser = Parser::new(input);
+    assert_eq!(parser.parse_statement()?, expected_statement);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("x )", TokenKind::RightParen, ")")]
+  #[case("x = y = z", TokenKind::Equals, "=")]
+  #[case("(x) = y", TokenKind::Equals, "=")]
+  fn trailing_token_error(
+    #[case] input: &str,
+    #[case] expected_kind: TokenKind,
+    #[case] expected_repr: &str,
+  ) {
+    let mut parser = Parser::new(input);
+    let result = parser.parse_statement();
     assert!(matches!(
       result,
       Err(err) if err.downcast_ref::<ParserError>().unwrap() == &ParserError::UnexpectedToken(
-        TokenError { kind: expected_kind.unwrap_or(TokenKind::Unknown), text: expected_repr.to_string() }
+        TokenError { kind: expected_kind, text: expected_repr.to_string() }
       )
     ));
   }
 
   #[rstest]
-  #[case("")]
-  #[case("(")]
-  #[case("(λ")]
-  #[case("(λx")]
-  #[case("(λx.")]
-  #[case("(λx.x")]
-  #[case("(λx.x)(")]
-  fn unexpected_end_of_input_error(#[case] input: &str) {
+  #[case("", TERM_START)]
+  #[case("λ", &[TokenKind::LowercaseId])]
+  #[case("λx", &[TokenKind::Dot])]
+  #[case("λx.λy.", TERM_START)]
+  fn unexpected_end_of_input_error(#[case] input: &str, #[case] expected: &[TokenKind]) {
     let mut parser = Parser::new(input);
     let result = parser.parse_term();
-    assert!(
-      matches!(result, Err(err) if err.downcast_ref::<ParserError>().unwrap() == &ParserError::UnexpectedEndOfInput)
-    );
+    assert!(matches!(
+      result,
+      Err(err) if err.downcast_ref::<ParserError>().unwrap() == &ParserError::UnexpectedEndOfInput {
+        expected: TokenSet::new(expected),
+      }
+    ));
+  }
+
+  #[rstest]
+  #[case("(", TERM_START, 1, 1)]
+  #[case("(λ", &[TokenKind::LowercaseId], 1, 1)]
+  #[case("(λx", &[TokenKind::Dot], 1, 1)]
+  #[case("(λx.", TERM_START, 1, 1)]
+  #[case("(λx.x", &[TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LowercaseId], 1, 1)]
+  #[case("(λx.x)(", TERM_START, 1, 7)]
+  #[case("(λf.(f x)", &[TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LowercaseId], 1, 1)]
+  #[case("(λf.(f x", &[TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LowercaseId], 1, 5)]
+  #[case("x\n  (λy.y", &[TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LowercaseId], 2, 3)]
+  fn unclosed_paren_error(
+    #[case] input: &str,
+    #[case] expected: &[TokenKind],
+    #[case] line: usize,
+    #[case] column: usize,
+  ) {
+    let mut parser = Parser::new(input);
+    let result = parser.parse_term();
+    assert!(matches!(
+      result,
+      Err(err) if err.downcast_ref::<ParserError>().unwrap() == &ParserError::UnclosedParen {
+        expected: TokenSet::new(expected),
+        open: Position { line, column },
+      }
+    ));
+  }
+
+  #[rstest]
+  #[case(&format!("{}x{}", "(".repeat(100_000), ")".repeat(100_000)), 1)]
+  #[case(&format!("{}x", "λx.".repeat(100_000)), 100_001)]
+  #[case(&format!("{}x{}", "(λx.(x ".repeat(50_000), "))".repeat(50_000)), 150_001)]
+  fn deep_nesting(#[case] input: &str, #[case] size: usize) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(term.size(), size);
+    let owned = term.to_static();
+    assert!(owned.alpha_eq(&term));
+    assert_eq!(owned.to_string(), term.to_string());
+    assert!(!to_debruijn(&term).to_string().is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn never_panics() {
+    use crate::random::{Rng, Xorshift};
+
+    // mostly bytes the grammar uses, to get past the first token, and some of any kind
+    const BYTES: &[u8] = b"()\\.= \nxyz\xce\xbb";
+    let mut rng = Xorshift::from_seed(0x5eed);
+    for _ in 0..20_000 {
+      let len = rng.below(24);
+      let bytes: Vec<u8> = (0..len)
+        .map(|_| match rng.below(8) {
+          0 => rng.next_u64() as u8,
+          _ => BYTES[rng.below(BYTES.len())],
+        })
+        .collect();
+      let input = String::from_utf8_lossy(&bytes);
+      for result in [
+        Parser::new(&input).parse().map(|_| ()),
+        Parser::new(&input).parse_statement().map(|_| ()),
+      ] {
+        if let Err(err) = result {
+          assert!(err.downcast_ref::<ParserError>().is_some(), "{:?}", input);
+        }
+      }
+    }
   }
 }
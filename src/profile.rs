@@ -0,0 +1,304 @@
+//! Heap profiling, attributing allocations to the pass that made them
+//!
+//! [`Profiler`] is a global allocator which counts every allocation and free
+//! against the pass running on the current thread: parsing, evaluation, or
+//! anything else. Nodes dropped are counted the same way. A program opts in by
+//! installing the profiler, after which [`report`] can be read at any time:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static PROFILER: camel::profile::Profiler = camel::profile::Profiler;
+//! ```
+//!
+//! Counting alone cannot say which code a pass allocated from, so the profiler can
+//! also capture a backtrace every so many allocations, set with [`sample_every`].
+//! The report groups these by the first frame outside of allocation itself.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The parts of the interpreter allocations are attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pass {
+  Parse,
+  Eval,
+  /// Anywhere outside of the other passes
+  Other,
+}
+
+impl Pass {
+  const ALL: [Pass; 3] = [Pass::Parse, Pass::Eval, Pass::Other];
+}
+
+/// Counts for a single pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PassCounts {
+  pub allocations: usize,
+  pub frees: usize,
+  pub bytes_allocated: usize,
+  pub nodes_dropped: usize,
+}
+
+/// Everything counted since the program started
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+  pub passes: Vec<(Pass, PassCounts)>,
+  /// Sampled allocations for each pass and site, most frequent first
+  pub sites: Vec<(Pass, String, usize)>,
+}
+
+/// A global allocator counting allocations against the current pass, which
+/// otherwise leaves allocation to the system allocator
+pub struct Profiler;
+
+struct Counters {
+  allocations: AtomicUsize,
+  frees: AtomicUsize,
+  bytes_allocated: AtomicUsize,
+  nodes_dropped: AtomicUsize,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: Counters = Counters {
+  allocations: AtomicUsize::new(0),
+  frees: AtomicUsize::new(0),
+  bytes_allocated: AtomicUsize::new(0),
+  nodes_dropped: AtomicUsize::new(0),
+};
+
+static COUNTERS: [Counters; 3] = [ZERO; 3];
+static SAMPLE_EVERY: AtomicUsize = AtomicUsize::new(0);
+static SAMPLES: Mutex<Vec<(Pass, Backtrace)>> = Mutex::new(Vec::new());
+
+thread_local! {
+  static PASS: Cell<Pass> = const { Cell::new(Pass::Other) };
+  /// Set while the profiler itself is allocating, so that it does not count or
+  /// sample its own allocations
+  static BUSY: Cell<bool> = const { Cell::new(false) };
+}
+
+fn counters(pass: Pass) -> &'static Counters {
+  &COUNTERS[pass as usize]
+}
+
+fn current() -> Pass {
+  PASS.try_with(Cell::get).unwrap_or(Pass::Other)
+}
+
+/// Attribute allocations on this thread to `pass` until the guard is dropped
+pub fn enter(pass: Pass) -> PassGuard {
+  let previous = PASS
+    .try_with(|current| current.replace(pass))
+    .unwrap_or(Pass::Other);
+  PassGuard { previous }
+}
+
+/// Restores the pass that was running before `enter`
+pub struct PassGuard {
+  previous: Pass,
+}
+
+impl Drop for PassGuard {
+  fn drop(&mut self) {
+    let _ = PASS.try_with(|current| current.set(self.previous));
+  }
+}
+
+/// Capture a backtrace on every `n`th allocation of each pass, or never for 0
+pub fn sample_every(n: usize) {
+  SAMPLE_EVERY.store(n, Ordering::Relaxed);
+}
+
+pub(crate) fn node_dropped() {
+  counters(current())
+    .nodes_dropped
+    .fetch_add(1, Ordering::Relaxed);
+}
+
+/// The counts so far, and the sites of the sampled allocations
+pub fn report() -> Report {
+  let passes = Pass::ALL
+    .into_iter()
+    .map(|pass| {
+      let counters = counters(pass);
+      let counts = PassCounts {
+        allocations: counters.allocations.load(Ordering::Relaxed),
+        frees: counters.frees.load(Ordering::Relaxed),
+        bytes_allocated: counters.bytes_allocated.load(Ordering::Relaxed),
+        nodes_dropped: counters.nodes_dropped.load(Ordering::Relaxed),
+      };
+      (pass, counts)
+    })
+    .collect();
+  let _busy = Busy::try_enter();
+  let mut counts: HashMap<(Pass, String), usize> = HashMap::new();
+  for (pass, backtrace) in SAMPLES.lock().unwrap_or_else(|err| err.into_inner()).iter() {
+    *counts.entry((*pass, site(backtrace))).or_default() += 1;
+  }
+  let mut sites: Vec<_> = counts
+    .into_iter()
+    .map(|((pass, site), count)| (pass, site, count))
+    .collect();
+  sites.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+  Report { passes, sites }
+}
+
+/// The first function in a backtrace which is not part of allocating
+fn site(backtrace: &Backtrace) -> String {
+  // trait methods are written `<Type as Trait>::method`, named after the type
+  const SKIP: [&str; 6] = [
+    "std::",
+    "core::",
+    "alloc::",
+    "hashbrown::",
+    "camel::profile::",
+    "__rust",
+  ];
+  backtrace
+    .to_string()
+    .lines()
+    .filter_map(|line| Some(line.trim().split_once(": ")?.1.to_string()))
+    .find(|symbol| {
+      let path = symbol.trim_start_matches('<');
+      !SKIP.iter().any(|prefix| path.starts_with(prefix))
+    })
+    .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// Marks the current thread as busy in the profiler until dropped
+struct Busy;
+
+impl Busy {
+  /// `None` if the thread is already busy, or is being torn down
+  fn try_enter() -> Option<Busy> {
+    // a `Busy` made for nothing would clear the flag as soon as it was dropped
+    let entered = BUSY.try_with(|busy| !busy.replace(true)).ok()?;
+    entered.then(|| Busy)
+  }
+}
+
+impl Drop for Busy {
+  fn drop(&mut self) {
+    let _ = BUSY.try_with(|busy| busy.set(false));
+  }
+}
+
+impl Profiler {
+  fn record_alloc(&self, size: usize) {
+    let Some(_busy) = Busy::try_enter() else {
+      return;
+    };
+    let pass = current();
+    let counters = counters(pass);
+    let count = counters.allocations.fetch_add(1, Ordering::Relaxed) + 1;
+    counters.bytes_allocated.fetch_add(size, Ordering::Relaxed);
+    let every = SAMPLE_EVERY.load(Ordering::Relaxed);
+    if every != 0 && count.is_multiple_of(every) {
+      let backtrace = Backtrace::force_capture();
+      if let Ok(mut samples) = SAMPLES.lock() {
+        samples.push((pass, backtrace));
+      }
+    }
+  }
+
+  fn record_free(&self) {
+    if let Some(_busy) = Busy::try_enter() {
+      counters(current()).frees.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+}
+
+unsafe impl GlobalAlloc for Profiler {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    self.record_alloc(layout.size());
+    System.alloc(layout)
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    self.record_alloc(layout.size());
+    System.alloc_zeroed(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    self.record_free();
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    self.record_alloc(new_size);
+    self.record_free();
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+impl fmt::Display for Pass {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Pass::Parse => write!(f, "parse"),
+      Pass::Eval => write!(f, "eval"),
+      Pass::Other => write!(f, "other"),
+    }
+  }
+}
+
+impl fmt::Display for Report {
+  /// A table of the counts for each pass, then the sampled sites
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(
+      f,
+      "{:<8} {:>12} {:>12} {:>14} {:>12}",
+      "pass", "allocations", "frees", "bytes", "nodes freed"
+    )?;
+    for (pass, counts) in &self.passes {
+      writeln!(
+        f,
+        "{:<8} {:>12} {:>12} {:>14} {:>12}",
+        pass.to_string(),
+        counts.allocations,
+        counts.frees,
+        counts.bytes_allocated,
+        counts.nodes_dropped
+      )?;
+    }
+    if !self.sites.is_empty() {
+      writeln!(f, "\nsampled allocation sites")?;
+    }
+    for (pass, site, count) in &self.sites {
+      writeln!(f, "{:>8}  {:<8} {}", count, pass.to_string(), site)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+
+  fn nodes_dropped(pass: Pass) -> usize {
+    counters(pass).nodes_dropped.load(Ordering::Relaxed)
+  }
+
+  #[test]
+  fn counts_against_the_current_pass() -> Result<(), anyhow::Error> {
+    let term = Parser::new("λx.x (y z)").parse()?;
+    let before = nodes_dropped(Pass::Eval);
+    {
+      let _eval = enter(Pass::Eval);
+      {
+        let _parse = enter(Pass::Parse);
+        assert_eq!(current(), Pass::Parse);
+      }
+      assert_eq!(current(), Pass::Eval);
+      drop(term);
+    }
+    assert_eq!(current(), Pass::Other);
+    assert!(nodes_dropped(Pass::Eval) >= before + 5);
+    Ok(())
+  }
+}
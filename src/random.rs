@@ -0,0 +1,158 @@
+//! Seeded randomness, and random terms built from it
+//!
+//! Everything random in camel draws from an [`Rng`] passed in by the caller rather
+//! than from a source of its own, so any run can be repeated by seeding the same
+//! generator the same way. [`Xorshift`] is the generator used when there is no
+//! reason to pick another, and any other source can be plugged in by implementing
+//! [`Rng`] for it.
+//!
+//! ```
+//! # use camel::random::{closed_term, Xorshift};
+//! let a = closed_term(&mut Xorshift::from_seed(7), 12);
+//! let b = closed_term(&mut Xorshift::from_seed(7), 12);
+//! assert_eq!(a, b);
+//! assert!(a.is_closed());
+//! ```
+
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+/// A source of random numbers
+pub trait Rng {
+  fn next_u64(&mut self) -> u64;
+
+  /// A number below `bound`, which must not be zero
+  fn below(&mut self, bound: usize) -> usize {
+    (self.next_u64() % bound as u64) as usize
+  }
+}
+
+/// A xorshift generator, which is plenty for picking terms and keeps a run
+/// reproducible from its seed
+#[derive(Debug, Clone)]
+pub struct Xorshift {
+  seed: u64,
+  state: u64,
+}
+
+impl Xorshift {
+  pub fn from_seed(seed: u64) -> Self {
+    Xorshift {
+      seed,
+      // the state must not be zero, and nearby seeds should not start out alike
+      state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+    }
+  }
+
+  /// The seed this generator started from, to be reported so that a run can be
+  /// repeated
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+}
+
+impl Rng for Xorshift {
+  fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state << 13;
+    self.state ^= self.state >> 7;
+    self.state ^= self.state << 17;
+    self.state
+  }
+}
+
+/// A seed taken from the clock, for runs which were not given one
+pub fn seed_from_clock() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// A closed term of roughly `size` nodes, built in prefix order with binders named
+/// after their depth
+pub fn closed_term(rng: &mut impl Rng, size: usize) -> Rc<Node<'static>> {
+  enum Frame {
+    Body,
+    Lhs,
+    Rhs(Rc<Node<'static>>),
+  }
+
+  let name = |depth: usize| Cow::Owned(format!("x{}", depth));
+  let mut budget = size.max(1);
+  let mut depth = 0;
+  let mut stack = Vec::new();
+  loop {
+    // subterms still to be started, each of which needs at least one node
+    let holes = 1
+      + stack
+        .iter()
+        .filter(|frame| matches!(frame, Frame::Lhs))
+        .count();
+    budget = budget.saturating_sub(1);
+    // with no binder in scope yet there is no variable to pick
+    let choice = if depth == 0 { 0 } else { rng.below(3) };
+    let mut done = match choice {
+      0 if depth == 0 || budget > holes => {
+        depth += 1;
+        stack.push(Frame::Body);
+        continue;
+      }
+      1 if budget > holes => {
+        stack.push(Frame::Lhs);
+        continue;
+      }
+      _ => Rc::new(Node::Identifier(Identifier {
+        name: name(rng.below(depth)),
+      })),
+    };
+    loop {
+      done = match stack.pop() {
+        None => return done,
+        Some(Frame::Lhs) => {
+          stack.push(Frame::Rhs(done));
+          break;
+        }
+        Some(Frame::Rhs(lhs)) => Rc::new(Node::Application(Application { lhs, rhs: done })),
+        Some(Frame::Body) => {
+          depth -= 1;
+          Rc::new(Node::Abstraction(Abstraction {
+            param: name(depth),
+            body: done,
+          }))
+        }
+      };
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[test]
+  fn same_seed_same_numbers() {
+    let mut a = Xorshift::from_seed(42);
+    let mut b = Xorshift::from_seed(42);
+    let mut c = Xorshift::from_seed(43);
+    let first: Vec<_> = (0..8).map(|_| a.next_u64()).collect();
+    assert_eq!(first, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+    assert_ne!(first, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+    assert_eq!(a.seed(), 42);
+  }
+
+  #[rstest]
+  #[case(1)]
+  #[case(10)]
+  #[case(100)]
+  fn terms_are_closed_and_bounded(#[case] size: usize) {
+    let mut rng = Xorshift::from_seed(size as u64);
+    for _ in 0..50 {
+      let term = closed_term(&mut rng, size);
+      assert!(term.is_closed(), "{} is open", term);
+      assert!(term.size() <= size.max(2), "{} is too big", term);
+    }
+  }
+}
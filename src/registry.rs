@@ -0,0 +1,341 @@
+//! Registries of evaluation strategies and term formats, open to other crates
+//!
+//! A [`Registry`] starts out with camel's own strategies and formats, and a crate
+//! building on camel registers its own alongside them, looking them all up by
+//! name. Registering under a name already taken replaces what was there, so the
+//! built in ones can be overridden too.
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::ast::Node;
+//! # use camel::registry::{BackEnd, Registry};
+//! /// Writes the number of nodes in a term
+//! struct Size;
+//!
+//! impl BackEnd for Size {
+//!   fn name(&self) -> &str {
+//!     "size"
+//!   }
+//!
+//!   fn write(&self, node: &Rc<Node<'_>>) -> Result<Vec<u8>, anyhow::Error> {
+//!     Ok(node.size().to_string().into_bytes())
+//!   }
+//! }
+//!
+//! let mut registry = Registry::builtin();
+//! registry.register_back_end(Size);
+//! let term = registry.front_end("text").unwrap().read(b"f (g x)")?;
+//! assert_eq!(registry.back_end("size").unwrap().write(&term)?, b"5");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::rc::Rc;
+
+use crate::ast::Node;
+use crate::capabilities::Capabilities;
+use crate::eval::{EvalError, EvalStrategy, Evaluator};
+use crate::parser::Parser;
+#[cfg(feature = "formats")]
+use crate::{binary, blc, json, sexpr};
+
+/// A way of evaluating terms
+pub trait Strategy {
+  fn name(&self) -> &str;
+
+  /// Evaluate `node`, calling `evaluator.count_beta()` before every beta reduction
+  /// so that the limits of the evaluator hold
+  fn eval<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    evaluator: &Evaluator<'_>,
+  ) -> Result<Rc<Node<'inp>>, EvalError>;
+}
+
+/// A format terms can be read from
+pub trait FrontEnd {
+  fn name(&self) -> &str;
+
+  fn read(&self, input: &[u8]) -> Result<Rc<Node<'static>>, anyhow::Error>;
+}
+
+/// A format terms can be written to
+pub trait BackEnd {
+  fn name(&self) -> &str;
+
+  fn write(&self, node: &Rc<Node<'_>>) -> Result<Vec<u8>, anyhow::Error>;
+
+  /// Whether what is written is raw bytes rather than a line of text, so that it
+  /// is not followed by a newline
+  fn is_binary(&self) -> bool {
+    false
+  }
+}
+
+/// Strategies, front ends and back ends, each kept in the order registered
+#[derive(Default)]
+pub struct Registry {
+  strategies: Vec<Rc<dyn Strategy>>,
+  front_ends: Vec<Rc<dyn FrontEnd>>,
+  back_ends: Vec<Rc<dyn BackEnd>>,
+}
+
+impl Registry {
+  /// A registry with nothing in it
+  pub fn new() -> Self {
+    Registry::default()
+  }
+
+  /// A registry with every strategy and format camel comes with
+  pub fn builtin() -> Self {
+    let mut registry = Registry::new();
+    for &strategy in EvalStrategy::ALL {
+      registry.register_strategy(strategy);
+    }
+    for &format in Format::ALL {
+      registry.register_front_end(format);
+      registry.register_back_end(format);
+    }
+    registry
+  }
+
+  pub fn register_strategy(&mut self, strategy: impl Strategy + 'static) -> &mut Self {
+    insert(&mut self.strategies, Rc::new(strategy), |s| s.name());
+    self
+  }
+
+  pub fn register_front_end(&mut self, front_end: impl FrontEnd + 'static) -> &mut Self {
+    insert(&mut self.front_ends, Rc::new(front_end), |f| f.name());
+    self
+  }
+
+  pub fn register_back_end(&mut self, back_end: impl BackEnd + 'static) -> &mut Self {
+    insert(&mut self.back_ends, Rc::new(back_end), |b| b.name());
+    self
+  }
+
+  pub fn strategy(&self, name: &str) -> Option<&dyn Strategy> {
+    let found = self.strategies.iter().find(|s| s.name() == name)?;
+    Some(&**found)
+  }
+
+  pub fn front_end(&self, name: &str) -> Option<&dyn FrontEnd> {
+    let found = self.front_ends.iter().find(|f| f.name() == name)?;
+    Some(&**found)
+  }
+
+  pub fn back_end(&self, name: &str) -> Option<&dyn BackEnd> {
+    let found = self.back_ends.iter().find(|b| b.name() == name)?;
+    Some(&**found)
+  }
+
+  /// What the build supports with this registry's strategies and formats
+  pub fn capabilities(&self) -> Capabilities {
+    Capabilities {
+      strategies: self
+        .strategies
+        .iter()
+        .map(|s| s.name().to_string())
+        .collect(),
+      front_ends: self
+        .front_ends
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect(),
+      back_ends: self
+        .back_ends
+        .iter()
+        .map(|b| b.name().to_string())
+        .collect(),
+      ..Capabilities::build()
+    }
+  }
+}
+
+/// Add `item` to `items`, in place of one with the same name if there is one
+fn insert<T: ?Sized>(items: &mut Vec<Rc<T>>, item: Rc<T>, name: impl Fn(&T) -> &str) {
+  match items.iter().position(|old| name(old) == name(&item)) {
+    Some(index) => items[index] = item,
+    None => items.push(item),
+  }
+}
+
+impl Strategy for EvalStrategy {
+  fn name(&self) -> &str {
+    EvalStrategy::name(*self)
+  }
+
+  fn eval<'inp>(
+    &self,
+    node: Rc<Node<'inp>>,
+    evaluator: &Evaluator<'_>,
+  ) -> Result<Rc<Node<'inp>>, EvalError> {
+    evaluator.reduce(*self, node)
+  }
+}
+
+/// The formats camel comes with, each read and written by its own module, all but
+/// text with the `formats` feature
+#[derive(Debug, Clone, Copy)]
+enum Format {
+  Text,
+  #[cfg(feature = "formats")]
+  Json,
+  #[cfg(feature = "formats")]
+  Binary,
+  #[cfg(feature = "formats")]
+  Blc,
+  #[cfg(feature = "formats")]
+  Sexpr,
+}
+
+impl Format {
+  const ALL: &'static [Format] = &[
+    Format::Text,
+    #[cfg(feature = "formats")]
+    Format::Json,
+    #[cfg(feature = "formats")]
+    Format::Binary,
+    #[cfg(feature = "formats")]
+    Format::Blc,
+    #[cfg(feature = "formats")]
+    Format::Sexpr,
+  ];
+
+  fn name(self) -> &'static str {
+    match self {
+      Format::Text => "text",
+      #[cfg(feature = "formats")]
+      Format::Json => "json",
+      #[cfg(feature = "formats")]
+      Format::Binary => "binary",
+      #[cfg(feature = "formats")]
+      Format::Blc => "blc",
+      #[cfg(feature = "formats")]
+      Format::Sexpr => "sexpr",
+    }
+  }
+}
+
+impl FrontEnd for Format {
+  fn name(&self) -> &str {
+    Format::name(*self)
+  }
+
+  fn read(&self, input: &[u8]) -> Result<Rc<Node<'static>>, anyhow::Error> {
+    let text = || std::str::from_utf8(input).map(str::trim);
+    Ok(match self {
+      Format::Text => Rc::new(Parser::new(text()?).parse()?.to_static()),
+      #[cfg(feature = "formats")]
+      Format::Json => json::from_json(text()?)?,
+      #[cfg(feature = "formats")]
+      Format::Binary => binary::decode(input)?,
+      #[cfg(feature = "formats")]
+      Format::Blc => blc::read(text()?)?,
+      #[cfg(feature = "formats")]
+      Format::Sexpr => sexpr::read(text()?)?,
+    })
+  }
+}
+
+impl BackEnd for Format {
+  fn name(&self) -> &str {
+    Format::name(*self)
+  }
+
+  fn write(&self, node: &Rc<Node<'_>>) -> Result<Vec<u8>, anyhow::Error> {
+    Ok(match self {
+      Format::Text => node.to_string().into_bytes(),
+      #[cfg(feature = "formats")]
+      Format::Json => json::to_json(node).into_bytes(),
+      #[cfg(feature = "formats")]
+      Format::Binary => binary::encode(node),
+      #[cfg(feature = "formats")]
+      Format::Blc => blc::write(node)?.into_bytes(),
+      #[cfg(feature = "formats")]
+      Format::Sexpr => sexpr::write(node)?.into_bytes(),
+    })
+  }
+
+  fn is_binary(&self) -> bool {
+    #[cfg(feature = "formats")]
+    if let Format::Binary = self {
+      return true;
+    }
+    false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Reduces only the redex at the root, if there is one
+  struct Once;
+
+  impl Strategy for Once {
+    fn name(&self) -> &str {
+      "once"
+    }
+
+    fn eval<'inp>(
+      &self,
+      node: Rc<Node<'inp>>,
+      evaluator: &Evaluator<'_>,
+    ) -> Result<Rc<Node<'inp>>, EvalError> {
+      if !node.is_redex() {
+        return Ok(node);
+      }
+      evaluator.count_beta()?;
+      Ok(crate::eval::reduce_at(&node, &Default::default()).expect("a redex"))
+    }
+  }
+
+  #[test]
+  fn registers_strategies() -> Result<(), anyhow::Error> {
+    let mut registry = Registry::builtin();
+    registry.register_strategy(Once);
+    let term = registry
+      .front_end("text")
+      .unwrap()
+      .read("(λx.x x) (λy.y)".as_bytes())?;
+    let evaluator = Evaluator::default().with_fuel(0);
+    let once = registry.strategy("once").unwrap();
+    assert_eq!(
+      evaluator.eval_with(once, Rc::clone(&term)),
+      Err(EvalError::StepLimitExceeded)
+    );
+    let evaluator = Evaluator::default();
+    let result = evaluator.eval_with(once, Rc::clone(&term))?;
+    assert_eq!(result.to_string(), "(λy. y) (λy. y)");
+    assert_eq!(evaluator.stats().beta_reductions, 1);
+    let normal = registry.strategy("NormalOrder").unwrap();
+    assert_eq!(evaluator.eval_with(normal, term)?.to_string(), "(λy. y)");
+    assert!(registry
+      .capabilities()
+      .strategies
+      .ends_with(&["once".to_string()]));
+    Ok(())
+  }
+
+  #[test]
+  #[cfg(feature = "formats")]
+  fn formats_round_trip() -> Result<(), anyhow::Error> {
+    let registry = Registry::builtin();
+    let term = registry.front_end("text").unwrap().read(b"\\x.\\y.x y")?;
+    for name in ["text", "json", "binary", "blc", "sexpr"] {
+      let written = registry.back_end(name).unwrap().write(&term)?;
+      let read = registry.front_end(name).unwrap().read(&written)?;
+      assert!(read.alpha_eq(&term), "{}", name);
+    }
+    assert!(registry.front_end("yaml").is_none());
+    Ok(())
+  }
+
+  #[test]
+  fn replaces_by_name() {
+    let mut registry = Registry::new();
+    registry.register_front_end(Format::Text);
+    registry.register_front_end(Format::Text);
+    assert_eq!(registry.capabilities().front_ends, ["text"]);
+  }
+}
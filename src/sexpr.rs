@@ -0,0 +1,357 @@
+//! Reading and writing terms as S-expressions, for tools from the Lisp family
+//!
+//! | term          | S-expression                   |
+//! |---------------|--------------------------------|
+//! | `λx. M`       | `(lambda (x) M)`               |
+//! | `λx.λy. M`    | `(lambda (x y) M)`             |
+//! | `M N`         | `(M N)`                        |
+//! | `M N O`       | `(M N O)`, the same as `((M N) O)` |
+//! | `x`           | `x`                            |
+//!
+//! A variable is any run of characters other than whitespace and brackets, except
+//! `lambda` itself, and a term using any other name, such as `lambda`, cannot be
+//! written. Written terms use the shortest of the equivalent forms, with the
+//! parameters of nested abstractions in a single list and applications to several
+//! arguments in a single list. Both directions keep their own stacks.
+//!
+//! ```
+//! # use camel::sexpr;
+//! # use camel::parser::Parser;
+//! let term = sexpr::read("(lambda (f x) (f (f x)))")?;
+//! assert_eq!(term.to_string(), "(λf. (λx. f (f x)))");
+//! assert_eq!(sexpr::write(&Parser::new("λx.x x y").parse()?)?, "(lambda (x) (x x y))");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::{Abstraction, Application, Identifier, Node};
+
+const LAMBDA: &str = "lambda";
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SexprError {
+  #[error("Unexpected '{found}' at {offset}, expected {expected}")]
+  Unexpected {
+    found: String,
+    offset: usize,
+    expected: &'static str,
+  },
+
+  #[error("Unexpected end of input, expected {0}")]
+  UnexpectedEnd(&'static str),
+
+  #[error("Application at {0} needs a function and at least one argument")]
+  TooFewItems(usize),
+
+  #[error("Abstraction at {0} has no parameters")]
+  NoParameters(usize),
+
+  #[error("Trailing input at {0}")]
+  TrailingInput(usize),
+
+  #[error("'{0}' cannot be written as a variable")]
+  InvalidName(String),
+}
+
+/// Write a term as an S-expression, on a single line, failing on a name which
+/// would not be read back as the same variable
+pub fn write(node: &Node<'_>) -> Result<String, SexprError> {
+  enum Visit<'n> {
+    Enter(&'n Node<'n>),
+    Text(&'static str),
+  }
+
+  let mut sexpr = String::new();
+  let mut stack = vec![Visit::Enter(node)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(Node::Abstraction(abs)) => {
+        sexpr.push_str("(lambda (");
+        sexpr.push_str(atom(&abs.param)?);
+        let mut body = &abs.body;
+        while let Node::Abstraction(inner) = &**body {
+          sexpr.push(' ');
+          sexpr.push_str(atom(&inner.param)?);
+          body = &inner.body;
+        }
+        sexpr.push_str(") ");
+        stack.push(Visit::Text(")"));
+        stack.push(Visit::Enter(body));
+      }
+      Visit::Enter(Node::Application(app)) => {
+        sexpr.push('(');
+        stack.push(Visit::Text(")"));
+        let mut head = app;
+        loop {
+          stack.push(Visit::Enter(&head.rhs));
+          stack.push(Visit::Text(" "));
+          match &*head.lhs {
+            Node::Application(inner) => head = inner,
+            lhs => break stack.push(Visit::Enter(lhs)),
+          }
+        }
+      }
+      Visit::Enter(Node::Identifier(id)) => sexpr.push_str(atom(&id.name)?),
+      Visit::Text(text) => sexpr.push_str(text),
+    }
+  }
+  Ok(sexpr)
+}
+
+/// `name`, if it is read back as a single variable
+fn atom(name: &str) -> Result<&str, SexprError> {
+  let mut tokens = Tokens {
+    text: name,
+    offset: 0,
+  };
+  match (tokens.next(), tokens.next()) {
+    (Some((0, Token::Atom(atom))), None) if atom == name && atom != LAMBDA => Ok(name),
+    _ => Err(SexprError::InvalidName(name.to_string())),
+  }
+}
+
+/// Read a single term written as an S-expression
+pub fn read(text: &str) -> Result<Rc<Node<'static>>, SexprError> {
+  /// A list whose closing bracket is still to come
+  enum Frame {
+    Application {
+      offset: usize,
+      items: Vec<Rc<Node<'static>>>,
+    },
+    Abstraction {
+      params: Vec<String>,
+    },
+  }
+
+  let mut tokens = Tokens { text, offset: 0 };
+  let mut stack = Vec::new();
+  loop {
+    // a term starts here
+    let (offset, token) = tokens.next().ok_or(SexprError::UnexpectedEnd("a term"))?;
+    let mut done = match token {
+      Token::Open if tokens.peek() == Some(Token::Atom(LAMBDA)) => {
+        tokens.next();
+        tokens.expect_open("a list of parameters")?;
+        let mut params = Vec::new();
+        loop {
+          match tokens.next() {
+            Some((_, Token::Close)) => break,
+            Some((_, Token::Atom(param))) if param != LAMBDA => params.push(param.to_string()),
+            Some((offset, token)) => return Err(token.unexpected(offset, "a parameter or ')'")),
+            None => return Err(SexprError::UnexpectedEnd("a parameter or ')'")),
+          }
+        }
+        if params.is_empty() {
+          return Err(SexprError::NoParameters(offset));
+        }
+        stack.push(Frame::Abstraction { params });
+        continue;
+      }
+      Token::Open => {
+        stack.push(Frame::Application {
+          offset,
+          items: Vec::new(),
+        });
+        continue;
+      }
+      Token::Atom(name) if name != LAMBDA => Rc::new(Node::Identifier(Identifier {
+        name: Cow::Owned(name.to_string()),
+      })),
+      token => return Err(token.unexpected(offset, "a term")),
+    };
+    // hand the term to the list it is in, closing each list it completes
+    loop {
+      match stack.pop() {
+        None => {
+          return match tokens.next() {
+            None => Ok(done),
+            Some((offset, _)) => Err(SexprError::TrailingInput(offset)),
+          }
+        }
+        Some(Frame::Application { offset, mut items }) => {
+          items.push(done);
+          if tokens.peek() != Some(Token::Close) {
+            stack.push(Frame::Application { offset, items });
+            break;
+          }
+          tokens.next();
+          let mut items = items.into_iter();
+          let (Some(head), Some(first)) = (items.next(), items.next()) else {
+            return Err(SexprError::TooFewItems(offset));
+          };
+          done = std::iter::once(first).chain(items).fold(head, |lhs, rhs| {
+            Rc::new(Node::Application(Application { lhs, rhs }))
+          });
+        }
+        Some(Frame::Abstraction { params }) => {
+          match tokens.next() {
+            Some((_, Token::Close)) => (),
+            Some((offset, token)) => return Err(token.unexpected(offset, "')'")),
+            None => return Err(SexprError::UnexpectedEnd("')'")),
+          }
+          done = params.into_iter().rev().fold(done, |body, param| {
+            Rc::new(Node::Abstraction(Abstraction {
+              param: Cow::Owned(param),
+              body,
+            }))
+          });
+        }
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'t> {
+  Open,
+  Close,
+  Atom(&'t str),
+}
+
+impl Token<'_> {
+  fn unexpected(self, offset: usize, expected: &'static str) -> SexprError {
+    let found = match self {
+      Token::Open => "(",
+      Token::Close => ")",
+      Token::Atom(atom) => atom,
+    };
+    SexprError::Unexpected {
+      found: found.to_string(),
+      offset,
+      expected,
+    }
+  }
+}
+
+/// Splits the input into brackets and atoms, each with its byte offset
+#[derive(Clone)]
+struct Tokens<'t> {
+  text: &'t str,
+  offset: usize,
+}
+
+impl<'t> Tokens<'t> {
+  fn peek(&self) -> Option<Token<'t>> {
+    self.clone().next().map(|(_, token)| token)
+  }
+
+  fn expect_open(&mut self, expected: &'static str) -> Result<(), SexprError> {
+    match self.next() {
+      Some((_, Token::Open)) => Ok(()),
+      Some((offset, token)) => Err(token.unexpected(offset, expected)),
+      None => Err(SexprError::UnexpectedEnd(expected)),
+    }
+  }
+}
+
+impl<'t> Iterator for Tokens<'t> {
+  type Item = (usize, Token<'t>);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let rest = &self.text[self.offset..];
+    let start = self.offset + (rest.len() - rest.trim_start().len());
+    let rest = &self.text[start..];
+    let (token, len) = match rest.chars().next()? {
+      '(' => (Token::Open, 1),
+      ')' => (Token::Close, 1),
+      _ => {
+        let len = rest
+          .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+          .unwrap_or(rest.len());
+        (Token::Atom(&rest[..len]), len)
+      }
+    };
+    self.offset = start + len;
+    Some((start, token))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", "x")]
+  #[case("λx.x x", "(lambda (x) (x x))")]
+  #[case("λx.λy.x", "(lambda (x y) x)")]
+  #[case("f a b (g c)", "(f a b (g c))")]
+  #[case("f (a b)", "(f (a b))")]
+  #[case("(λx.x) (λy.λz.z)", "((lambda (x) x) (lambda (y z) z))")]
+  fn writes_and_reads(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    let term = Parser::new(input).parse()?;
+    assert_eq!(write(&term)?, expected);
+    assert_eq!(*read(expected)?, term);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("  ( lambda(x)\n(x x) ) ", "(λx. x x)")]
+  #[case("((f a) b)", "f a b")]
+  #[case("(Foo-bar? 1)", "Foo-bar? 1")]
+  fn reads_any_layout(#[case] input: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+    assert_eq!(read(input)?.to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn deep_terms() -> Result<(), anyhow::Error> {
+    let source = format!("{}x{}", "λx.y (".repeat(100_000), ")".repeat(100_000));
+    let term = Parser::new(&source).parse()?;
+    let sexpr = write(&term)?;
+    let back = read(&sexpr)?;
+    assert_eq!(write(&back)?, sexpr);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("", SexprError::UnexpectedEnd("a term"))]
+  #[case("(f x", SexprError::UnexpectedEnd("a term"))]
+  #[case("x y", SexprError::TrailingInput(2))]
+  #[case(")", SexprError::Unexpected { found: ")".to_string(), offset: 0, expected: "a term" })]
+  #[case("lambda", SexprError::Unexpected { found: "lambda".to_string(), offset: 0, expected: "a term" })]
+  #[case("(f)", SexprError::TooFewItems(0))]
+  #[case(" ()", SexprError::Unexpected { found: ")".to_string(), offset: 2, expected: "a term" })]
+  #[case("(lambda () x)", SexprError::NoParameters(0))]
+  #[case("(lambda x x)", SexprError::Unexpected { found: "x".to_string(), offset: 8, expected: "a list of parameters" })]
+  #[case("(lambda (x) x y)", SexprError::Unexpected { found: "y".to_string(), offset: 14, expected: "')'" })]
+  fn invalid_sexprs(#[case] input: &str, #[case] expected: SexprError) {
+    assert_eq!(read(input), Err(expected));
+  }
+
+  #[rstest]
+  #[case("lambda")]
+  #[case("")]
+  #[case("f x")]
+  #[case("(x)")]
+  #[case(" x")]
+  fn unwritable_names(#[case] name: &str) {
+    let term = Node::Abstraction(Abstraction {
+      param: Cow::Borrowed(name),
+      body: Rc::new(Node::Identifier(Identifier {
+        name: Cow::Borrowed(name),
+      })),
+    });
+    assert_eq!(write(&term), Err(SexprError::InvalidName(name.to_string())));
+  }
+
+  #[test]
+  fn round_trips_every_writable_term() -> Result<(), anyhow::Error> {
+    for source in ["λlambdax.lambdax", "x lambda1", "λfoo.bar"] {
+      let term = Parser::new(source).parse()?;
+      assert!(read(&write(&term)?)?.alpha_eq(&term), "{}", source);
+    }
+    let term = Parser::new("λlambda.lambda").parse()?;
+    assert_eq!(
+      write(&term),
+      Err(SexprError::InvalidName("lambda".to_string()))
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,187 @@
+//! Cutting a term down to a smaller one that still shows the same failure
+//!
+//! When some property fails on a large term, such as two evaluation strategies
+//! disagreeing on it, the cause is usually easier to see in a small term with the
+//! same failure. [`shrink`] searches for one by repeatedly trying smaller variants
+//! of the term, and keeping the first that still fails:
+//!
+//! ```
+//! # use std::rc::Rc;
+//! # use camel::parser::Parser;
+//! # use camel::shrink::shrink;
+//! let term = Rc::new(Parser::new("λa.(λx.x x) (λy.y y) a").parse()?);
+//! // any term applying some term to itself "fails"
+//! let smallest = shrink(term, |term| term.to_string().contains("x x"));
+//! assert_eq!(smallest.to_string(), "(λx. x x)");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::ast::{Direction, Identifier, Node, Path};
+use crate::eval::reduce_at;
+
+/// The smallest term found which still satisfies `fails`, starting from `term`,
+/// which is taken to satisfy it already
+///
+/// Every variant tried has strictly fewer nodes than the term it came from, and no
+/// free variables the original term did not have, so a closed term only ever
+/// shrinks to closed terms. The variants of a term are, smallest first:
+///
+/// - each of its subterms without new free variables, in place of the whole term
+/// - each subterm replaced by one of its own children or by a variable in scope
+/// - each redex contracted, where that makes the term smaller
+///
+/// The search is greedy, so the result is a local minimum: none of its own variants
+/// fail, though some smaller unrelated term might
+pub fn shrink<'inp>(
+  mut term: Rc<Node<'inp>>,
+  mut fails: impl FnMut(&Rc<Node<'inp>>) -> bool,
+) -> Rc<Node<'inp>> {
+  let allowed: HashSet<String> = term.free_vars().into_iter().map(str::to_string).collect();
+  loop {
+    let mut candidates = variants(&term);
+    candidates.retain(|candidate| {
+      candidate
+        .free_vars()
+        .iter()
+        .all(|name| allowed.contains(*name))
+    });
+    candidates.sort_by_key(|candidate| candidate.size());
+    match candidates.into_iter().find(|candidate| fails(candidate)) {
+      Some(smaller) => term = smaller,
+      None => return term,
+    }
+  }
+}
+
+/// Variants of `term` with fewer nodes, which may have new free variables
+fn variants<'inp>(term: &Rc<Node<'inp>>) -> Vec<Rc<Node<'inp>>> {
+  enum Visit<'n, 'inp> {
+    Enter(&'n Rc<Node<'inp>>),
+    Descend(Direction),
+    Ascend,
+    Unbind,
+  }
+
+  let size = term.size();
+  let mut variants = Vec::new();
+  let mut path = Vec::new();
+  // parameters of the binders around the current subterm, innermost last
+  let mut binders: Vec<&Cow<'inp, str>> = Vec::new();
+  let mut stack = vec![Visit::Enter(term)];
+  while let Some(visit) = stack.pop() {
+    match visit {
+      Visit::Enter(node) => {
+        if !path.is_empty() {
+          variants.push(Rc::clone(node));
+        }
+        let mut replacements = Vec::new();
+        for direction in [Direction::Body, Direction::Lhs, Direction::Rhs] {
+          if let Some(child) = node.child(direction) {
+            replacements.push(Rc::clone(child));
+          }
+        }
+        let innermost = binders
+          .last()
+          .filter(|_| !matches!(**node, Node::Identifier(..)));
+        if let Some(param) = innermost {
+          replacements.push(Rc::new(Node::Identifier(Identifier {
+            name: (*param).clone(),
+          })));
+        }
+        let at = Path(path.clone());
+        for replacement in replacements {
+          let variant = term.replace_at(&at, replacement);
+          variants.push(variant.expect("the path leads into the term"));
+        }
+        if let Some(reduced) = reduce_at(term, &at).filter(|reduced| reduced.size() < size) {
+          variants.push(reduced);
+        }
+
+        if let Node::Abstraction(abs) = &**node {
+          binders.push(&abs.param);
+          stack.push(Visit::Unbind);
+        }
+        for direction in [Direction::Rhs, Direction::Lhs, Direction::Body] {
+          if let Some(child) = node.child(direction) {
+            stack.extend([
+              Visit::Ascend,
+              Visit::Enter(child),
+              Visit::Descend(direction),
+            ]);
+          }
+        }
+      }
+      Visit::Descend(direction) => path.push(direction),
+      Visit::Ascend => {
+        path.pop();
+      }
+      Visit::Unbind => {
+        binders.pop();
+      }
+    }
+  }
+  variants
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("λa.λb.(λx.x x) b", "(λx. x x)")]
+  #[case("(λz.z) (λa.λb.a (b b))", "(λb. b b)")]
+  #[case("λf.f (f (λx.x x))", "(λf. f f)")]
+  fn shrinks_to_smallest_failing(
+    #[case] input: &str,
+    #[case] expected: &str,
+  ) -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new(input).parse()?);
+    let self_application = |term: &Rc<Node>| {
+      let mut stack = vec![&**term];
+      while let Some(node) = stack.pop() {
+        match node {
+          Node::Application(app) => {
+            if matches!((&*app.lhs, &*app.rhs), (Node::Identifier(a), Node::Identifier(b)) if a == b)
+            {
+              return true;
+            }
+            stack.extend([&*app.lhs, &*app.rhs]);
+          }
+          Node::Abstraction(abs) => stack.push(&abs.body),
+          Node::Identifier(..) => (),
+        }
+      }
+      false
+    };
+    assert_eq!(shrink(term, self_application).to_string(), expected);
+    Ok(())
+  }
+
+  #[test]
+  fn closed_terms_stay_closed() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("λx.λy.x (y y) x").parse()?);
+    let mut tried = Vec::new();
+    let smallest = shrink(term, |term| {
+      tried.push(term.to_string());
+      assert!(term.is_closed(), "{} is open", term);
+      true
+    });
+    assert!(!tried.is_empty());
+    assert_eq!(smallest.size(), 2);
+    Ok(())
+  }
+
+  #[test]
+  fn keeps_term_when_nothing_smaller_fails() -> Result<(), anyhow::Error> {
+    let term = Rc::new(Parser::new("(λx.x) y").parse()?);
+    let smallest = shrink(Rc::clone(&term), |candidate| candidate == &term);
+    assert!(Rc::ptr_eq(&smallest, &term));
+    Ok(())
+  }
+}
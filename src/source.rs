@@ -0,0 +1,247 @@
+//! Source text, and conversions between byte offsets and lines and columns
+//!
+//! Programs spanning several files keep them in a [`SourceMap`], where each file
+//! gets a [`FileId`] and locations are [`Span`]s of bytes within one file.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A line and column in the input, both counted from 1, with columns in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+  pub line: usize,
+  pub column: usize,
+}
+
+impl fmt::Display for Position {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.line, self.column)
+  }
+}
+
+/// The text of a program along with an index of where its lines start
+///
+/// Terms parsed from `text` borrow their names from it, so a source file has to
+/// outlive everything parsed out of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+  name: String,
+  text: String,
+  /// Byte offset at which each line starts, the first always being 0
+  line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+  pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+    let text = text.into();
+    let line_starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    SourceFile {
+      name: name.into(),
+      text,
+      line_starts,
+    }
+  }
+
+  /// Read a file, naming the source after its path
+  pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+    let path = path.as_ref();
+    Ok(SourceFile::new(
+      path.display().to_string(),
+      fs::read_to_string(path)?,
+    ))
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn line_count(&self) -> usize {
+    self.line_starts.len()
+  }
+
+  /// The text of a line, counted from 1, without its line ending
+  pub fn line(&self, line: usize) -> Option<&str> {
+    let start = *self.line_starts.get(line.checked_sub(1)?)?;
+    let end = self
+      .line_starts
+      .get(line)
+      .map_or(self.text.len(), |&next| next - 1);
+    let text = &self.text[start..end];
+    Some(text.strip_suffix('\r').unwrap_or(text))
+  }
+
+  /// Every line along with its number
+  pub fn lines(&self) -> impl Iterator<Item = (usize, &str)> {
+    (1..=self.line_count()).filter_map(|line| Some((line, self.line(line)?)))
+  }
+
+  /// The line and column of a byte offset, which must lie on a character boundary
+  /// within the text or at its end
+  pub fn position(&self, offset: usize) -> Position {
+    let line = self.line_starts.partition_point(|&start| start <= offset);
+    let start = self.line_starts[line - 1];
+    Position {
+      line,
+      column: self.text[start..offset].chars().count() + 1,
+    }
+  }
+
+  /// The byte offset of a position, if it lies within the text
+  pub fn offset(&self, position: Position) -> Option<usize> {
+    let line = self.line(position.line)?;
+    let start = self.line_starts[position.line - 1];
+    let column = position.column.checked_sub(1)?;
+    line
+      .char_indices()
+      .map(|(i, _)| i)
+      .chain(std::iter::once(line.len()))
+      .nth(column)
+      .map(|i| start + i)
+  }
+}
+
+/// Identifies a file within a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// A range of bytes within one file of a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+  pub file: FileId,
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Where a span starts, in terms a reader can follow
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location<'s> {
+  pub file: &'s str,
+  pub position: Position,
+}
+
+impl fmt::Display for Location<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.file, self.position)
+  }
+}
+
+/// Every source file of a program, each known by the id it was added under
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+  files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+  pub fn new() -> Self {
+    SourceMap::default()
+  }
+
+  pub fn add(&mut self, file: SourceFile) -> FileId {
+    let id = FileId(u32::try_from(self.files.len()).expect("fewer than 2^32 files"));
+    self.files.push(file);
+    id
+  }
+
+  /// The file with `id`, which must have come from this map
+  pub fn file(&self, id: FileId) -> &SourceFile {
+    &self.files[id.0 as usize]
+  }
+
+  /// The file with `name`, most recently added first
+  pub fn find(&self, name: &str) -> Option<FileId> {
+    let index = self.files.iter().rposition(|file| file.name == name)?;
+    Some(FileId(index as u32))
+  }
+
+  pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+    (0..).map(FileId).zip(&self.files)
+  }
+
+  /// The text covered by a span
+  pub fn text(&self, span: Span) -> &str {
+    &self.file(span.file).text[span.start..span.end]
+  }
+
+  /// The file and position at which a span starts
+  pub fn location(&self, span: Span) -> Location<'_> {
+    let file = self.file(span.file);
+    Location {
+      file: &file.name,
+      position: file.position(span.start),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("x", 0, 1, 1)]
+  #[case("λx.x", 3, 1, 3)]
+  #[case("x\n  y", 4, 2, 3)]
+  #[case("x\n", 2, 2, 1)]
+  #[case("a\r\nb", 3, 2, 1)]
+  fn positions(
+    #[case] text: &str,
+    #[case] offset: usize,
+    #[case] line: usize,
+    #[case] column: usize,
+  ) {
+    let source = SourceFile::new("test", text);
+    let position = Position { line, column };
+    assert_eq!(source.position(offset), position);
+    assert_eq!(source.offset(position), Some(offset));
+  }
+
+  #[rstest]
+  #[case(Position { line: 0, column: 1 })]
+  #[case(Position { line: 1, column: 0 })]
+  #[case(Position { line: 1, column: 3 })]
+  #[case(Position { line: 3, column: 1 })]
+  fn offsets_outside_the_text(#[case] position: Position) {
+    assert_eq!(SourceFile::new("test", "x\ny").offset(position), None);
+  }
+
+  #[test]
+  fn lines() {
+    let source = SourceFile::new("test", "id = λx.x\r\n\nid y\n");
+    assert_eq!(
+      source.lines().collect::<Vec<_>>(),
+      [(1, "id = λx.x"), (2, ""), (3, "id y"), (4, "")]
+    );
+  }
+
+  #[test]
+  fn source_map() {
+    let mut map = SourceMap::new();
+    let prelude = map.add(SourceFile::new("prelude.lc", "id = λx.x\nk = λx.λy.x\n"));
+    let main = map.add(SourceFile::new("main.lc", "k id"));
+    assert_ne!(prelude, main);
+    assert_eq!(map.find("prelude.lc"), Some(prelude));
+    assert_eq!(map.find("missing.lc"), None);
+    let span = Span {
+      file: prelude,
+      start: 11,
+      end: 13,
+    };
+    assert_eq!(map.text(span), "k ");
+    assert_eq!(map.location(span).to_string(), "prelude.lc:2:1");
+    let span = Span {
+      file: main,
+      start: 2,
+      end: 4,
+    };
+    assert_eq!(map.text(span), "id");
+    assert_eq!(map.location(span).to_string(), "main.lc:1:3");
+  }
+}
@@ -0,0 +1,121 @@
+//! Interned names, compared and hashed as small integers
+//!
+//! An [`Interner`] gives each distinct name a [`Symbol`] the first time it is seen
+//! and the same one every time after, and turns a symbol back into its name. A
+//! symbol only means something to the interner that made it, so each
+//! [`Engine`](crate::Engine) keeps its own.
+//!
+//! So far only the names an engine defines are interned. Terms still name their
+//! variables with strings, so substitution and alpha-equivalence compare and hash
+//! those strings.
+//!
+//! ```
+//! # use camel::symbol::Interner;
+//! let mut names = Interner::new();
+//! let x = names.intern("x");
+//! assert_eq!(names.intern("x"), x);
+//! assert_ne!(names.intern("y"), x);
+//! assert_eq!(names.resolve(x), Some("x"));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A name interned by an `Interner`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+  /// The position of the name among those interned, counting from zero
+  pub fn index(self) -> usize {
+    self.0 as usize
+  }
+}
+
+/// Names and their symbols, in the order they were first interned
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+  names: Vec<Rc<str>>,
+  symbols: HashMap<Rc<str>, Symbol>,
+}
+
+impl Interner {
+  pub fn new() -> Self {
+    Interner::default()
+  }
+
+  /// The symbol for `name`, giving it a new one if it has none yet
+  ///
+  /// # Panics
+  ///
+  /// If more than `u32::MAX` distinct names are interned
+  pub fn intern(&mut self, name: &str) -> Symbol {
+    if let Some(&symbol) = self.symbols.get(name) {
+      return symbol;
+    }
+    let symbol = Symbol(u32::try_from(self.names.len()).expect("too many names to intern"));
+    let name: Rc<str> = Rc::from(name);
+    self.names.push(Rc::clone(&name));
+    self.symbols.insert(name, symbol);
+    symbol
+  }
+
+  /// The symbol for `name` if it has been interned, without interning it
+  pub fn get(&self, name: &str) -> Option<Symbol> {
+    self.symbols.get(name).copied()
+  }
+
+  /// The name of `symbol`, or `None` if it came from another interner
+  pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+    self.names.get(symbol.index()).map(|name| &**name)
+  }
+
+  pub fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+
+  /// Every name interned, with its symbol, in the order they were first interned
+  pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+    (0..).map(Symbol).zip(self.names.iter().map(|name| &**name))
+  }
+}
+
+impl fmt::Display for Symbol {
+  /// Written with its index, as `#3`, since only the interner knows its name
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "#{}", self.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn interns_names_once() {
+    let mut names = Interner::new();
+    let symbols: Vec<_> = ["x", "y", "x", "λ", "", "y"]
+      .into_iter()
+      .map(|name| names.intern(name))
+      .collect();
+    assert_eq!(
+      symbols.iter().map(|s| s.index()).collect::<Vec<_>>(),
+      [0, 1, 0, 2, 3, 1]
+    );
+    assert_eq!(names.len(), 4);
+    assert_eq!(names.get("λ"), Some(symbols[3]));
+    assert_eq!(names.get("z"), None);
+    let listed: Vec<_> = names.iter().map(|(_, name)| name).collect();
+    assert_eq!(listed, ["x", "y", "λ", ""]);
+    for symbol in symbols {
+      assert_eq!(names.get(names.resolve(symbol).unwrap()), Some(symbol));
+    }
+    assert_eq!(Interner::new().resolve(Symbol(0)), None);
+    assert_eq!(Symbol(3).to_string(), "#3");
+  }
+}
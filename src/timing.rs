@@ -0,0 +1,156 @@
+//! Where the time goes when running a term, broken down by phase
+//!
+//! [`PhaseTimes`] adds up the time spent in each phase, however many times it is
+//! entered, so one can be kept across a whole run and printed at the end:
+//!
+//! ```
+//! # use camel::timing::{self, Phase, PhaseTimes};
+//! # use camel::Evaluator;
+//! let mut times = PhaseTimes::default();
+//! let term = timing::parse("(λx.x) y", &mut times)?;
+//! let normal = times.time(Phase::Normalize, || Evaluator::default().eval(term.into()))?;
+//! let text = times.time(Phase::Print, || normal.to_string());
+//! assert_eq!(text, "y");
+//! println!("{}", times);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::ast::Node;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// The phases of running a term, in the order they happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+  /// Splitting the source into tokens
+  Lex,
+  /// Building a term from the tokens
+  Parse,
+  /// Reducing the term
+  Normalize,
+  /// Reading a number or other value back out of the result
+  Decode,
+  /// Writing out the result
+  Print,
+}
+
+impl Phase {
+  pub const ALL: [Phase; 5] = [
+    Phase::Lex,
+    Phase::Parse,
+    Phase::Normalize,
+    Phase::Decode,
+    Phase::Print,
+  ];
+}
+
+/// Time spent in each phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PhaseTimes {
+  times: [Duration; Phase::ALL.len()],
+}
+
+impl PhaseTimes {
+  pub fn get(&self, phase: Phase) -> Duration {
+    self.times[phase as usize]
+  }
+
+  pub fn add(&mut self, phase: Phase, elapsed: Duration) {
+    self.times[phase as usize] += elapsed;
+  }
+
+  /// Run `f`, counting the time it takes towards `phase`
+  pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    self.add(phase, start.elapsed());
+    result
+  }
+
+  pub fn total(&self) -> Duration {
+    self.times.iter().sum()
+  }
+
+  /// The percentage of the total time spent in `phase`, 0 if no time was spent
+  pub fn share(&self, phase: Phase) -> f64 {
+    let total = self.total();
+    if total.is_zero() {
+      return 0.0;
+    }
+    self.get(phase).as_secs_f64() / total.as_secs_f64() * 100.0
+  }
+}
+
+/// Parse `source` as a single term, timing lexing and parsing separately
+///
+/// The parser pulls tokens from the lexer as it goes, so the source is lexed again
+/// on its own to time lexing, and that time is taken off the time to parse
+pub fn parse<'inp>(source: &'inp str, times: &mut PhaseTimes) -> Result<Node<'inp>, anyhow::Error> {
+  let start = Instant::now();
+  let term = Parser::new(source).parse();
+  let both = start.elapsed();
+  let start = Instant::now();
+  let mut lexer = Lexer::new(source);
+  while lexer.next_token().is_some() {}
+  let lex = start.elapsed().min(both);
+  times.add(Phase::Lex, lex);
+  times.add(Phase::Parse, both - lex);
+  term
+}
+
+impl fmt::Display for Phase {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Phase::Lex => write!(f, "lex"),
+      Phase::Parse => write!(f, "parse"),
+      Phase::Normalize => write!(f, "normalize"),
+      Phase::Decode => write!(f, "decode"),
+      Phase::Print => write!(f, "print"),
+    }
+  }
+}
+
+impl fmt::Display for PhaseTimes {
+  /// A line for each phase with its time and share of the total, then the total
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let total = self.total();
+    for phase in Phase::ALL {
+      writeln!(
+        f,
+        "{:<10} {:>12.3}ms {:>6.1}%",
+        phase.to_string(),
+        self.get(phase).as_secs_f64() * 1000.0,
+        self.share(phase)
+      )?;
+    }
+    write!(
+      f,
+      "{:<10} {:>12.3}ms",
+      "total",
+      total.as_secs_f64() * 1000.0
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn adds_up_phases() -> Result<(), anyhow::Error> {
+    let mut times = PhaseTimes::default();
+    times.add(Phase::Decode, Duration::from_millis(2));
+    times.time(Phase::Decode, || ());
+    times.add(Phase::Print, Duration::from_millis(3));
+    assert!(times.get(Phase::Decode) >= Duration::from_millis(2));
+    assert!(times.total() >= Duration::from_millis(5));
+    assert!(parse("λx.(x", &mut times).is_err());
+    let text = times.to_string();
+    assert_eq!(text.lines().count(), Phase::ALL.len() + 1);
+    assert!(text.starts_with("lex") && text.lines().last().is_some_and(|l| l.starts_with("total")));
+    Ok(())
+  }
+}
@@ -1,21 +1,71 @@
-#[derive(Debug, Clone, PartialEq)]
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Token<'inp> {
   pub kind: TokenKind,
   pub text: &'inp str,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum TokenKind {
   LeftParen,
   RightParen,
   Lambda,
   Dot,
+  Equals,
   LowercaseId,
   Unknown,
 }
 
-#[derive(Debug, PartialEq)]
+impl TokenKind {
+  pub const ALL: [TokenKind; 7] = [
+    TokenKind::LeftParen,
+    TokenKind::RightParen,
+    TokenKind::Lambda,
+    TokenKind::Dot,
+    TokenKind::Equals,
+    TokenKind::LowercaseId,
+    TokenKind::Unknown,
+  ];
+}
+
+/// A set of token kinds, used to report everything the parser would have accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TokenSet(u16);
+
+impl TokenSet {
+  pub const fn new(kinds: &[TokenKind]) -> Self {
+    let mut bits = 0;
+    let mut i = 0;
+    while i < kinds.len() {
+      bits |= 1 << kinds[i] as u16;
+      i += 1;
+    }
+    TokenSet(bits)
+  }
+
+  pub fn contains(self, kind: TokenKind) -> bool {
+    self.0 & (1 << kind as u16) != 0
+  }
+
+  pub fn union(self, other: TokenSet) -> Self {
+    TokenSet(self.0 | other.0)
+  }
+
+  pub fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn iter(self) -> impl Iterator<Item = TokenKind> {
+    TokenKind::ALL
+      .into_iter()
+      .filter(move |&kind| self.contains(kind))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TokenError {
   pub kind: TokenKind,
   pub text: String,
@@ -29,3 +79,76 @@ impl From<Token<'_>> for TokenError {
     }
   }
 }
+
+impl fmt::Display for TokenKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TokenKind::LeftParen => write!(f, "'('"),
+      TokenKind::RightParen => write!(f, "')'"),
+      TokenKind::Lambda => write!(f, "lambda"),
+      TokenKind::Dot => write!(f, "'.'"),
+      TokenKind::Equals => write!(f, "'='"),
+      TokenKind::LowercaseId => write!(f, "identifier"),
+      TokenKind::Unknown => write!(f, "character"),
+    }
+  }
+}
+
+impl fmt::Display for TokenSet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let kinds: Vec<_> = self.iter().map(|kind| kind.to_string()).collect();
+    match kinds.as_slice() {
+      [] => write!(f, "nothing"),
+      [kind] => write!(f, "{}", kind),
+      _ => write!(f, "one of {}", kinds.join(", ")),
+    }
+  }
+}
+
+/// Describe a token by its kind, adding the text where the kind alone doesn't give it
+fn fmt_token(kind: TokenKind, text: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  match kind {
+    TokenKind::LowercaseId | TokenKind::Unknown => write!(f, "{} '{}'", kind, text),
+    _ => write!(f, "{}", kind),
+  }
+}
+
+impl fmt::Display for Token<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt_token(self.kind, self.text, f)
+  }
+}
+
+impl fmt::Display for TokenError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt_token(self.kind, &self.text, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case(Token { kind: TokenKind::LeftParen, text: "(" }, "'('")]
+  #[case(Token { kind: TokenKind::Lambda, text: "\\" }, "lambda")]
+  #[case(Token { kind: TokenKind::Dot, text: "." }, "'.'")]
+  #[case(Token { kind: TokenKind::LowercaseId, text: "foo" }, "identifier 'foo'")]
+  #[case(Token { kind: TokenKind::Unknown, text: "3" }, "character '3'")]
+  fn display(#[case] token: Token, #[case] expected_str: &str) {
+    assert_eq!(token.to_string(), expected_str);
+    assert_eq!(TokenError::from(token).to_string(), expected_str);
+  }
+
+  #[rstest]
+  #[case(&[], "nothing")]
+  #[case(&[TokenKind::Dot], "'.'")]
+  #[case(&[TokenKind::LowercaseId, TokenKind::LeftParen], "one of '(', identifier")]
+  fn display_set(#[case] kinds: &[TokenKind], #[case] expected_str: &str) {
+    let set = TokenSet::new(kinds);
+    assert_eq!(set.to_string(), expected_str);
+    assert!(kinds.iter().all(|&kind| set.contains(kind)));
+    assert_eq!(set.iter().count(), kinds.len());
+  }
+}